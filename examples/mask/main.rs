@@ -1,9 +1,6 @@
 use std::{fs, time::Instant};
 
-use image::Rgba;
-use nanorand::{Rng, WyRand};
-use palette::{Hsl, IntoColor, Pixel, Srgb};
-use rust_wcloud::{ChineseTokenizer, Word, WordCloud, WordCloudSize};
+use rust_wcloud::{random_hsl_color, ChineseTokenizer, WordCloud, WordCloudSize};
 
 pub fn main() {
     let wukong = include_str!("news.txt");
@@ -13,7 +10,9 @@ pub fn main() {
         .with_filter(&["一个"])
         .with_min_word_len(2);
 
-    let wordcloud = WordCloud::default().with_tokenizer(tokenlizer);
+    let wordcloud = WordCloud::default()
+        .with_tokenizer(tokenlizer)
+        .with_color_closure(random_hsl_color(0.0..360.0, 0.0..0.0, 0.4..1.0));
 
     for (i, path) in ["spin.jpg", "stormtrooper_mask.png", "avatar.jfif"]
         .iter()
@@ -26,21 +25,9 @@ pub fn main() {
 
         let mask = WordCloudSize::FromMask(mask_img);
 
-        let color_func = |_word: &Word, rng: &mut WyRand| {
-            let lightness = rng.generate_range(40..100);
-
-            let col = Hsl::new(1.0, 0.0, lightness as f32 / 100.0);
-            let rgb: Srgb = col.into_color();
-
-            let raw: [u8; 3] = rgb.into_format().into_raw();
-
-            Rgba([raw[0], raw[1], raw[2], 1])
-        };
-
         let now = Instant::now();
 
-        let wordcloud_image =
-            wordcloud.generate_from_text_with_color_func(wukong, mask, 1.0, color_func);
+        let wordcloud_image = wordcloud.generate_from_text(wukong, mask, 1.0);
         println!("Generated in {}ms", now.elapsed().as_millis());
         let i = i + 1;
         wordcloud_image
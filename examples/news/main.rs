@@ -30,7 +30,7 @@ pub fn main() {
     //     let raw: [u8; 3] = rgb.into_format()
     //         .into_raw();
 
-    //     Rgba([raw[0], raw[1], raw[2], 1])
+    //     Rgba([raw[0], raw[1], raw[2], 255])
     // };
 
     let now = Instant::now();
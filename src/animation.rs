@@ -0,0 +1,164 @@
+use std::time::Duration;
+
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, Frame,
+};
+
+use crate::{WordCloud, WordCloudError, WordCloudSize};
+
+/// Settings for `WordCloud::generate_gif_from_text`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GifOptions {
+    /// At most this many frames after the empty first one. Long word lists show several
+    /// words per frame to stay within it
+    pub max_frames: usize,
+    /// How long each frame is shown
+    pub frame_delay: Duration,
+    /// How long the finished cloud is shown before the animation starts over
+    pub final_delay: Duration,
+}
+
+impl Default for GifOptions {
+    fn default() -> Self {
+        GifOptions {
+            max_frames: 50,
+            frame_delay: Duration::from_millis(100),
+            final_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+impl WordCloud {
+    /// Panics if words were dropped under `DropPolicy::Error`, see `try_generate_gif_from_text`
+    pub fn generate_gif_from_text(
+        &self,
+        text: &str,
+        size: WordCloudSize,
+        scale: f32,
+        options: GifOptions,
+    ) -> Vec<u8> {
+        self.try_generate_gif_from_text(text, size, scale, options)
+            .expect("Unable to generate word cloud")
+    }
+
+    /// Encodes an animated GIF of the cloud building up, the words appear in the order they
+    /// were placed, starting from an empty canvas. The GIF loops forever
+    pub fn try_generate_gif_from_text(
+        &self,
+        text: &str,
+        size: WordCloudSize,
+        scale: f32,
+        options: GifOptions,
+    ) -> Result<Vec<u8>, WordCloudError> {
+        if options.max_frames == 0 {
+            return Err(WordCloudError::InvalidConfig(
+                "max_frames must be greater than 0".to_string(),
+            ));
+        }
+
+        let layout = self.generate_layout_from_text(text, size)?;
+        let mut scaled =
+            self.scale_layout(&layout, scale, |word, rng| self.word_color(word, rng))?;
+        // GIF 的宽高只有 16 位
+        if scaled.width > u16::MAX as u32 || scaled.height > u16::MAX as u32 {
+            return Err(WordCloudError::CanvasTooLarge {
+                width: scaled.width as f64,
+                height: scaled.height as f64,
+            });
+        }
+
+        // 每一帧多放一批词，再整张重画
+        let mut remaining = std::mem::take(&mut scaled.words).into_iter();
+        let words_per_frame = remaining.len().div_ceil(options.max_frames).max(1);
+
+        let mut gif = vec![];
+        {
+            let mut encoder = GifEncoder::new(&mut gif);
+            encoder
+                .set_repeat(Repeat::Infinite)
+                .map_err(WordCloudError::Encode)?;
+            loop {
+                let image = self.render_scaled_layout(&scaled)?;
+                let done = remaining.len() == 0;
+                let delay = if done {
+                    options.final_delay
+                } else {
+                    options.frame_delay
+                };
+                encoder
+                    .encode_frame(Frame::from_parts(
+                        image,
+                        0,
+                        0,
+                        Delay::from_saturating_duration(delay),
+                    ))
+                    .map_err(WordCloudError::Encode)?;
+
+                if done {
+                    break;
+                }
+                scaled
+                    .words
+                    .extend(remaining.by_ref().take(words_per_frame));
+            }
+        }
+
+        Ok(gif)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{codecs::gif::GifDecoder, AnimationDecoder, Rgba};
+
+    use super::GifOptions;
+    use crate::{WordCloud, WordCloudSize};
+
+    #[test]
+    fn gif_builds_up() {
+        let background = Rgba([0, 0, 0, 255]);
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_background_color(background);
+        let size = || WordCloudSize::FromDimensions {
+            width: 200,
+            height: 100,
+        };
+        let text = "apple apple apple banana banana cherry date elderberry";
+        let words = wordcloud
+            .generate_layout_from_text(text, size())
+            .unwrap()
+            .words
+            .len();
+
+        let options = GifOptions {
+            max_frames: 3,
+            ..GifOptions::default()
+        };
+        let gif = wordcloud.generate_gif_from_text(text, size(), 1.0, options);
+        let frames = GifDecoder::new(gif.as_slice())
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+
+        assert_eq!(frames.len(), words.div_ceil(words.div_ceil(3)) + 1);
+        assert!(frames.len() <= 4);
+        assert_eq!(frames[0].buffer().dimensions(), (200, 100));
+        assert!(frames[0].buffer().pixels().all(|px| *px == background));
+
+        let ink = |i: usize| {
+            frames[i]
+                .buffer()
+                .pixels()
+                .filter(|px| **px != background)
+                .count()
+        };
+        for i in 1..frames.len() {
+            assert!(ink(i) > ink(i - 1));
+        }
+        let (numerator, _) = frames.last().unwrap().delay().numer_denom_ms();
+        assert_eq!(numerator, 2000);
+    }
+}
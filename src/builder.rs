@@ -0,0 +1,508 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use ab_glyph::{FontVec, Point};
+use image::{GrayImage, Rgba};
+use nanorand::WyRand;
+
+use crate::{
+    font_from_collection, Align, ChineseTokenizer, ColorProfile, ColorStrategy, DrawOrder,
+    DropPolicy, GlyphStyle, GradientSpec, MissingGlyphPolicy, PathWord, PlacementOrder, Rect,
+    ResourceLimits, ShapeBias, SizeStrategy, TextShadow, WatermarkSpec, Word, WordCloud,
+    WordCloudError,
+};
+
+/// Collects the settings of a `WordCloud` and checks them all at once in `build`, instead of
+/// panicking in the `with_*` setters or during generation
+pub struct WordCloudBuilder {
+    cloud: WordCloud,
+    /// 加载字体时的错误，`build` 时返回
+    font_error: Option<WordCloudError>,
+}
+
+impl WordCloud {
+    pub fn builder() -> WordCloudBuilder {
+        WordCloudBuilder {
+            cloud: WordCloud::default(),
+            font_error: None,
+        }
+    }
+}
+
+impl WordCloudBuilder {
+    pub fn tokenizer(mut self, value: ChineseTokenizer) -> Self {
+        self.cloud.tokenizer = value;
+        self
+    }
+
+    pub fn font(mut self, value: FontVec) -> Self {
+        self.cloud = self.cloud.with_font(value);
+        self.font_error = None;
+        self
+    }
+
+    /// Loads the face at `index` of a font file or TrueType collection. `build` fails if the
+    /// file can't be read or has no such face
+    pub fn font_from_path_indexed(mut self, path: impl AsRef<Path>, index: u32) -> Self {
+        let path = path.as_ref();
+        let font = fs::read(path)
+            .map_err(|err| {
+                WordCloudError::InvalidConfig(format!(
+                    "Unable to read font file {}: {err}",
+                    path.display()
+                ))
+            })
+            .and_then(|data| font_from_collection(data, index));
+        match font {
+            Ok(font) => {
+                self.cloud = self.cloud.with_collection_font(font, index);
+                self.font_error = None;
+            }
+            Err(err) => self.font_error = Some(err),
+        }
+        self
+    }
+
+    pub fn font_variation(mut self, axis: &str, value: f32) -> Self {
+        self.cloud = self.cloud.with_font_variation(axis, value);
+        self
+    }
+
+    pub fn frequency_font_variation(mut self, axis: &str, min: f32, max: f32) -> Self {
+        self.cloud = self.cloud.with_frequency_font_variation(axis, min, max);
+        self
+    }
+
+    pub fn background_color(mut self, value: Rgba<u8>) -> Self {
+        self.cloud.background_color = value;
+        self
+    }
+
+    pub fn background_gradient(mut self, value: GradientSpec) -> Self {
+        self.cloud.background_gradient = Some(value);
+        self
+    }
+
+    pub fn watermark(mut self, value: WatermarkSpec) -> Self {
+        self.cloud.watermark = Some(value);
+        self
+    }
+
+    pub fn min_font_size(mut self, value: f32) -> Self {
+        self.cloud.min_font_size = value;
+        self
+    }
+
+    pub fn max_font_size(mut self, value: f32) -> Self {
+        self.cloud.max_font_size = Some(value);
+        self
+    }
+
+    pub fn max_font_size_ratio(mut self, value: f32) -> Self {
+        self.cloud.max_font_size_ratio = Some(value);
+        self
+    }
+
+    /// Clamped to 0.1 - 0.95 like `WordCloud::with_first_word_height_fraction`
+    pub fn first_word_height_fraction(mut self, value: f32) -> Self {
+        self.cloud.first_word_height_fraction = Some(value.clamp(0.1, 0.95));
+        self
+    }
+
+    pub fn tab_width(mut self, value: usize) -> Self {
+        self.cloud.tab_width = value;
+        self
+    }
+
+    pub fn missing_glyph_policy(mut self, value: MissingGlyphPolicy) -> Self {
+        self.cloud.missing_glyph_policy = value;
+        self
+    }
+
+    pub fn letter_spacing(mut self, value: f32) -> Self {
+        self.cloud.letter_spacing = value;
+        self
+    }
+
+    pub fn text_align(mut self, value: Align) -> Self {
+        self.cloud.text_align = value;
+        self
+    }
+
+    pub fn placement_order(mut self, value: PlacementOrder) -> Self {
+        self.cloud.placement_order = value;
+        self
+    }
+
+    pub fn size_strategy(mut self, value: SizeStrategy) -> Self {
+        self.cloud.size_strategy = value;
+        self
+    }
+
+    pub fn color_strategy(mut self, value: ColorStrategy) -> Self {
+        self.cloud.color_strategy = value;
+        self
+    }
+
+    pub fn draw_order(mut self, value: DrawOrder) -> Self {
+        self.cloud.draw_order = value;
+        self
+    }
+
+    pub fn output_dimensions(mut self, width: u32, height: u32) -> Self {
+        self.cloud.output_dimensions = Some((width, height));
+        self
+    }
+
+    pub fn supersample(mut self, factor: u32) -> Self {
+        self.cloud.supersample = factor;
+        self
+    }
+
+    pub fn flatten_color(mut self, value: Rgba<u8>) -> Self {
+        self.cloud.flatten_color = Some(value);
+        self
+    }
+
+    pub fn output_color_profile(mut self, value: ColorProfile) -> Self {
+        self.cloud.output_color_profile = value;
+        self
+    }
+
+    pub fn font_step(mut self, value: f32) -> Self {
+        self.cloud.font_step = value;
+        self
+    }
+
+    pub fn relocate_before_shrink_below(mut self, value: f32) -> Self {
+        self.cloud.relocate_threshold = Some(value);
+        self
+    }
+
+    pub fn repeat_max_copies(mut self, value: usize) -> Self {
+        self.cloud.repeat_max_copies = Some(value);
+        self
+    }
+
+    pub fn min_same_word_distance(mut self, value: f32) -> Self {
+        self.cloud.min_same_word_distance = value;
+        self
+    }
+
+    pub fn occupancy_threshold(mut self, value: f32) -> Self {
+        self.cloud.occupancy_threshold = value;
+        self
+    }
+
+    pub fn shape_bias(mut self, value: ShapeBias) -> Self {
+        self.cloud.shape_bias = Some(value);
+        self
+    }
+
+    pub fn auto_enlarge(mut self, value: bool) -> Self {
+        self.cloud.auto_enlarge = value;
+        self
+    }
+
+    pub fn placement_candidates(mut self, value: usize) -> Self {
+        self.cloud.placement_candidates = value;
+        self
+    }
+
+    pub fn fill_empty_space(mut self, value: bool) -> Self {
+        self.cloud.fill_empty_space = value;
+        self
+    }
+
+    pub fn word_margin(mut self, value: u32) -> Self {
+        self.cloud.visual_margin = value;
+        self.cloud.collision_margin = value as i32;
+        self
+    }
+
+    pub fn tight_margin(mut self, value: i32) -> Self {
+        self.cloud = self.cloud.with_tight_margin(value);
+        self
+    }
+
+    pub fn visual_margin(mut self, value: u32) -> Self {
+        self.cloud.visual_margin = value;
+        self
+    }
+
+    pub fn collision_margin(mut self, value: u32) -> Self {
+        self.cloud.collision_margin = value as i32;
+        self
+    }
+
+    pub fn frequency_scaled_margin(mut self, base: u32, scale: f32) -> Self {
+        self.cloud = self.cloud.with_frequency_scaled_margin(base, scale);
+        self
+    }
+
+    pub fn word_rotate_chance(mut self, value: f64) -> Self {
+        self.cloud.word_rotate_chance = value;
+        self
+    }
+
+    pub fn relative_font_scaling(mut self, value: f32) -> Self {
+        self.cloud.relative_font_scaling = value;
+        self
+    }
+
+    pub fn rng_seed(mut self, value: u64) -> Self {
+        self.cloud.rng_seed = Some(value);
+        self
+    }
+
+    pub fn seed_str(mut self, value: &str) -> Self {
+        self.cloud = self.cloud.with_seed_str(value);
+        self
+    }
+
+    pub fn deterministic_seed(mut self, value: bool) -> Self {
+        self.cloud.deterministic_seed = value;
+        self
+    }
+
+    pub fn preview_scale(mut self, value: f32) -> Self {
+        self.cloud.preview_scale = value;
+        self
+    }
+
+    pub fn color_closure(
+        mut self,
+        value: impl Fn(&Word, &mut WyRand) -> Rgba<u8> + Send + Sync + 'static,
+    ) -> Self {
+        self.cloud.color_func = Some(Box::new(value));
+        self
+    }
+
+    pub fn word_colors(mut self, value: HashMap<String, Rgba<u8>>) -> Self {
+        self.cloud.word_colors = value;
+        self
+    }
+
+    pub fn drop_policy(mut self, value: DropPolicy) -> Self {
+        self.cloud.drop_policy = value;
+        self
+    }
+
+    pub fn text_shadow(mut self, value: TextShadow) -> Self {
+        self.cloud.text_shadow = Some(value);
+        self
+    }
+
+    pub fn mask_invert(mut self, value: bool) -> Self {
+        self.cloud.mask_invert = value;
+        self
+    }
+
+    pub fn mask_edge_margin(mut self, value: u32) -> Self {
+        self.cloud.mask_edge_margin = value;
+        self
+    }
+
+    pub fn mask_threshold(mut self, value: u8) -> Self {
+        self.cloud.mask_threshold = value;
+        self
+    }
+
+    pub fn balance_across_regions(mut self, value: bool) -> Self {
+        self.cloud.balance_across_regions = value;
+        self
+    }
+
+    pub fn pixel_clip_to_mask(mut self, value: bool) -> Self {
+        self.cloud.pixel_clip_to_mask = value;
+        self
+    }
+
+    pub fn exclusion_mask(mut self, value: GrayImage) -> Self {
+        self.cloud.exclusion_mask = Some(value);
+        self
+    }
+
+    pub fn rotation_decider(
+        mut self,
+        value: impl Fn(&str, f32) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.cloud.rotation_decider = Some(Box::new(value));
+        self
+    }
+
+    pub fn rotation_range(mut self, min_deg: f32, max_deg: f32, step_deg: f32) -> Self {
+        self.cloud.rotation_range = Some((min_deg, max_deg, step_deg));
+        self
+    }
+
+    pub fn bidirectional_rotation(mut self, value: bool) -> Self {
+        self.cloud.bidirectional_rotation = value;
+        self
+    }
+
+    pub fn placement_filter(
+        mut self,
+        value: impl Fn(&str, &Point, &Rect) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.cloud.placement_filter = Some(Box::new(value));
+        self
+    }
+
+    pub fn glyph_style(mut self, value: GlyphStyle) -> Self {
+        self.cloud.glyph_style = value;
+        self
+    }
+
+    pub fn global_alpha(mut self, value: f32) -> Self {
+        self.cloud.global_alpha = value;
+        self
+    }
+
+    pub fn max_canvas_bytes(mut self, value: usize) -> Self {
+        self.cloud.max_canvas_bytes = value;
+        self
+    }
+
+    pub fn resource_limits(mut self, value: ResourceLimits) -> Self {
+        self.cloud.resource_limits = value;
+        self
+    }
+
+    pub fn progress(mut self, value: impl FnMut(f32) + Send + 'static) -> Self {
+        self.cloud = self.cloud.with_progress(value);
+        self
+    }
+
+    pub fn path_word(mut self, value: PathWord) -> Self {
+        self.cloud.path_words.push(value);
+        self
+    }
+
+    /// Returns the first invalid setting as `WordCloudError::InvalidConfig`
+    pub fn build(self) -> Result<WordCloud, WordCloudError> {
+        if let Some(err) = self.font_error {
+            return Err(err);
+        }
+        let cloud = self.cloud;
+        let invalid = |reason: &str| Err(WordCloudError::InvalidConfig(reason.to_string()));
+
+        if cloud.min_font_size.is_nan() || cloud.min_font_size <= 0.0 {
+            return invalid("min_font_size must be greater than 0");
+        }
+        if cloud
+            .background_gradient
+            .as_ref()
+            .is_some_and(|gradient| gradient.stops.is_empty())
+        {
+            return invalid("background_gradient needs at least one stop");
+        }
+        if let Some(max_font_size) = cloud.max_font_size {
+            if max_font_size.is_nan() || cloud.min_font_size > max_font_size {
+                return invalid("min_font_size must not be greater than max_font_size");
+            }
+        }
+        if let Some(threshold) = cloud.relocate_threshold {
+            if threshold.is_nan() || threshold < cloud.min_font_size {
+                return invalid("relocate_before_shrink_below must not be less than min_font_size");
+            }
+        }
+        if let Some(ratio) = cloud.max_font_size_ratio {
+            if ratio.is_nan() || ratio <= 0.0 {
+                return invalid("max_font_size_ratio must be greater than 0");
+            }
+        }
+        if cloud.min_same_word_distance.is_nan() || cloud.min_same_word_distance < 0.0 {
+            return invalid("min_same_word_distance must not be negative");
+        }
+        if !cloud.frequency_margin_scale.is_finite() || cloud.frequency_margin_scale < 0.0 {
+            return invalid("frequency_scaled_margin scale must be a finite number of at least 0");
+        }
+        if !(0.0..=1.0).contains(&cloud.occupancy_threshold) {
+            return invalid("occupancy_threshold must be between 0 and 1");
+        }
+        if !(0.0..=1.0).contains(&cloud.global_alpha) {
+            return invalid("global_alpha must be between 0 and 1");
+        }
+        if cloud.supersample == 0 {
+            return invalid("supersample must be at least 1");
+        }
+        if cloud.placement_candidates == 0 {
+            return invalid("placement_candidates must be greater than 0");
+        }
+        if !cloud.letter_spacing.is_finite() {
+            return invalid("letter_spacing must be a finite number");
+        }
+        if cloud.font_step.is_nan() || cloud.font_step <= 0.0 {
+            return invalid("font_step must be greater than 0");
+        }
+        if !(0.0..=1.0).contains(&cloud.word_rotate_chance) {
+            return invalid("word_rotate_chance must be between 0 and 1");
+        }
+        if !(0.0..=1.0).contains(&cloud.relative_font_scaling) {
+            return invalid("relative_font_scaling must be between 0 and 1");
+        }
+        if cloud.preview_scale.is_nan() || cloud.preview_scale <= 0.0 || cloud.preview_scale > 1.0 {
+            return invalid("preview_scale must be greater than 0 and at most 1");
+        }
+        if let Some(shadow) = &cloud.text_shadow {
+            if shadow.blur.is_nan() || shadow.blur < 0.0 {
+                return invalid("text_shadow blur must not be negative");
+            }
+        }
+        if let Some((width, height)) = cloud.output_dimensions {
+            if width == 0 || height == 0 {
+                return invalid("output_dimensions must not be 0");
+            }
+        }
+        if let Some((min, max, step)) = cloud.rotation_range {
+            if !(min.is_finite() && max.is_finite() && min <= max) {
+                return invalid("rotation_range min must not be greater than max");
+            }
+            if step.is_nan() || step <= 0.0 {
+                return invalid("rotation_range step must be greater than 0");
+            }
+        }
+        if let GlyphStyle::Outline { width } = cloud.glyph_style {
+            if width.is_nan() || width <= 0.0 {
+                return invalid("outline width must be greater than 0");
+            }
+        }
+
+        if cloud
+            .path_words
+            .iter()
+            .any(|word| word.font_size.is_nan() || word.font_size <= 0.0)
+        {
+            return invalid("path word font_size must be greater than 0");
+        }
+
+        Ok(cloud)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_validates_settings() {
+        assert!(WordCloud::builder()
+            .min_font_size(8.0)
+            .max_font_size(64.0)
+            .word_rotate_chance(0.5)
+            .build()
+            .is_ok());
+
+        let errors = [
+            WordCloud::builder()
+                .min_font_size(32.0)
+                .max_font_size(16.0)
+                .build(),
+            WordCloud::builder().word_rotate_chance(1.5).build(),
+            WordCloud::builder().font_step(0.0).build(),
+        ];
+        for result in errors {
+            assert!(matches!(result, Err(WordCloudError::InvalidConfig(_))));
+        }
+    }
+}
@@ -0,0 +1,376 @@
+use std::ops::Range;
+
+use image::Rgba;
+use nanorand::{Rng, WyRand};
+use palette::{Hsl, IntoColor, Lch, Mix, Pixel, Srgb};
+
+use crate::Word;
+
+/// 在给定范围内随机取 HSL 颜色，hue 是角度 (0.0 - 360.0)，saturation 和 lightness 是 0.0 - 1.0。
+/// 输出的 alpha 总是 255
+pub fn random_hsl_color(
+    hue: Range<f32>,
+    saturation: Range<f32>,
+    lightness: Range<f32>,
+) -> impl Fn(&Word, &mut WyRand) -> Rgba<u8> + Send + Sync + 'static {
+    move |_, rng| {
+        let col = Hsl::new(
+            sample(rng, &hue),
+            sample(rng, &saturation),
+            sample(rng, &lightness),
+        );
+
+        hsl_to_rgba(col)
+    }
+}
+
+/// How `generate_from_text` colors words when no color closure is set
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorStrategy {
+    /// A random hue for every word, drawn from the layout's random number generator. Placing
+    /// more or fewer words changes the colors of all of them
+    #[default]
+    Random,
+    /// The hue comes from a hash of `Word::text`, so a word has the same color in every cloud,
+    /// whatever else is in it and whatever the seed
+    Hashed,
+}
+
+impl ColorStrategy {
+    pub(crate) fn color(&self, word: &Word, rng: &mut WyRand) -> Rgba<u8> {
+        match self {
+            ColorStrategy::Random => {
+                let hue: u8 = rng.generate_range(0..255);
+                hsl_to_rgba(Hsl::new(hue as f32, 1.0, 0.5))
+            }
+            ColorStrategy::Hashed => {
+                let hash =
+                    crate::wordcloud::fnv1a(crate::wordcloud::FNV_OFFSET, word.text.as_bytes());
+                hsl_to_rgba(Hsl::new((hash % 360) as f32, 1.0, 0.5))
+            }
+        }
+    }
+}
+
+/// The color space colors are interpolated in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Straight blend of the red, green and blue channels
+    Srgb,
+    /// Blends the hue around the color wheel, like `random_hsl_color`
+    #[default]
+    Hsl,
+    /// Perceptually even blends, no muddy colors in the middle of a gradient
+    Lch,
+}
+
+impl ColorSpace {
+    /// 在 `from` 和 `to` 之间插值，alpha 总是线性插值
+    pub fn mix(&self, from: Rgba<u8>, to: Rgba<u8>, t: f32) -> Rgba<u8> {
+        let t = t.clamp(0.0, 1.0);
+        let alpha = from.0[3] as f32 + (to.0[3] as f32 - from.0[3] as f32) * t;
+        let (from_rgb, to_rgb) = (rgba_to_srgb(from), rgba_to_srgb(to));
+
+        let mixed: Srgb = match self {
+            ColorSpace::Srgb => Srgb::new(
+                from_rgb.red + (to_rgb.red - from_rgb.red) * t,
+                from_rgb.green + (to_rgb.green - from_rgb.green) * t,
+                from_rgb.blue + (to_rgb.blue - from_rgb.blue) * t,
+            ),
+            ColorSpace::Hsl => {
+                let (from, to): (Hsl, Hsl) = (from_rgb.into_color(), to_rgb.into_color());
+                from.mix(&to, t).into_color()
+            }
+            ColorSpace::Lch => {
+                let (from, to): (Lch, Lch) = (from_rgb.into_color(), to_rgb.into_color());
+                from.mix(&to, t).into_color()
+            }
+        };
+
+        let raw: [u8; 3] = mixed.into_format().into_raw();
+        Rgba([raw[0], raw[1], raw[2], alpha.round() as u8])
+    }
+}
+
+/// Colors words along a gradient through `stops`, from the first stop for the least frequent
+/// words to the last one for the most frequent, interpolated in `space`
+pub fn frequency_gradient(
+    stops: Vec<Rgba<u8>>,
+    space: ColorSpace,
+) -> impl Fn(&Word, &mut WyRand) -> Rgba<u8> + Send + Sync + 'static {
+    assert!(!stops.is_empty(), "A gradient needs at least one stop");
+
+    move |word, _| gradient_at(&stops, space, word.frequency)
+}
+
+/// Colors words along a gradient through `stops` by their rank instead of their frequency, so
+/// the colors spread evenly over a long tail of rare words. The most frequent word gets the
+/// last stop and the `total`-th word the first one, `total` being the number of words drawn
+/// (`layout.words.len()`, or `max_words` if every word fits)
+pub fn rank_gradient(
+    stops: Vec<Rgba<u8>>,
+    space: ColorSpace,
+    total: usize,
+) -> impl Fn(&Word, &mut WyRand) -> Rgba<u8> + Send + Sync + 'static {
+    assert!(!stops.is_empty(), "A gradient needs at least one stop");
+
+    // 排名 0 是最常见的词
+    let last = total.saturating_sub(1).max(1) as f32;
+    move |word, _| gradient_at(&stops, space, 1.0 - word.index as f32 / last)
+}
+
+/// Gives every word a random color from the gradient through `stops`, interpolated in `space`
+pub fn random_gradient_color(
+    stops: Vec<Rgba<u8>>,
+    space: ColorSpace,
+) -> impl Fn(&Word, &mut WyRand) -> Rgba<u8> + Send + Sync + 'static {
+    assert!(!stops.is_empty(), "A gradient needs at least one stop");
+
+    move |_, rng| gradient_at(&stops, space, rng.generate::<f32>())
+}
+
+/// A gradient the background is filled with, see `WordCloud::with_background_gradient`
+#[derive(Clone, Debug, PartialEq)]
+pub struct GradientSpec {
+    pub kind: GradientKind,
+    /// Evenly spaced from the start of the gradient to its end, at least one
+    pub stops: Vec<Rgba<u8>>,
+    pub space: ColorSpace,
+}
+
+/// The shape of a `GradientSpec`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GradientKind {
+    /// Runs across the canvas from the first stop to the last, `angle` degrees
+    /// counterclockwise from left to right
+    Linear { angle: f32 },
+    /// Runs from the first stop in the center to the last in the corners
+    Radial,
+}
+
+impl GradientSpec {
+    pub fn linear(stops: Vec<Rgba<u8>>, angle: f32) -> Self {
+        GradientSpec {
+            kind: GradientKind::Linear { angle },
+            stops,
+            space: ColorSpace::default(),
+        }
+    }
+
+    pub fn radial(stops: Vec<Rgba<u8>>) -> Self {
+        GradientSpec {
+            kind: GradientKind::Radial,
+            stops,
+            space: ColorSpace::default(),
+        }
+    }
+
+    pub fn with_space(mut self, value: ColorSpace) -> Self {
+        self.space = value;
+        self
+    }
+
+    /// 宽 `width` 高 `height` 的画布上像素 (x, y) 的颜色
+    pub(crate) fn color_at(&self, x: u32, y: u32, width: u32, height: u32) -> Rgba<u8> {
+        let (half_width, half_height) = (width as f32 / 2.0, height as f32 / 2.0);
+        // 像素中心到画布中心
+        let (dx, dy) = (x as f32 + 0.5 - half_width, y as f32 + 0.5 - half_height);
+
+        let t = match self.kind {
+            GradientKind::Linear { angle } => {
+                // 画布的 y 朝下，逆时针是 -sin
+                let (sin, cos) = angle.to_radians().sin_cos();
+                let extent = (half_width * cos).abs() + (half_height * sin).abs();
+                if extent == 0.0 {
+                    0.0
+                } else {
+                    0.5 + (dx * cos - dy * sin) / (extent * 2.0)
+                }
+            }
+            GradientKind::Radial => {
+                let extent = half_width.hypot(half_height);
+                if extent == 0.0 {
+                    0.0
+                } else {
+                    dx.hypot(dy) / extent
+                }
+            }
+        };
+
+        gradient_at(&self.stops, self.space, t)
+    }
+}
+
+/// 多个色标之间均匀分布，`t` 在 0.0 - 1.0
+fn gradient_at(stops: &[Rgba<u8>], space: ColorSpace, t: f32) -> Rgba<u8> {
+    if stops.len() == 1 {
+        return stops[0];
+    }
+
+    let position = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+    let i = (position.floor() as usize).min(stops.len() - 2);
+
+    space.mix(stops[i], stops[i + 1], position - i as f32)
+}
+
+fn rgba_to_srgb(color: Rgba<u8>) -> Srgb {
+    Srgb::new(color.0[0], color.0[1], color.0[2]).into_format()
+}
+
+pub(crate) fn hsl_to_rgba(col: Hsl) -> Rgba<u8> {
+    let rgb: Srgb = col.into_color();
+    let raw: [u8; 3] = rgb.into_format().into_raw();
+
+    Rgba([raw[0], raw[1], raw[2], 255])
+}
+
+fn sample(rng: &mut WyRand, range: &Range<f32>) -> f32 {
+    range.start + rng.generate::<f32>() * (range.end - range.start)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use image::Rgba;
+    use nanorand::WyRand;
+
+    use super::{
+        gradient_at, random_hsl_color, rank_gradient, ColorSpace, ColorStrategy, GradientSpec,
+    };
+    use crate::{ChineseTokenizer, WordCloud, WordCloudSize};
+
+    #[test]
+    fn gradient() {
+        let (red, blue) = (Rgba([255, 0, 0, 255]), Rgba([0, 0, 255, 255]));
+        let stops = [red, Rgba([0, 255, 0, 255]), blue];
+
+        for space in [ColorSpace::Srgb, ColorSpace::Hsl, ColorSpace::Lch] {
+            assert_eq!(gradient_at(&stops, space, 0.0), red);
+            assert_eq!(gradient_at(&stops, space, 1.0), blue);
+        }
+        assert_eq!(
+            ColorSpace::Srgb.mix(red, blue, 0.5),
+            Rgba([128, 0, 128, 255])
+        );
+        // 色相走最短的路，红到蓝中间是品红
+        assert_eq!(
+            ColorSpace::Hsl.mix(red, blue, 0.5),
+            Rgba([255, 0, 255, 255])
+        );
+    }
+
+    #[test]
+    fn gradient_spec() {
+        let (black, white) = (Rgba([0, 0, 0, 255]), Rgba([255, 255, 255, 255]));
+        let brightness = |spec: &GradientSpec, x, y| spec.color_at(x, y, 100, 50).0[0];
+
+        let left_to_right = GradientSpec::linear(vec![black, white], 0.0);
+        assert!(brightness(&left_to_right, 0, 25) < 5);
+        assert!(brightness(&left_to_right, 99, 25) > 250);
+        assert_eq!(
+            brightness(&left_to_right, 10, 0),
+            brightness(&left_to_right, 10, 49)
+        );
+
+        let bottom_to_top = GradientSpec::linear(vec![black, white], 90.0);
+        assert!(brightness(&bottom_to_top, 50, 49) < 5);
+        assert!(brightness(&bottom_to_top, 50, 0) > 250);
+
+        let radial = GradientSpec::radial(vec![black, white]);
+        assert!(brightness(&radial, 50, 25) < 5);
+        assert!(brightness(&radial, 0, 0) > 245);
+    }
+
+    #[test]
+    fn hashed_colors() {
+        let text = "经济 经济 经济 发展 发展 改革 开放 创新 合作";
+        let colors = |max_words, seed| {
+            let wordcloud = WordCloud::default()
+                .with_rng_seed(seed)
+                .with_color_strategy(ColorStrategy::Hashed)
+                .with_tokenizer(ChineseTokenizer::default().with_max_words(max_words));
+            wordcloud
+                .generate(
+                    text,
+                    WordCloudSize::FromDimensions {
+                        width: 400,
+                        height: 200,
+                    },
+                    1.0,
+                    |word, rng| wordcloud.word_color(word, rng),
+                )
+                .words
+                .into_iter()
+                .map(|word| (word.text, word.color))
+                .collect::<HashMap<_, _>>()
+        };
+
+        let few = colors(2, 1);
+        let many = colors(6, 2);
+        assert_eq!(few.len(), 2);
+        assert_eq!(many.len(), 6);
+        for (text, color) in &few {
+            assert_eq!(many[text], *color, "{text}");
+        }
+        assert_ne!(many["经济"], many["发展"]);
+    }
+
+    #[test]
+    fn rank_gradient_spreads_evenly() {
+        let wordcloud = WordCloud::default().with_rng_seed(1);
+        let layout = wordcloud
+            .generate_layout_from_text(
+                "one one one one one one one one two three four five",
+                WordCloudSize::FromDimensions {
+                    width: 400,
+                    height: 200,
+                },
+            )
+            .unwrap();
+        let (red, blue) = (Rgba([255, 0, 0, 255]), Rgba([0, 0, 255, 255]));
+        let color = rank_gradient(vec![red, blue], ColorSpace::Srgb, layout.words.len());
+        let mut rng = WyRand::new_seed(0);
+
+        let colors = layout
+            .words
+            .iter()
+            .map(|word| color(word, &mut rng))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            colors,
+            [
+                blue,
+                Rgba([64, 0, 191, 255]),
+                Rgba([128, 0, 128, 255]),
+                Rgba([191, 0, 64, 255]),
+                red,
+            ]
+        );
+    }
+
+    #[test]
+    fn random_hsl() {
+        let wordcloud = WordCloud::default().with_rng_seed(1);
+        let layout = wordcloud
+            .generate_layout_from_text(
+                "apple apple banana cherry",
+                WordCloudSize::FromDimensions {
+                    width: 200,
+                    height: 100,
+                },
+            )
+            .unwrap();
+        // 色相在绿色附近
+        let color = random_hsl_color(100.0..140.0, 0.8..1.0, 0.4..0.6);
+        let mut rng = WyRand::new_seed(0);
+
+        for word in &layout.words {
+            for _ in 0..20 {
+                let Rgba([r, g, b, a]) = color(word, &mut rng);
+                assert_eq!(a, 255);
+                assert!(g > r && g > b, "{r} {g} {b}");
+            }
+        }
+    }
+}
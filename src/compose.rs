@@ -0,0 +1,73 @@
+use image::{imageops, Rgba, RgbaImage};
+
+/// Arranges word cloud images into a grid of `cols` columns, `gap` pixels apart. Every cell
+/// is as large as the largest image and smaller images are centered in their cell
+pub fn compose_grid(
+    clouds: &[RgbaImage],
+    cols: usize,
+    gap: u32,
+    background: Rgba<u8>,
+) -> RgbaImage {
+    assert!(cols > 0, "A grid needs at least one column");
+
+    if clouds.is_empty() {
+        return RgbaImage::new(0, 0);
+    }
+
+    let cell_width = clouds.iter().map(|cloud| cloud.width()).max().unwrap_or(0);
+    let cell_height = clouds.iter().map(|cloud| cloud.height()).max().unwrap_or(0);
+    let cols = cols.min(clouds.len());
+    let rows = clouds.len().div_ceil(cols);
+
+    let width = cols as u32 * cell_width + (cols as u32 - 1) * gap;
+    let height = rows as u32 * cell_height + (rows as u32 - 1) * gap;
+    let mut buffer = RgbaImage::from_pixel(width, height, background);
+
+    for (i, cloud) in clouds.iter().enumerate() {
+        let (col, row) = ((i % cols) as u32, (i / cols) as u32);
+        let x = col * (cell_width + gap) + (cell_width - cloud.width()) / 2;
+        let y = row * (cell_height + gap) + (cell_height - cloud.height()) / 2;
+
+        imageops::replace(&mut buffer, cloud, x as i64, y as i64);
+    }
+
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::compose_grid;
+
+    #[test]
+    fn grid() {
+        let (red, blue, white) = (
+            Rgba([255, 0, 0, 255]),
+            Rgba([0, 0, 255, 255]),
+            Rgba([255, 255, 255, 255]),
+        );
+        let clouds = [
+            RgbaImage::from_pixel(4, 4, red),
+            RgbaImage::from_pixel(2, 2, blue),
+            RgbaImage::from_pixel(4, 2, red),
+        ];
+
+        // 两列两行，每格 4x4，中间空 1 像素
+        let grid = compose_grid(&clouds, 2, 1, white);
+        assert_eq!(grid.dimensions(), (9, 9));
+        assert_eq!(*grid.get_pixel(0, 0), red);
+        assert_eq!(*grid.get_pixel(4, 0), white);
+        // 小图在格子里居中
+        assert_eq!(*grid.get_pixel(5, 0), white);
+        assert_eq!(*grid.get_pixel(6, 1), blue);
+        assert_eq!(*grid.get_pixel(7, 2), blue);
+        assert_eq!(*grid.get_pixel(0, 5), white);
+        assert_eq!(*grid.get_pixel(0, 6), red);
+        assert_eq!(*grid.get_pixel(5, 6), white);
+
+        // 列数比图多时只有一行
+        assert_eq!(compose_grid(&clouds, 5, 0, white).dimensions(), (12, 4));
+        assert_eq!(compose_grid(&[], 2, 1, white).dimensions(), (0, 0));
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use image::{Rgba, RgbaImage};
+
+use crate::{WordCloud, WordCloudSize};
+
+/// 占比变化不超过这个比例算作没变
+const UNCHANGED_TOLERANCE: f32 = 0.1;
+
+/// How a word's share of the text changed between two texts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WordChange {
+    Increased,
+    Decreased,
+    New,
+    Unchanged,
+    Removed,
+}
+
+impl WordChange {
+    fn color(&self) -> Rgba<u8> {
+        match self {
+            WordChange::Increased => Rgba([46, 204, 113, 255]),
+            WordChange::Decreased => Rgba([231, 76, 60, 255]),
+            WordChange::New => Rgba([52, 152, 219, 255]),
+            WordChange::Unchanged | WordChange::Removed => Rgba([149, 165, 166, 255]),
+        }
+    }
+}
+
+/// Compares the share of every word in `baseline` and `current`, so texts of different
+/// lengths can be compared. Shares within 10% of each other count as unchanged
+pub fn word_changes<'a>(
+    baseline: &HashMap<&'a str, usize>,
+    current: &HashMap<&'a str, usize>,
+) -> HashMap<&'a str, WordChange> {
+    let baseline_total = baseline.values().sum::<usize>().max(1) as f32;
+    let current_total = current.values().sum::<usize>().max(1) as f32;
+
+    let mut changes = HashMap::with_capacity(baseline.len().max(current.len()));
+    for (word, count) in current {
+        let change = match baseline.get(word) {
+            None => WordChange::New,
+            Some(baseline_count) => {
+                let before = *baseline_count as f32 / baseline_total;
+                let after = *count as f32 / current_total;
+
+                if (after - before).abs() <= before * UNCHANGED_TOLERANCE {
+                    WordChange::Unchanged
+                } else if after > before {
+                    WordChange::Increased
+                } else {
+                    WordChange::Decreased
+                }
+            }
+        };
+        changes.insert(*word, change);
+    }
+    for word in baseline.keys() {
+        changes.entry(*word).or_insert(WordChange::Removed);
+    }
+
+    changes
+}
+
+impl WordCloud {
+    /// Draws the words of `current_text` colored by how they changed since `baseline_text`:
+    /// green for increased, red for decreased, blue for new and gray for unchanged. Words that
+    /// are gone are drawn in gray at their old size
+    pub fn generate_diff(
+        &self,
+        baseline_text: &str,
+        current_text: &str,
+        size: WordCloudSize,
+        scale: f32,
+    ) -> RgbaImage {
+        let baseline = self.tokenizer.get_word_frequencies(baseline_text);
+        let current = self.tokenizer.get_word_frequencies(current_text);
+        let changes = word_changes(&baseline, &current);
+
+        // 已经消失的词按旧的次数排
+        let frequencies = changes
+            .keys()
+            .map(|word| {
+                let count = current.get(word).or_else(|| baseline.get(word));
+                (word.to_string(), count.copied().unwrap_or(0))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let layout = self
+            .generate_layout_from_frequencies(&frequencies, size)
+            .expect("Unable to generate word cloud");
+
+        self.render_layout(&layout, scale, |word, _| {
+            changes
+                .get(word.text)
+                .map_or(WordChange::Unchanged, |change| *change)
+                .color()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{word_changes, WordChange};
+
+    #[test]
+    fn changes() {
+        let baseline = HashMap::from([("rust", 4), ("go", 4), ("java", 2), ("perl", 10)]);
+        let current = HashMap::from([("rust", 8), ("go", 2), ("java", 2), ("zig", 8)]);
+
+        let changes = word_changes(&baseline, &current);
+        assert_eq!(changes["rust"], WordChange::Increased);
+        assert_eq!(changes["go"], WordChange::Decreased);
+        assert_eq!(changes["java"], WordChange::Unchanged);
+        assert_eq!(changes["zig"], WordChange::New);
+        assert_eq!(changes["perl"], WordChange::Removed);
+    }
+}
@@ -0,0 +1,62 @@
+use std::fmt;
+
+use image::ImageError;
+
+use crate::Limit;
+
+#[derive(Debug)]
+pub enum WordCloudError {
+    /// 有词没能放进画布，`DropPolicy::Error` 时返回
+    WordsDropped(Vec<String>),
+    /// 最常见的词用 `min_font_size` 横竖都放不进画布，`DropPolicy::Error` 时返回
+    WordTooLarge(String),
+    /// `WordCloudBuilder::build` 时发现的无效设置
+    InvalidConfig(String),
+    /// 画布超出 `u32` 或者 `max_canvas_bytes`
+    CanvasTooLarge { width: f64, height: f64 },
+    /// 超出了 `with_resource_limits` 设置的限制
+    LimitExceeded { which: Limit },
+    /// 编码或者写出图片失败
+    Encode(ImageError),
+}
+
+impl fmt::Display for WordCloudError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WordCloudError::WordsDropped(words) => {
+                write!(
+                    f,
+                    "{} words could not be placed: {}",
+                    words.len(),
+                    words.join(", ")
+                )
+            }
+            WordCloudError::WordTooLarge(word) => write!(
+                f,
+                "The most frequent word \"{word}\" doesn't fit the canvas even at min_font_size"
+            ),
+            WordCloudError::InvalidConfig(reason) => write!(f, "Invalid configuration: {reason}"),
+            WordCloudError::CanvasTooLarge { width, height } => {
+                write!(f, "A canvas of {width}x{height} pixels is too large")
+            }
+            WordCloudError::LimitExceeded { which } => {
+                let limit = match which {
+                    Limit::CanvasBytes => "canvas size",
+                    Limit::Words => "word count",
+                    Limit::GenerationTime => "generation time",
+                };
+                write!(f, "The {limit} limit was exceeded")
+            }
+            WordCloudError::Encode(err) => write!(f, "Unable to encode the image: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for WordCloudError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WordCloudError::Encode(err) => Some(err),
+            _ => None,
+        }
+    }
+}
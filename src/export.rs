@@ -0,0 +1,471 @@
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use image::{
+    codecs::{bmp::BmpEncoder, jpeg::JpegEncoder},
+    error::{ImageFormatHint, UnsupportedError},
+    imageops::FilterType,
+    ColorType, ImageError, ImageFormat, ImageResult, Rgb, RgbImage, Rgba, RgbaImage,
+};
+
+use crate::{text, Layout, WordCloud, WordCloudError, WordCloudSize};
+
+/// `generate_and_encode` 每次画多少行
+const STRIP_HEIGHT: u32 = 64;
+
+/// Which color space PNGs are tagged with, see `WordCloud::with_output_color_profile`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorProfile {
+    /// An sRGB chunk, the space the colors are made in
+    #[default]
+    Srgb,
+    /// No color space chunks, viewers pick one themselves
+    Untagged,
+}
+
+/// Composites `image` over an opaque `color`, for formats without an alpha channel. The alpha
+/// of `color` is ignored
+pub fn flatten(image: &RgbaImage, color: Rgba<u8>) -> RgbImage {
+    RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        let mut pixel = Rgba([color.0[0], color.0[1], color.0[2], 255]);
+        text::blend(&mut pixel, *image.get_pixel(x, y), 1.0);
+        Rgb([pixel.0[0], pixel.0[1], pixel.0[2]])
+    })
+}
+
+/// Draws `image` for a 24-bit color terminal, `cols` characters wide, e.g. for a quick preview.
+/// Every character is an upper half block covering two pixels, the upper one in the
+/// foreground color and the lower one in the background color. Transparent pixels are
+/// composited over black. Every line ends by resetting the colors
+pub fn render_to_ansi(image: &RgbaImage, cols: u32) -> String {
+    let (width, height) = image.dimensions();
+    if cols == 0 || width == 0 || height == 0 {
+        return String::new();
+    }
+
+    // 一个字符两行像素，按宽的比例缩放
+    let rows = ((height as f64 * cols as f64 / width as f64 / 2.0).round() as u32).max(1);
+    let flat = flatten(image, Rgba([0, 0, 0, 255]));
+    let scaled = if (cols, rows * 2) == (width, height) {
+        flat
+    } else {
+        image::imageops::resize(&flat, cols, rows * 2, FilterType::Triangle)
+    };
+
+    let mut ansi = String::new();
+    for row in 0..rows {
+        for x in 0..cols {
+            let Rgb([r, g, b]) = *scaled.get_pixel(x, row * 2);
+            let Rgb([br, bg, bb]) = *scaled.get_pixel(x, row * 2 + 1);
+            let _ = write!(
+                ansi,
+                "\x1b[38;2;{r};{g};{b}m\x1b[48;2;{br};{bg};{bb}m\u{2580}"
+            );
+        }
+        ansi.push_str("\x1b[0m\n");
+    }
+
+    ansi
+}
+
+/// Builds the `<area>` elements of an HTML image map over the words of `layout`, one per word
+/// and one per line. `{word}` in `url_template` is replaced by the percent-encoded word, e.g.
+/// `https://example.com/search?q={word}`. The coordinates are the words' bounding boxes in
+/// the pixels of an image rendered at `scale`, e.g. by `render_layout`, the whole rotated
+/// rectangle for rotated words. With `with_output_dimensions` use the placements of
+/// `WordCloud::generate` instead. Wrap them in `<map name="...">` and point the `usemap` of
+/// the image at it
+pub fn layout_to_image_map(layout: &Layout, scale: f32, url_template: &str) -> String {
+    let mut html = String::new();
+    for word in &layout.words {
+        let (width, height) =
+            text::rotated_dimensions(word.glyphs.width, word.glyphs.height, word.angle);
+        // 两个角分别缩放再取整，相邻的词之间不会空出或者重叠一个像素
+        let corner = |x: f32, y: f32| ((x * scale).round() as i64, (y * scale).round() as i64);
+        let (left, top) = corner(word.position.x, word.position.y);
+        let (right, bottom) = corner(
+            word.position.x + width as f32,
+            word.position.y + height as f32,
+        );
+        let href = url_template.replace("{word}", &percent_encode(word.text));
+
+        let _ = writeln!(
+            html,
+            r#"<area shape="rect" coords="{},{},{},{}" href="{}" alt="{}">"#,
+            left,
+            top,
+            right,
+            bottom,
+            escape_html(&href),
+            escape_html(word.text),
+        );
+    }
+    html
+}
+
+/// 只留下 URL 里不用转义的字符，其他的按 UTF-8 字节转成 %XX
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => {
+                let _ = write!(encoded, "%{:02X}", byte);
+            }
+        }
+    }
+    encoded
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl WordCloud {
+    /// Saves a generated image, the format is chosen from the extension of `path`. JPEG and
+    /// BMP have no alpha, so the image is flattened onto the flatten color first, see
+    /// `with_flatten_color`. PNGs are tagged like in `write_png`
+    pub fn save_image(&self, image: &RgbaImage, path: impl AsRef<Path>) -> ImageResult<()> {
+        let path = path.as_ref();
+        match ImageFormat::from_path(path)? {
+            ImageFormat::Png => {
+                let file = File::create(path).map_err(ImageError::IoError)?;
+                self.write_png(image, BufWriter::new(file))
+            }
+            format @ (ImageFormat::Jpeg | ImageFormat::Bmp) => {
+                let color = self.flatten_color.unwrap_or(self.background_color);
+                flatten(image, color).save_with_format(path, format)
+            }
+            format => image.save_with_format(path, format),
+        }
+    }
+
+    /// Encodes `image` as a PNG tagged with the output color profile, see
+    /// `with_output_color_profile`
+    pub fn write_png(&self, image: &RgbaImage, writer: impl Write) -> ImageResult<()> {
+        let png_error = |err: png::EncodingError| ImageError::IoError(err.into());
+        let mut png = self
+            .png_encoder(writer, image.width(), image.height())
+            .write_header()
+            .map_err(png_error)?;
+        png.write_image_data(image.as_raw()).map_err(png_error)?;
+        png.finish().map_err(png_error)
+    }
+
+    fn png_encoder<W: Write>(
+        &self,
+        writer: W,
+        width: u32,
+        height: u32,
+    ) -> png::Encoder<'static, W> {
+        let mut encoder = png::Encoder::new(writer, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        // 颜色都是按 sRGB 算的，标上以后不同的程序显示出来一样
+        if self.output_color_profile == ColorProfile::Srgb {
+            encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+        }
+        encoder
+    }
+
+    /// Generates a word cloud from `text` and writes it to `writer` encoded as `format`, e.g.
+    /// straight to a file or socket. PNGs are drawn and encoded a strip of rows at a time, so
+    /// neither the whole image nor the whole file is held in memory and only a strip has to fit
+    /// in `max_canvas_bytes`. JPEG and BMP are drawn whole and flattened like in `save_image`.
+    /// Other formats fail with `WordCloudError::Encode`
+    pub fn generate_and_encode<W: Write>(
+        &self,
+        text: &str,
+        size: WordCloudSize,
+        scale: f32,
+        writer: W,
+        format: ImageFormat,
+    ) -> Result<(), WordCloudError> {
+        let layout = self.generate_layout_from_text(text, size)?;
+        let scaled = self.scale_layout(&layout, scale, |word, rng| self.word_color(word, rng))?;
+        let (width, height) = (scaled.width, scaled.height);
+        let encode_error = |err: io::Error| WordCloudError::Encode(ImageError::IoError(err));
+
+        match format {
+            ImageFormat::Png => {
+                let mut png = self
+                    .png_encoder(writer, width, height)
+                    .write_header()
+                    .map_err(|err| encode_error(err.into()))?;
+                let mut stream = png
+                    .stream_writer()
+                    .map_err(|err| encode_error(err.into()))?;
+
+                for y in (0..height).step_by(STRIP_HEIGHT as usize) {
+                    let strip = self.render_scaled_region(
+                        &scaled,
+                        0,
+                        y,
+                        width,
+                        STRIP_HEIGHT.min(height - y),
+                    )?;
+                    stream.write_all(strip.as_raw()).map_err(encode_error)?;
+                }
+
+                stream.finish().map_err(|err| encode_error(err.into()))?;
+                png.finish().map_err(|err| encode_error(err.into()))
+            }
+            ImageFormat::Jpeg | ImageFormat::Bmp => {
+                let image = self.render_scaled_layout(&scaled)?;
+                let color = self.flatten_color.unwrap_or(self.background_color);
+                let image = flatten(&image, color);
+
+                let mut writer = writer;
+                let result = if format == ImageFormat::Jpeg {
+                    JpegEncoder::new(&mut writer).encode_image(&image)
+                } else {
+                    BmpEncoder::new(&mut writer).encode(
+                        image.as_raw(),
+                        width,
+                        height,
+                        ColorType::Rgb8,
+                    )
+                };
+                result.map_err(WordCloudError::Encode)
+            }
+            format => Err(WordCloudError::Encode(ImageError::Unsupported(
+                UnsupportedError::from(ImageFormatHint::Exact(format)),
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{ImageFormat, Rgb, Rgba, RgbaImage};
+
+    use super::{flatten, layout_to_image_map, render_to_ansi, ColorProfile};
+    use crate::{text, WordCloud, WordCloudError, WordCloudSize};
+
+    #[test]
+    fn flatten_blends_over_color() {
+        let mut image = RgbaImage::new(4, 1);
+        image.put_pixel(0, 0, Rgba([0, 0, 0, 0]));
+        image.put_pixel(1, 0, Rgba([255, 0, 0, 255]));
+        image.put_pixel(2, 0, Rgba([255, 0, 0, 128]));
+        image.put_pixel(3, 0, Rgba([0, 0, 255, 64]));
+
+        let white = flatten(&image, Rgba([255, 255, 255, 255]));
+        assert_eq!(white.get_pixel(0, 0), &Rgb([255, 255, 255]));
+        assert_eq!(white.get_pixel(1, 0), &Rgb([255, 0, 0]));
+        assert_eq!(white.get_pixel(2, 0), &Rgb([255, 127, 127]));
+        assert_eq!(white.get_pixel(3, 0), &Rgb([191, 191, 255]));
+
+        // 透明的背景色也当作不透明的
+        let black = flatten(&image, Rgba([0, 0, 0, 0]));
+        assert_eq!(black.get_pixel(0, 0), &Rgb([0, 0, 0]));
+        assert_eq!(black.get_pixel(2, 0), &Rgb([128, 0, 0]));
+        assert_eq!(black.get_pixel(3, 0), &Rgb([0, 0, 64]));
+    }
+
+    #[test]
+    fn ansi() {
+        let (red, blue) = (Rgba([255, 0, 0, 255]), Rgba([0, 0, 255, 255]));
+        let image = RgbaImage::from_fn(3, 4, |_, y| if y % 2 == 0 { red } else { blue });
+
+        let ansi = render_to_ansi(&image, 3);
+        let lines = ansi.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        let cell = "\x1b[38;2;255;0;0m\x1b[48;2;0;0;255m\u{2580}";
+        assert_eq!(lines[0], format!("{}\x1b[0m", cell.repeat(3)));
+        assert_eq!(lines[0], lines[1]);
+
+        // 缩小后保持宽高比，透明的地方是黑的
+        let ansi = render_to_ansi(&RgbaImage::new(200, 100), 40);
+        assert_eq!(ansi.lines().count(), 10);
+        assert!(ansi.starts_with("\x1b[38;2;0;0;0m\x1b[48;2;0;0;0m"));
+        assert_eq!(render_to_ansi(&image, 0), "");
+    }
+
+    #[test]
+    fn generate_and_encode() {
+        let wordcloud = WordCloud::default().with_rng_seed(1);
+        let size = || WordCloudSize::FromDimensions {
+            width: 200,
+            height: 150,
+        };
+        let text = "apple apple apple banana banana cherry date";
+        let encode = |format| {
+            let mut encoded = vec![];
+            wordcloud
+                .generate_and_encode(text, size(), 1.0, &mut encoded, format)
+                .map(|_| encoded)
+        };
+
+        // 分条画出来的和整张画的一样
+        let png = image::load_from_memory(&encode(ImageFormat::Png).unwrap()).unwrap();
+        assert_eq!(
+            png.into_rgba8(),
+            wordcloud.generate_from_text(text, size(), 1.0)
+        );
+
+        let jpeg = image::load_from_memory(&encode(ImageFormat::Jpeg).unwrap()).unwrap();
+        assert_eq!((jpeg.width(), jpeg.height()), (200, 150));
+
+        assert!(matches!(
+            encode(ImageFormat::Tiff),
+            Err(WordCloudError::Encode(_))
+        ));
+    }
+
+    #[test]
+    fn write_png_keeps_alpha() {
+        // 命令行没给 --output 时就是这样写到 stdout 的
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_transparent_background();
+        let image = wordcloud.generate_from_text(
+            "apple apple banana",
+            WordCloudSize::FromDimensions {
+                width: 100,
+                height: 50,
+            },
+            1.0,
+        );
+        let mut written = vec![];
+        wordcloud.write_png(&image, &mut written).unwrap();
+
+        let decoded = image::load_from_memory(&written).unwrap();
+        assert_eq!(decoded.color(), image::ColorType::Rgba8);
+        let decoded = decoded.into_rgba8();
+        assert_eq!(decoded, image);
+        assert!(decoded.pixels().any(|px| px.0[3] == 0));
+        assert!(decoded.pixels().any(|px| px.0[3] == 255));
+    }
+
+    #[test]
+    fn color_profile() {
+        let srgb = |png: &[u8]| {
+            let reader = png::Decoder::new(png).read_info().unwrap();
+            reader.info().srgb
+        };
+        let size = || WordCloudSize::FromDimensions {
+            width: 100,
+            height: 50,
+        };
+        let text = "apple apple banana";
+
+        for (profile, tagged) in [(ColorProfile::Srgb, true), (ColorProfile::Untagged, false)] {
+            let wordcloud = WordCloud::default()
+                .with_rng_seed(1)
+                .with_output_color_profile(profile);
+            let mut streamed = vec![];
+            wordcloud
+                .generate_and_encode(text, size(), 1.0, &mut streamed, ImageFormat::Png)
+                .unwrap();
+            let image = wordcloud.generate_from_text(text, size(), 1.0);
+            let mut written = vec![];
+            wordcloud.write_png(&image, &mut written).unwrap();
+
+            for png in [&streamed, &written] {
+                assert_eq!(srgb(png).is_some(), tagged);
+                assert_eq!(image::load_from_memory(png).unwrap().into_rgba8(), image);
+            }
+        }
+    }
+
+    #[test]
+    fn image_map() {
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_word_rotate_chance(1.0);
+        let layout = wordcloud
+            .generate_layout_from_text(
+                "苹果 苹果 apple",
+                WordCloudSize::FromDimensions {
+                    width: 300,
+                    height: 200,
+                },
+            )
+            .unwrap();
+
+        let map = layout_to_image_map(&layout, 1.0, "https://example.com/?q={word}&lang=zh");
+        let lines = map.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), layout.words.len());
+        assert!(map.contains(
+            r#"href="https://example.com/?q=%E8%8B%B9%E6%9E%9C&amp;lang=zh" alt="苹果""#
+        ));
+
+        // 转过的词用转过后的外框
+        let word = &layout.words[0];
+        assert!(word.rotated);
+        let (width, height) =
+            text::rotated_dimensions(word.glyphs.width, word.glyphs.height, word.angle);
+        let (left, top) = (
+            word.position.x.round() as i64,
+            word.position.y.round() as i64,
+        );
+        let coords = format!(
+            r#"coords="{},{},{},{}""#,
+            left,
+            top,
+            left + width as i64,
+            top + height as i64
+        );
+        assert!(lines[0].contains(&coords));
+    }
+    #[test]
+    fn image_map_scaled() {
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_word_rotate_chance(0.5);
+        let size = || WordCloudSize::FromDimensions {
+            width: 200,
+            height: 100,
+        };
+        let text = "apple apple apple banana banana cherry date";
+        let layout = wordcloud.generate_layout_from_text(text, size()).unwrap();
+        let result = wordcloud.generate(text, size(), 2.0, |_, _| Rgba([255, 0, 0, 255]));
+
+        // 和放大两倍画出来的图里每个词的外框对得上
+        let map = layout_to_image_map(&layout, 2.0, "{word}");
+        let lines = map.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), result.words.len());
+        for (line, placement) in lines.iter().zip(&result.words) {
+            let coords = line
+                .split(r#"coords=""#)
+                .nth(1)
+                .and_then(|rest| rest.split('"').next())
+                .unwrap()
+                .split(',')
+                .map(|value| value.parse::<i64>().unwrap())
+                .collect::<Vec<_>>();
+            let (left, top) = (
+                placement.position.x.round() as i64,
+                placement.position.y.round() as i64,
+            );
+            let expected = [
+                left,
+                top,
+                left + placement.width as i64,
+                top + placement.height as i64,
+            ];
+            for (got, expected) in coords.iter().zip(expected) {
+                assert!((got - expected).abs() <= 2, "{line} {expected:?}");
+            }
+            assert!(line.contains(&format!(r#"alt="{}""#, placement.text)));
+        }
+    }
+}
@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+
+use crate::CasingPref;
+
+/// 衰减到比这还少的词直接去掉，不然一直衰减下去的旧词永远留在表里
+const MIN_COUNT: f32 = 0.01;
+
+/// Word counts merged from several texts, e.g. the chapters of a book, with the counts of
+/// each text multiplied by a weight before they are added up
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrequencyMap {
+    /// 每种写法分开记，开了 `consistent_case` 时合并以后再选写法
+    counts: HashMap<String, f32>,
+    /// 开了 `consistent_case` 时按小写合并的次数，查询时不用每次重新分组
+    case_groups: HashMap<String, f32>,
+    /// 每种写法第一次出现在第几个文本里，`CasingPref::FirstSeen` 要用
+    first_seen: HashMap<String, usize>,
+    texts: usize,
+    consistent_case: bool,
+}
+
+impl FrequencyMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decides the casing of each word once over the merged counts of all texts: every casing
+    /// keeps its own count and the word is shown with the sum of all of them, in the casing
+    /// the tokenizer's `with_casing_preference` picks when a cloud is generated. Add the counts
+    /// of each text with `ChineseTokenizer::get_word_frequencies_by_case_from_reader`, because
+    /// the tokenizer's usual counts already picked a casing for that text alone.
+    /// `CasingPref::FirstSeen` picks a casing from the earliest text it was added with
+    pub fn with_consistent_word_case_across_corpus(mut self, value: bool) -> Self {
+        self.consistent_case = value;
+        self.regroup();
+        self
+    }
+
+    /// Adds the counts of one text, e.g. from `ChineseTokenizer::get_word_frequencies_from_reader`,
+    /// each multiplied by `weight`. Panics if `weight` is negative or not finite
+    pub fn add(&mut self, frequencies: &HashMap<String, usize>, weight: f32) {
+        assert!(
+            weight.is_finite() && weight >= 0.0,
+            "Frequency weight must be a finite number of at least 0"
+        );
+
+        for (word, count) in frequencies {
+            self.add_count(word, *count as f32 * weight);
+        }
+        self.texts += 1;
+    }
+
+    /// Adds all the counts of `other`, each multiplied by `weight`. Panics like `add`
+    pub fn merge(&mut self, other: &FrequencyMap, weight: f32) {
+        assert!(
+            weight.is_finite() && weight >= 0.0,
+            "Frequency weight must be a finite number of at least 0"
+        );
+
+        for (word, count) in &other.counts {
+            self.add_count(word, count * weight);
+        }
+        self.texts += 1;
+    }
+
+    fn add_count(&mut self, word: &str, count: f32) {
+        *self.counts.entry(word.to_string()).or_insert(0.0) += count;
+        self.first_seen
+            .entry(word.to_string())
+            .or_insert(self.texts);
+        if self.consistent_case {
+            *self.case_groups.entry(word.to_lowercase()).or_insert(0.0) += count;
+        }
+    }
+
+    /// Multiplies every count by `factor` (0.0 - 1.0), for a rolling window over a stream:
+    /// decay before `add`ing each new text and older words fade out. Words whose count drops
+    /// below 0.01 are removed. Panics if `factor` is not between 0.0 and 1.0
+    pub fn decay_frequencies(&mut self, factor: f32) {
+        assert!(
+            (0.0..=1.0).contains(&factor),
+            "Decay factor must be between 0 and 1"
+        );
+
+        self.counts.retain(|_, count| {
+            *count *= factor;
+            *count >= MIN_COUNT
+        });
+        self.first_seen
+            .retain(|word, _| self.counts.contains_key(word));
+        self.regroup();
+    }
+
+    /// 按现在的次数重新按小写合并
+    fn regroup(&mut self) {
+        self.case_groups.clear();
+        if self.consistent_case {
+            for (word, count) in &self.counts {
+                *self.case_groups.entry(word.to_lowercase()).or_insert(0.0) += count;
+            }
+        }
+    }
+
+    /// The weighted count of `word`. With `with_consistent_word_case_across_corpus` the
+    /// counts of all its casings added up, looked up in any casing
+    pub fn get(&self, word: &str) -> Option<f32> {
+        if self.consistent_case {
+            self.case_groups.get(&word.to_lowercase()).copied()
+        } else {
+            self.counts.get(word).copied()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        if self.consistent_case {
+            self.case_groups.len()
+        } else {
+            self.counts.len()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Every word and its weighted count. With `with_consistent_word_case_across_corpus`
+    /// each word is shown in its most frequent casing
+    pub fn iter(&self) -> impl Iterator<Item = (&str, f32)> {
+        self.cased_counts(CasingPref::MostFrequent).into_iter()
+    }
+
+    /// 开了 `consistent_case` 时按小写合并，每个词用 `preference` 选的写法，一样时用次数多的，
+    /// 再一样时按字符串选，结果不受 HashMap 顺序影响
+    pub(crate) fn cased_counts(&self, preference: CasingPref) -> Vec<(&str, f32)> {
+        if !self.consistent_case {
+            return self
+                .counts
+                .iter()
+                .map(|(word, count)| (word.as_str(), *count))
+                .collect();
+        }
+
+        let first_seen = |word: &str| self.first_seen.get(word).copied();
+        let mut shown = HashMap::<String, (&str, f32)>::new();
+        for (word, count) in &self.counts {
+            let (best, best_count) = shown.entry(word.to_lowercase()).or_insert((word, *count));
+            let better = preference
+                .compare(word, best, first_seen)
+                .then(best_count.total_cmp(count))
+                .then((*best).cmp(word.as_str()))
+                .is_lt();
+            if better {
+                (*best, *best_count) = (word, *count);
+            }
+        }
+
+        shown
+            .into_iter()
+            .map(|(lower, (word, _))| (word, self.case_groups[&lower]))
+            .collect()
+    }
+}
+
+impl From<&HashMap<String, usize>> for FrequencyMap {
+    fn from(frequencies: &HashMap<String, usize>) -> Self {
+        let mut map = FrequencyMap::new();
+        map.add(frequencies, 1.0);
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::FrequencyMap;
+    use crate::{CasingPref, ChineseTokenizer, WordCloud, WordCloudSize};
+
+    #[test]
+    fn decay_frequencies() {
+        let mut map = FrequencyMap::new();
+        map.add(&HashMap::from([("old".to_string(), 10)]), 1.0);
+        for _ in 0..3 {
+            map.decay_frequencies(0.5);
+            map.add(&HashMap::from([("new".to_string(), 2)]), 1.0);
+        }
+        assert_eq!(map.get("old"), Some(1.25));
+        assert_eq!(map.get("new"), Some(3.5));
+
+        // 一直没有再出现的词最后会被去掉
+        for _ in 0..8 {
+            map.decay_frequencies(0.5);
+        }
+        assert_eq!(map.get("old"), None);
+        assert!(map.get("new").is_some());
+        map.decay_frequencies(0.0);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn consistent_case() {
+        let tokenizer = ChineseTokenizer::default();
+        let first = "Rust Rust Rust rust go";
+        let second = "rust rust rust Go";
+        // 每个文本自己选写法时，合并以后同一个词有两种写法
+        let mut separate = FrequencyMap::new();
+        for text in [first, second] {
+            let counts = tokenizer
+                .get_word_frequencies_from_reader(text.as_bytes())
+                .unwrap();
+            separate.add(&counts, 1.0);
+        }
+        assert_eq!(separate.get("Rust"), Some(4.0));
+        assert_eq!(separate.get("rust"), Some(3.0));
+
+        let mut merged = FrequencyMap::new().with_consistent_word_case_across_corpus(true);
+        for text in [first, second] {
+            let counts = tokenizer
+                .get_word_frequencies_by_case_from_reader(text.as_bytes())
+                .unwrap();
+            assert_eq!(counts.len(), if text == first { 3 } else { 2 });
+            merged.add(&counts, 1.0);
+        }
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged.get("rust"), Some(7.0));
+        assert_eq!(merged.get("Rust"), Some(7.0));
+        // 次数一样时选的写法是固定的
+        assert_eq!(merged.get("go"), Some(2.0));
+        let mut words: Vec<_> = merged.iter().collect();
+        words.sort_by(|a, b| a.0.cmp(b.0));
+        assert_eq!(words, vec![("go", 2.0), ("rust", 7.0)]);
+
+        // 生成时按分词器的写法偏好选
+        let mut acronyms = FrequencyMap::new().with_consistent_word_case_across_corpus(true);
+        for text in ["NASA", "nasa nasa nasa"] {
+            let counts = tokenizer
+                .get_word_frequencies_by_case_from_reader(text.as_bytes())
+                .unwrap();
+            acronyms.add(&counts, 1.0);
+        }
+        let shown = |preference| {
+            let wordcloud = WordCloud::default()
+                .with_rng_seed(1)
+                .with_tokenizer(ChineseTokenizer::default().with_casing_preference(preference));
+            let layout = wordcloud
+                .generate_layout_from_frequency_map(
+                    &acronyms,
+                    WordCloudSize::FromDimensions {
+                        width: 200,
+                        height: 100,
+                    },
+                )
+                .unwrap();
+            (layout.words[0].text.to_string(), layout.words[0].frequency)
+        };
+        assert_eq!(shown(CasingPref::MostFrequent), ("nasa".to_string(), 1.0));
+        assert_eq!(
+            shown(CasingPref::PreferUppercase),
+            ("NASA".to_string(), 1.0)
+        );
+        assert_eq!(shown(CasingPref::FirstSeen), ("NASA".to_string(), 1.0));
+    }
+
+    #[test]
+    fn weighted_merge() {
+        let first = HashMap::from([("apple".to_string(), 3), ("fig".to_string(), 1)]);
+        let second = HashMap::from([("apple".to_string(), 1), ("kiwi".to_string(), 4)]);
+
+        let mut map = FrequencyMap::new();
+        map.add(&first, 1.0);
+        map.add(&second, 2.0);
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get("apple"), Some(5.0));
+        assert_eq!(map.get("fig"), Some(1.0));
+        assert_eq!(map.get("kiwi"), Some(8.0));
+
+        let mut merged = FrequencyMap::from(&first);
+        merged.merge(&map, 0.5);
+        assert_eq!(merged.get("apple"), Some(5.5));
+        assert_eq!(merged.get("kiwi"), Some(4.0));
+
+        // 加权以后 kiwi 最多
+        let wordcloud = WordCloud::default().with_rng_seed(1);
+        let layout = wordcloud
+            .generate_layout_from_frequency_map(
+                &map,
+                WordCloudSize::FromDimensions {
+                    width: 200,
+                    height: 100,
+                },
+            )
+            .unwrap();
+        assert_eq!(layout.words[0].text, "kiwi");
+        assert_eq!(layout.words[0].frequency, 1.0);
+    }
+    #[test]
+    fn zero_weights() {
+        let counts = HashMap::from([("apple".to_string(), 3), ("fig".to_string(), 1)]);
+        let size = || WordCloudSize::FromDimensions {
+            width: 200,
+            height: 100,
+        };
+        let wordcloud = WordCloud::default().with_rng_seed(1);
+
+        // 权重全是 0 时没有词，不会因为除以 0 出错
+        let mut map = FrequencyMap::new();
+        map.add(&counts, 0.0);
+        let layout = wordcloud
+            .generate_layout_from_frequency_map(&map, size())
+            .unwrap();
+        assert!(layout.words.is_empty());
+
+        // 只有权重是 0 的那部分不画
+        map.add(&HashMap::from([("kiwi".to_string(), 2)]), 1.0);
+        let layout = wordcloud
+            .generate_layout_from_frequency_map(&map, size())
+            .unwrap();
+        let words: Vec<_> = layout.words.iter().map(|word| word.text).collect();
+        assert_eq!(words, vec!["kiwi"]);
+    }
+}
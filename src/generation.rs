@@ -0,0 +1,187 @@
+use std::time::{Duration, Instant};
+
+use ab_glyph::{Point, PxScale};
+use image::{Rgba, RgbaImage};
+use nanorand::WyRand;
+
+use crate::{score, text, Word, WordCloud, WordCloudError, WordCloudSize};
+
+/// Everything one generation produces: the image and where each word ended up in it
+#[derive(Clone, Debug)]
+pub struct GenerationResult {
+    pub image: RgbaImage,
+    pub words: Vec<WordPlacement>,
+    pub width: u32,
+    pub height: u32,
+    /// Words that didn't fit, in the order they were given up on
+    pub dropped: Vec<String>,
+}
+
+/// A placed word in the coordinates of the generated image
+#[derive(Clone, Debug, PartialEq)]
+pub struct WordPlacement {
+    pub text: String,
+    /// The top left of the word's bounding box
+    pub position: Point,
+    /// The size of the bounding box, turned with the word
+    pub width: u32,
+    pub height: u32,
+    pub font_size: PxScale,
+    /// Degrees counterclockwise, 0 for words that aren't rotated
+    pub angle: f32,
+    pub frequency: f32,
+    pub color: Rgba<u8>,
+}
+
+/// What a generation would cost and how full it would be, see `WordCloud::measure`
+#[derive(Clone, Debug, PartialEq)]
+pub struct GenerationStats {
+    /// The words handed to placement after tokenizing, filtering and `max_words`, repeats
+    /// included
+    pub candidate_words: usize,
+    pub placed: usize,
+    pub dropped: usize,
+    pub tokenize_time: Duration,
+    pub placement_time: Duration,
+    /// The share of the canvas covered by the words' bounding boxes, 0.0 - 1.0
+    pub fill_ratio: f32,
+}
+
+impl WordCloud {
+    /// Tokenizes and places the words of `text` like `generate_layout_from_text`, but only
+    /// returns how long that took and how it went, e.g. to budget a batch of variants. Nothing
+    /// is drawn, so this costs about as much as placement alone
+    pub fn measure(
+        &self,
+        text: &str,
+        size: WordCloudSize,
+    ) -> Result<GenerationStats, WordCloudError> {
+        let started = Instant::now();
+        let words = self.tokenizer.get_normalized_word_frequencies(text);
+        let candidate_words = words.len();
+        let tokenize_time = started.elapsed();
+
+        let layout = self.generate_layout_from_words(words, size)?;
+        let placement_time = started.elapsed() - tokenize_time;
+
+        Ok(GenerationStats {
+            candidate_words,
+            placed: layout.words.len(),
+            dropped: layout.dropped.len(),
+            tokenize_time,
+            placement_time,
+            fill_ratio: score::fill_ratio(&layout, layout.width, layout.height),
+        })
+    }
+
+    /// Panics if words were dropped under `DropPolicy::Error`, see `try_generate`
+    pub fn generate(
+        &self,
+        text: &str,
+        size: WordCloudSize,
+        scale: f32,
+        color_func: impl FnMut(&Word, &mut WyRand) -> Rgba<u8>,
+    ) -> GenerationResult {
+        self.try_generate(text, size, scale, color_func)
+            .expect("Unable to generate word cloud")
+    }
+
+    /// Places and draws the words of `text` in one pass, returning the placements along with
+    /// the image for callers that need both, e.g. for image maps or legends
+    pub fn try_generate(
+        &self,
+        text: &str,
+        size: WordCloudSize,
+        scale: f32,
+        color_func: impl FnMut(&Word, &mut WyRand) -> Rgba<u8>,
+    ) -> Result<GenerationResult, WordCloudError> {
+        let layout = self.generate_layout_from_text(text, size)?;
+        let scaled = self.scale_layout(&layout, scale, color_func)?;
+        let image = self.render_scaled_layout(&scaled)?;
+
+        let words = scaled
+            .words
+            .iter()
+            .map(|scaled_word| {
+                let word = scaled_word.word;
+                let (width, height) = text::rotated_dimensions(
+                    scaled_word.glyphs.width,
+                    scaled_word.glyphs.height,
+                    word.angle,
+                );
+                WordPlacement {
+                    text: word.text.to_string(),
+                    position: scaled_word.position,
+                    width,
+                    height,
+                    font_size: scaled_word.font_size,
+                    angle: word.angle,
+                    frequency: word.frequency,
+                    color: scaled_word.color,
+                }
+            })
+            .collect();
+
+        Ok(GenerationResult {
+            width: image.width(),
+            height: image.height(),
+            image,
+            words,
+            dropped: layout.dropped.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use image::Rgba;
+
+    use crate::{WordCloud, WordCloudSize};
+
+    #[test]
+    fn measure() {
+        let wordcloud = WordCloud::default().with_rng_seed(1);
+        let size = || WordCloudSize::FromDimensions {
+            width: 200,
+            height: 100,
+        };
+        let text = "apple apple apple banana banana cherry date elderberry fig grape";
+
+        let stats = wordcloud.measure(text, size()).unwrap();
+        let layout = wordcloud.generate_layout_from_text(text, size()).unwrap();
+        assert_eq!(stats.candidate_words, 7);
+        assert_eq!(stats.placed, layout.words.len());
+        assert_eq!(stats.placed + stats.dropped, stats.candidate_words);
+        assert!(stats.fill_ratio > 0.0 && stats.fill_ratio <= 1.0);
+        assert!(stats.placement_time > Duration::ZERO);
+    }
+
+    #[test]
+    fn generate_returns_image_and_placements() {
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_min_font_size(30.0);
+        let size = || WordCloudSize::FromDimensions {
+            width: 200,
+            height: 100,
+        };
+        let text = "apple apple apple banana banana cherry date elderberry fig grape";
+
+        let result = wordcloud.generate(text, size(), 2.0, |_, _| Rgba([255, 0, 0, 255]));
+        let image = wordcloud
+            .generate_from_text_with_color_func(text, size(), 2.0, |_, _| Rgba([255, 0, 0, 255]));
+
+        assert!(result.image == image);
+        assert_eq!((result.width, result.height), (400, 200));
+        assert!(!result.words.is_empty());
+        assert!(!result.dropped.is_empty());
+        for placement in &result.words {
+            assert!(placement.position.x + placement.width as f32 <= 400.0);
+            assert!(placement.position.y + placement.height as f32 <= 200.0);
+            assert_eq!(placement.color, Rgba([255, 0, 0, 255]));
+            assert!(!result.dropped.contains(&placement.text));
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use ab_glyph::{point, PxScale};
+use image::{Rgba, RgbaImage};
+
+use crate::{text, Layout, WordCloud};
+
+const LEGEND_FONT_SIZE: f32 = 16.0;
+const LEGEND_PADDING: u32 = 8;
+
+impl WordCloud {
+    /// Draws a strip with one color swatch per frequency bucket, labelled with the bucket's
+    /// frequency range. Each swatch is colored like the first word of the layout in that bucket,
+    /// so this is meant for color functions that depend on the frequency. Empty buckets are left out
+    pub fn render_legend(&self, layout: &Layout, buckets: usize) -> RgbaImage {
+        assert!(buckets > 0, "A legend needs at least one bucket");
+
+        let (min_freq, max_freq) = layout
+            .words
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), word| {
+                (min.min(word.frequency), max.max(word.frequency))
+            });
+        let step = (max_freq - min_freq) / buckets as f32;
+
+        let mut rng = layout.rng.clone();
+        let mut rows = vec![];
+        // 高频的放在上面
+        for i in (0..buckets).rev() {
+            let low = min_freq + step * i as f32;
+            let high = if i + 1 == buckets {
+                max_freq
+            } else {
+                low + step
+            };
+
+            let word = layout
+                .words
+                .iter()
+                .find(|word| word.frequency >= low && (word.frequency < high || i + 1 == buckets));
+
+            if let Some(word) = word {
+                let color = self.word_color(word, &mut rng);
+                let label = text::text_to_glyphs(
+                    &format!("{low:.2} - {high:.2}"),
+                    &self.font,
+                    PxScale::from(LEGEND_FONT_SIZE),
+                    self.text_options(),
+                );
+                rows.push((color, label));
+            }
+        }
+
+        let row_height = rows
+            .iter()
+            .map(|(_, label)| label.height)
+            .max()
+            .unwrap_or(0);
+        let label_width = rows.iter().map(|(_, label)| label.width).max().unwrap_or(0);
+        let width = LEGEND_PADDING * 3 + row_height + label_width;
+        let height = LEGEND_PADDING + rows.len() as u32 * (row_height + LEGEND_PADDING);
+
+        let mut buffer = RgbaImage::from_pixel(width, height, self.background_color);
+        let label_color = legend_label_color(self.background_color);
+
+        let mut context = text::DrawContext::new(self.font_index);
+        for (i, (color, label)) in rows.iter().enumerate() {
+            let y = LEGEND_PADDING + i as u32 * (row_height + LEGEND_PADDING);
+
+            for swatch_y in y..y + row_height {
+                for swatch_x in LEGEND_PADDING..LEGEND_PADDING + row_height {
+                    buffer.put_pixel(swatch_x, swatch_y, *color);
+                }
+            }
+
+            text::draw_glyphs_to_rgba_buffer(
+                &mut buffer,
+                &mut context,
+                label,
+                &self.font,
+                point((LEGEND_PADDING * 2 + row_height) as f32, y as f32),
+                0.0,
+                label_color,
+            );
+        }
+
+        buffer
+    }
+}
+
+/// 背景较亮时用黑色文字，否则用白色
+fn legend_label_color(background: Rgba<u8>) -> Rgba<u8> {
+    let [r, g, b, a] = background.0;
+    let luma = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32;
+
+    if a > 127 && luma > 127.0 {
+        Rgba([0, 0, 0, 255])
+    } else {
+        Rgba([255, 255, 255, 255])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::LEGEND_PADDING;
+    use crate::{WordCloud, WordCloudSize};
+
+    #[test]
+    fn legend_swatches() {
+        let (red, blue) = (Rgba([255, 0, 0, 255]), Rgba([0, 0, 255, 255]));
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_color_closure(move |word, _| if word.frequency > 0.5 { red } else { blue });
+        let layout = wordcloud
+            .generate_layout_from_text(
+                "apple apple apple apple banana cherry",
+                WordCloudSize::FromDimensions {
+                    width: 200,
+                    height: 100,
+                },
+            )
+            .unwrap();
+
+        // 高频的一档在上面，中间没有词的一档不画
+        let legend = wordcloud.render_legend(&layout, 3);
+        let row_height = (legend.height() - LEGEND_PADDING) / 2 - LEGEND_PADDING;
+        let swatch = |row: u32| {
+            *legend.get_pixel(
+                LEGEND_PADDING + row_height / 2,
+                LEGEND_PADDING + row * (row_height + LEGEND_PADDING) + row_height / 2,
+            )
+        };
+        assert_eq!(swatch(0), red);
+        assert_eq!(swatch(1), blue);
+        assert_eq!(*legend.get_pixel(0, 0), Rgba([0, 0, 0, 255]));
+    }
+}
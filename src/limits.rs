@@ -0,0 +1,22 @@
+use std::time::Duration;
+
+/// Caps on the work a single generation may do, so a service can apply one policy to every
+/// request from untrusted input. Exceeding one fails with `WordCloudError::LimitExceeded`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// The largest canvas, in bytes of RGBA pixels, that may be allocated
+    pub max_canvas_bytes: Option<usize>,
+    /// The most words that may be placed, after `max_words` and `repeat` are applied
+    pub max_words: Option<usize>,
+    /// How long placing the words may take. Checked between words, so a single word can
+    /// still run over
+    pub max_generation_time: Option<Duration>,
+}
+
+/// Which of the `ResourceLimits` was exceeded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    CanvasBytes,
+    Words,
+    GenerationTime,
+}
@@ -1,13 +1,12 @@
 use ab_glyph::FontVec;
 use clap::{App, Arg};
 use csscolorparser::Color;
-use image::codecs::png::PngEncoder;
-use image::{ColorType, ImageEncoder, Rgba};
+use image::Rgba;
 use regex::Regex;
-use rust_wcloud::{ChineseTokenizer, WordCloud, WordCloudSize};
+use rust_wcloud::{render_to_ansi, ChineseTokenizer, FrequencyMap, WordCloud, WordCloudSize};
 use std::collections::HashSet;
 use std::fs;
-use std::io::{self, stdout, Read};
+use std::io::{self, stdout, BufReader};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -18,8 +17,9 @@ fn main() {
         .about("Generate word clouds!")
         .arg(Arg::with_name("text")
             .long("text")
-            .value_name("FILE")
-            .help("Specifies the file of words to build the word cloud with"))
+            .value_name("FILE[:WEIGHT]")
+            .multiple_occurrences(true)
+            .help("Specifies a file of words to build the word cloud with. Repeat it to combine several files, the counts of each are multiplied by its weight [1.0]"))
         .arg(Arg::with_name("regex")
             .long("regex")
             .value_name("REGEX")
@@ -39,7 +39,11 @@ fn main() {
         .arg(Arg::with_name("background-color")
             .long("background-color")
             .value_name("TEXT")
-            .help("Sets the background color of the word cloud image"))
+            .help("Sets the background color of the word cloud image, e.g. transparent [opaque black, like the library]"))
+        .arg(Arg::with_name("flatten-color")
+            .long("flatten-color")
+            .value_name("TEXT")
+            .help("Sets the color transparent pixels are filled with when saving as JPEG or BMP [background color]"))
         .arg(Arg::with_name("margin")
             .long("margin")
             .value_name("NUM")
@@ -78,10 +82,31 @@ fn main() {
             .long("min-word-length")
             .value_name("NUM")
             .help("Sets the min length of words"))
+        .arg(Arg::with_name("max-word-length")
+            .long("max-word-length")
+            .value_name("NUM")
+            .help("Sets the max length of words"))
         .arg(Arg::with_name("mask")
             .long("mask")
             .value_name("FILE")
             .help("Sets the boolean mask image for the word cloud shape. Any color other than black (#000) means there is no space"))
+        .arg(Arg::with_name("preview")
+            .long("preview")
+            .help("Draws the word cloud in the terminal instead of writing a PNG to stdout, $COLUMNS characters wide [80]"))
+        .arg(Arg::with_name("progress")
+            .long("progress")
+            .help("Prints how many of the words were placed so far to stderr"))
+        .arg(Arg::with_name("mask-invert")
+            .long("mask-invert")
+            .help("Treats white (#fff) mask pixels as the space words can be placed in, instead of black"))
+        .arg(Arg::with_name("mask-edge-margin")
+            .long("mask-edge-margin")
+            .value_name("NUM")
+            .help("Keeps words this many pixels inside the edge of the mask [0]"))
+        .arg(Arg::with_name("exclusion-mask")
+            .long("exclusion-mask")
+            .value_name("FILE")
+            .help("Sets a second mask image whose non-black pixels are kept free of words"))
         .arg(Arg::with_name("exclude-words")
             .long("exclude-words")
             .value_name("FILE")
@@ -90,7 +115,7 @@ fn main() {
             .long("output")
             .short('o')
             .value_name("FILE")
-            .help("The output path of the final word cloud image").required(true))
+            .help("The output path of the final word cloud image. Writes a PNG to stdout when omitted"))
         .arg(Arg::with_name("font")
             .long("font")
             .short('f')
@@ -111,6 +136,13 @@ fn main() {
         tokenizer = tokenizer.with_min_word_len(min_word_length);
     }
 
+    if let Some(max_word_length) = matches.value_of("max-word-length") {
+        let max_word_length = max_word_length
+            .parse()
+            .expect("Max word length must be a number");
+        tokenizer = tokenizer.with_max_word_length(max_word_length);
+    }
+
     if let Some(max_words) = matches.value_of("max-words") {
         let max_words = max_words
             .parse()
@@ -166,21 +198,73 @@ fn main() {
         }
     };
 
-    let background_color = match matches.value_of("background-color") {
-        Some(color) => {
-            let col = color
-                .parse::<Color>()
-                .unwrap_or(Color::new(0.0, 0.0, 0.0, 1.0))
-                .to_rgba8();
+    // 没给背景色时用 `WordCloud::default()` 的，和库一样是不透明的黑色
+    let background_color = matches.value_of("background-color").map(|color| {
+        let col = color
+            .parse::<Color>()
+            .unwrap_or(Color::new(0.0, 0.0, 0.0, 1.0))
+            .to_rgba8();
+
+        Rgba(col)
+    });
 
-            Rgba(col)
+    // 流式统计词频，大文件不需要整个读进内存
+    let mut frequencies = FrequencyMap::new();
+    if let Some(texts) = matches.values_of("text") {
+        for text in texts {
+            let (text_file_path, weight) = parse_weighted_path(text);
+            let file = fs::File::open(text_file_path)
+                .unwrap_or_else(|_| panic!("Unable to read text file \'{}\'", text_file_path));
+            let file_frequencies = tokenizer
+                .get_word_frequencies_from_reader(BufReader::new(file))
+                .unwrap_or_else(|_| panic!("Unable to read text file \'{}\'", text_file_path));
+            frequencies.add(&file_frequencies, weight);
         }
-        None => Rgba([0, 0, 0, 0]),
-    };
+    } else {
+        let stdin_frequencies = tokenizer
+            .get_word_frequencies_from_reader(io::stdin().lock())
+            .expect("Unable to read stdin");
+        frequencies.add(&stdin_frequencies, 1.0);
+    }
+
+    let mut wordcloud = WordCloud::default().with_tokenizer(tokenizer);
+    if let Some(background_color) = background_color {
+        wordcloud = wordcloud.with_background_color(background_color);
+    }
+
+    if let Some(color) = matches.value_of("flatten-color") {
+        let col = color
+            .parse::<Color>()
+            .expect("The flatten color must be a valid CSS color")
+            .to_rgba8();
+        wordcloud = wordcloud.with_flatten_color(Rgba(col));
+    }
 
-    let mut wordcloud = WordCloud::default()
-        .with_tokenizer(tokenizer)
-        .with_background_color(background_color);
+    if matches.is_present("progress") {
+        wordcloud = wordcloud.with_progress(|fraction| {
+            eprint!("\rPlacing words: {:3.0}%", fraction * 100.0);
+            if fraction >= 1.0 {
+                eprintln!();
+            }
+        });
+    }
+
+    if matches.is_present("mask-invert") {
+        wordcloud = wordcloud.with_mask_invert(true);
+    }
+
+    if let Some(mask_path) = matches.value_of("exclusion-mask") {
+        let mask_image = image::open(mask_path).unwrap().into_luma8();
+        wordcloud = wordcloud.with_exclusion_mask(mask_image);
+    }
+
+    if let Some(margin) = matches.value_of("mask-edge-margin") {
+        wordcloud = wordcloud.with_mask_edge_margin(
+            margin
+                .parse()
+                .expect("Mask edge margin must be a valid number"),
+        );
+    }
 
     if let Some(margin) = matches.value_of("margin") {
         wordcloud =
@@ -240,32 +324,39 @@ fn main() {
         .parse()
         .expect("Scale must be a number between 0 and 100");
 
-    let text = if let Some(text_file_path) = matches.value_of("text") {
-        fs::read_to_string(text_file_path)
-            .unwrap_or_else(|_| panic!("Unable to read text file \'{}\'", text_file_path))
-    } else {
-        let mut buffer = String::new();
-        io::stdin()
-            .read_to_string(&mut buffer)
-            .expect("Unable to read stdin");
-
-        buffer
-    };
-
-    let wordcloud_image = wordcloud.generate_from_text(&text, wordcloud_size, scale);
+    let wordcloud_image =
+        wordcloud.generate_from_frequency_map(&frequencies, wordcloud_size, scale);
 
     if let Some(file_path) = matches.value_of("output") {
-        wordcloud_image
-            .save(file_path)
+        wordcloud
+            .save_image(&wordcloud_image, file_path)
             .expect("Failed to save WordCloud image");
-    } else {
-        let encoder = PngEncoder::new(stdout());
-
-        let width = wordcloud_image.width();
-        let height = wordcloud_image.height();
+    }
 
-        encoder
-            .write_image(&wordcloud_image, width, height, ColorType::Rgb8)
+    if matches.is_present("preview") {
+        let cols = std::env::var("COLUMNS")
+            .ok()
+            .and_then(|cols| cols.parse().ok())
+            .unwrap_or(80);
+        print!("{}", render_to_ansi(&wordcloud_image, cols));
+    } else if matches.value_of("output").is_none() {
+        wordcloud
+            .write_png(&wordcloud_image, stdout().lock())
             .expect("Failed to save wordcloud image");
     }
 }
+
+/// `path:weight`，没有权重或者冒号后面不是数字时整个都是路径（比如 Windows 的 `C:\text.txt`）
+fn parse_weighted_path(value: &str) -> (&str, f32) {
+    match value.rsplit_once(':') {
+        Some((path, weight)) if !path.is_empty() => match weight.parse::<f32>() {
+            Ok(weight) if weight.is_finite() && weight >= 0.0 => (path, weight),
+            Ok(_) => {
+                eprintln!("The weight of '{}' must be a number of at least 0", path);
+                std::process::exit(1)
+            }
+            Err(_) => (value, 1.0),
+        },
+        _ => (value, 1.0),
+    }
+}
@@ -0,0 +1,461 @@
+use image::{imageops::FilterType, GrayImage, Luma};
+
+use crate::{ShapeBias, WordCloud};
+
+/// 可用面积低于这个比例时提醒
+const LOW_AVAILABILITY: f32 = 0.05;
+
+/// How much room a mask leaves for words, see `WordCloud::inspect_mask`
+#[derive(Clone, Debug, PartialEq)]
+pub struct MaskReport {
+    /// The number of black pixels, where words can be placed
+    pub available_pixels: usize,
+    /// `available_pixels` as a fraction of the mask, 0.0 - 1.0
+    pub available_fraction: f32,
+    /// The smallest (x, y, width, height) rect holding every available pixel, if there are any
+    pub available_bounds: Option<(u32, u32, u32, u32)>,
+    /// Less than 5% of the mask is available. With a mostly white mask, `with_mask_invert`
+    /// may be what you want
+    pub low_availability: bool,
+}
+
+/// 保持宽高比缩放以后的大小，最长的一边等于 `max_dimension`
+pub(crate) fn fit_dimensions((width, height): (u32, u32), max_dimension: u32) -> (u32, u32) {
+    let longest = width.max(height);
+    if longest == max_dimension || longest == 0 {
+        return (width, height);
+    }
+
+    let ratio = max_dimension as f64 / longest as f64;
+    let scaled = |side: u32| ((side as f64 * ratio).round() as u32).clamp(1, max_dimension);
+    (scaled(width), scaled(height))
+}
+
+/// 缩放到 `fit_dimensions` 的大小。用最近邻插值，边缘不会变灰
+pub(crate) fn scale_to_fit(mask: &GrayImage, max_dimension: u32) -> GrayImage {
+    let (width, height) = fit_dimensions(mask.dimensions(), max_dimension);
+    if (width, height) == mask.dimensions() {
+        return mask.clone();
+    }
+
+    image::imageops::resize(mask, width, height, FilterType::Nearest)
+}
+
+/// 画出 `shape` 的遮罩，形状外面是白的。按像素中心判断在不在形状里
+pub(crate) fn shape_mask(shape: ShapeBias, width: u32, height: u32) -> GrayImage {
+    let (half_width, half_height) = (width as f32 / 2.0, height as f32 / 2.0);
+    GrayImage::from_fn(width, height, |x, y| {
+        let dx = (x as f32 + 0.5 - half_width) / half_width;
+        let dy = (y as f32 + 0.5 - half_height) / half_height;
+        let inside = match shape {
+            ShapeBias::Ellipse => dx * dx + dy * dy <= 1.0,
+            ShapeBias::Diamond => dx.abs() + dy.abs() <= 1.0,
+        };
+        if inside {
+            Luma([0])
+        } else {
+            Luma([255])
+        }
+    })
+}
+
+/// 按非零环绕规则填充 SVG 路径画出遮罩，路径里面是黑的。路径解析不了或者没盖住任何像素时报错
+#[cfg(feature = "svg")]
+pub(crate) fn svg_path_mask(
+    path: &str,
+    width: u32,
+    height: u32,
+) -> Result<GrayImage, crate::WordCloudError> {
+    use svgtypes::{SimplePathSegment, SimplifyingPathParser};
+    use tiny_skia::{FillRule, Paint, PathBuilder, Pixmap, Transform};
+
+    let invalid = |reason: String| crate::WordCloudError::InvalidConfig(reason);
+
+    let mut builder = PathBuilder::new();
+    for segment in SimplifyingPathParser::from(path) {
+        match segment.map_err(|err| invalid(format!("Invalid SVG path: {err}")))? {
+            SimplePathSegment::MoveTo { x, y } => builder.move_to(x as f32, y as f32),
+            SimplePathSegment::LineTo { x, y } => builder.line_to(x as f32, y as f32),
+            SimplePathSegment::CurveTo {
+                x1,
+                y1,
+                x2,
+                y2,
+                x,
+                y,
+            } => builder.cubic_to(
+                x1 as f32, y1 as f32, x2 as f32, y2 as f32, x as f32, y as f32,
+            ),
+            SimplePathSegment::Quadratic { x1, y1, x, y } => {
+                builder.quad_to(x1 as f32, y1 as f32, x as f32, y as f32)
+            }
+            SimplePathSegment::ClosePath => builder.close(),
+        }
+    }
+    let path = builder
+        .finish()
+        .ok_or_else(|| invalid("The SVG path is empty".to_string()))?;
+    let mut pixmap = Pixmap::new(width, height)
+        .ok_or_else(|| invalid(format!("A {width}x{height} SVG mask is invalid")))?;
+
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(0, 0, 0, 255);
+    paint.anti_alias = false;
+    pixmap.fill_path(
+        &path,
+        &paint,
+        FillRule::Winding,
+        Transform::identity(),
+        None,
+    );
+
+    let mask = GrayImage::from_fn(width, height, |x, y| {
+        match pixmap.pixel(x, y).map(|px| px.alpha()) {
+            Some(0) | None => Luma([255]),
+            Some(_) => Luma([0]),
+        }
+    });
+    if mask.pixels().all(|px| px.0[0] != 0) {
+        return Err(invalid(
+            "The SVG path doesn't cover any pixel of the canvas".to_string(),
+        ));
+    }
+
+    Ok(mask)
+}
+
+/// 遮罩上能放字的（纯黑）像素连成的区域，上下左右相邻的算连通
+pub(crate) struct Regions {
+    width: u32,
+    /// 每个像素属于哪个区域，不能放字的是 `NO_REGION`
+    labels: Vec<u32>,
+    /// 每个区域的像素数
+    pub areas: Vec<usize>,
+}
+
+const NO_REGION: u32 = u32::MAX;
+
+/// 比可用面积的这个比例还小的区域不单独分词，大多是遮罩上的噪点
+const MIN_REGION_FRACTION: f32 = 0.01;
+
+impl Regions {
+    pub fn label_at(&self, x: u32, y: u32) -> Option<usize> {
+        let label = *self.labels.get((y * self.width + x) as usize)?;
+        (label != NO_REGION).then_some(label as usize)
+    }
+
+    /// 按面积算放得最少的区域
+    pub fn least_filled(&self, filled: &[usize]) -> usize {
+        let total = self.areas.iter().sum::<usize>() as f32;
+        self.areas
+            .iter()
+            .zip(filled)
+            .enumerate()
+            .filter(|(_, (area, _))| **area as f32 >= total * MIN_REGION_FRACTION)
+            .min_by(|(_, (a_area, a_filled)), (_, (b_area, b_filled))| {
+                let ratio = |area: usize, filled: usize| filled as f32 / area as f32;
+                ratio(**a_area, **a_filled).total_cmp(&ratio(**b_area, **b_filled))
+            })
+            .map_or(0, |(label, _)| label)
+    }
+}
+
+/// 给能放字的像素标上连通区域，一个区域一个区域地往外填
+pub(crate) fn label_regions(mask: &GrayImage) -> Regions {
+    let (width, height) = mask.dimensions();
+    let mut labels = vec![NO_REGION; mask.len()];
+    let mut areas = vec![];
+    let mut stack = vec![];
+
+    for start in 0..labels.len() {
+        if labels[start] != NO_REGION || mask.as_raw()[start] != 0 {
+            continue;
+        }
+
+        let label = areas.len() as u32;
+        let mut area = 0;
+        labels[start] = label;
+        stack.push(start);
+        while let Some(i) = stack.pop() {
+            area += 1;
+            let (x, y) = (i as u32 % width, i as u32 / width);
+            let neighbors = [
+                (x > 0).then(|| i - 1),
+                (x + 1 < width).then(|| i + 1),
+                (y > 0).then(|| i - width as usize),
+                (y + 1 < height).then(|| i + width as usize),
+            ];
+            for j in neighbors.into_iter().flatten() {
+                if labels[j] == NO_REGION && mask.as_raw()[j] == 0 {
+                    labels[j] = label;
+                    stack.push(j);
+                }
+            }
+        }
+        areas.push(area);
+    }
+
+    Regions {
+        width,
+        labels,
+        areas,
+    }
+}
+
+/// 把不能放字的（非黑）像素向外扩 `margin` 个像素（正方形），相当于把可用区域往里收。
+/// 先按行再按列，每个像素只看左右（上下）最近的占用像素有多远
+pub(crate) fn erode_available(mask: &GrayImage, margin: u32) -> GrayImage {
+    let (width, height) = mask.dimensions();
+    let mut horizontal = GrayImage::from_pixel(width, height, Luma([0]));
+    for y in 0..height {
+        spread_line(
+            width,
+            margin,
+            |x| mask.get_pixel(x, y).0[0] != 0,
+            |x| horizontal.put_pixel(x, y, Luma([1])),
+        );
+    }
+
+    let mut eroded = GrayImage::from_pixel(width, height, Luma([0]));
+    for x in 0..width {
+        spread_line(
+            height,
+            margin,
+            |y| horizontal.get_pixel(x, y).0[0] != 0,
+            |y| eroded.put_pixel(x, y, Luma([1])),
+        );
+    }
+
+    // 原来的像素值不变，新占用的像素设成 1
+    for (px, original) in eroded.pixels_mut().zip(mask.pixels()) {
+        if original.0[0] != 0 {
+            *px = *original;
+        }
+    }
+
+    eroded
+}
+
+/// 一行（列）里离占用像素不超过 `margin` 的位置都标记出来
+fn spread_line(
+    len: u32,
+    margin: u32,
+    is_blocked: impl Fn(u32) -> bool,
+    mut block: impl FnMut(u32),
+) {
+    let mut distance = vec![u32::MAX; len as usize];
+    let mut last = None;
+    for i in 0..len {
+        if is_blocked(i) {
+            last = Some(i);
+        }
+        if let Some(last) = last {
+            distance[i as usize] = i - last;
+        }
+    }
+    last = None;
+    for i in (0..len).rev() {
+        if is_blocked(i) {
+            last = Some(i);
+        }
+        if let Some(last) = last {
+            distance[i as usize] = distance[i as usize].min(last - i);
+        }
+        if distance[i as usize] <= margin {
+            block(i);
+        }
+    }
+}
+
+impl WordCloud {
+    /// Checks a mask before generating with it. Like generation, black pixels are the space
+    /// words can be placed in
+    pub fn inspect_mask(mask: &GrayImage) -> MaskReport {
+        let mut available_pixels = 0;
+        let mut bounds: Option<(u32, u32, u32, u32)> = None;
+
+        for (y, row) in mask.rows().enumerate() {
+            let y = y as u32;
+            // 和 create_mask_skip_list 一样，只有纯黑的像素才能放字
+            let mut row_available = row
+                .enumerate()
+                .filter(|(_, p)| **p == Luma::from([0]))
+                .map(|(x, _)| x as u32)
+                .peekable();
+            let Some(&left) = row_available.peek() else {
+                continue;
+            };
+            let mut right = left;
+            for x in row_available {
+                right = x;
+                available_pixels += 1;
+            }
+
+            bounds = Some(match bounds {
+                Some((min_x, min_y, max_x, _)) => (min_x.min(left), min_y, max_x.max(right), y),
+                None => (left, y, right, y),
+            });
+        }
+
+        let available_fraction = if mask.is_empty() {
+            0.0
+        } else {
+            available_pixels as f32 / mask.len() as f32
+        };
+
+        MaskReport {
+            available_pixels,
+            available_fraction,
+            available_bounds: bounds.map(|(min_x, min_y, max_x, max_y)| {
+                (min_x, min_y, max_x - min_x + 1, max_y - min_y + 1)
+            }),
+            low_availability: available_fraction < LOW_AVAILABILITY,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GrayImage, Luma};
+
+    use super::{erode_available, label_regions, scale_to_fit, shape_mask};
+    use crate::{ShapeBias, WordCloud};
+
+    #[test]
+    fn shape_masks() {
+        let available = |mask: &GrayImage, x, y| mask.get_pixel(x, y).0[0] == 0;
+
+        let ellipse = shape_mask(ShapeBias::Ellipse, 200, 100);
+        assert!(available(&ellipse, 100, 50));
+        assert!(available(&ellipse, 1, 50));
+        assert!(available(&ellipse, 100, 1));
+        assert!(!available(&ellipse, 0, 0));
+        assert!(!available(&ellipse, 20, 10));
+
+        let diamond = shape_mask(ShapeBias::Diamond, 200, 100);
+        assert!(available(&diamond, 100, 50));
+        assert!(available(&diamond, 60, 40));
+        assert!(!available(&diamond, 40, 20));
+        assert!(!available(&diamond, 199, 99));
+    }
+
+    #[test]
+    fn regions() {
+        // 左边一个方块，右边一个 L 形，中间一个孤立的点
+        let mask = GrayImage::from_fn(30, 10, |x, y| {
+            let available = x < 10 || (x == 15 && y == 5) || x == 29 || (y == 9 && x >= 20);
+            Luma([if available { 0 } else { 255 }])
+        });
+
+        let regions = label_regions(&mask);
+        assert_eq!(regions.areas, vec![100, 19, 1]);
+        assert_eq!(regions.label_at(0, 0), Some(0));
+        assert_eq!(regions.label_at(20, 9), Some(1));
+        assert_eq!(regions.label_at(15, 5), Some(2));
+        assert_eq!(regions.label_at(15, 0), None);
+
+        // 按面积比例算，太小的区域不算
+        assert_eq!(regions.least_filled(&[50, 5, 0]), 1);
+        assert_eq!(regions.least_filled(&[10, 5, 0]), 0);
+    }
+
+    #[test]
+    fn erode_available_follows_the_edge() {
+        // 中间一个 20x20 的可用方块
+        let mask = GrayImage::from_fn(40, 40, |x, y| {
+            if (10..30).contains(&x) && (10..30).contains(&y) {
+                Luma([0])
+            } else {
+                Luma([255])
+            }
+        });
+
+        let eroded = erode_available(&mask, 3);
+        let report = WordCloud::inspect_mask(&eroded);
+        assert_eq!(report.available_bounds, Some((13, 13, 14, 14)));
+        assert_eq!(report.available_pixels, 14 * 14);
+        assert_eq!(eroded.get_pixel(0, 0), &Luma([255]));
+        assert_eq!(eroded.get_pixel(12, 20), &Luma([1]));
+
+        assert_eq!(erode_available(&mask, 0), mask);
+    }
+
+    #[test]
+    fn scale_to_fit_keeps_aspect_ratio() {
+        let mask = GrayImage::from_fn(200, 100, |x, _| Luma([if x < 100 { 0 } else { 255 }]));
+
+        let smaller = scale_to_fit(&mask, 50);
+        assert_eq!(smaller.dimensions(), (50, 25));
+        let larger = scale_to_fit(&mask, 400);
+        assert_eq!(larger.dimensions(), (400, 200));
+        // 边缘没有灰色
+        assert!(larger.pixels().all(|px| px.0[0] == 0 || px.0[0] == 255));
+        assert_eq!(larger.get_pixel(199, 0), &Luma([0]));
+        assert_eq!(larger.get_pixel(200, 0), &Luma([255]));
+
+        let tall = GrayImage::new(10, 300);
+        assert_eq!(scale_to_fit(&tall, 60).dimensions(), (2, 60));
+    }
+
+    #[test]
+    fn inspect_mask() {
+        let mask = GrayImage::from_fn(100, 50, |x, y| {
+            if (10..30).contains(&x) && (5..25).contains(&y) {
+                Luma([0])
+            } else {
+                Luma([255])
+            }
+        });
+
+        let report = WordCloud::inspect_mask(&mask);
+        assert_eq!(report.available_pixels, 400);
+        assert_eq!(report.available_fraction, 0.08);
+        assert_eq!(report.available_bounds, Some((10, 5, 20, 20)));
+        assert!(!report.low_availability);
+
+        let report = WordCloud::inspect_mask(&GrayImage::from_pixel(10, 10, Luma([255])));
+        assert_eq!(report.available_pixels, 0);
+        assert_eq!(report.available_bounds, None);
+        assert!(report.low_availability);
+    }
+
+    #[cfg(feature = "svg")]
+    #[test]
+    fn svg_path_mask() {
+        use super::svg_path_mask;
+        use crate::{text, WordCloudError, WordCloudSize};
+
+        // 相对坐标和绝对坐标混用的三角形
+        let mask = svg_path_mask("M 10 10 L 190 10 l -90 80 Z", 200, 100).unwrap();
+        assert_eq!(mask.dimensions(), (200, 100));
+        assert_eq!(mask.get_pixel(100, 30), &Luma([0]));
+        assert_eq!(mask.get_pixel(15, 80), &Luma([255]));
+        assert_eq!(mask.get_pixel(100, 95), &Luma([255]));
+
+        let invalid = |path: &str| {
+            matches!(
+                svg_path_mask(path, 200, 100),
+                Err(WordCloudError::InvalidConfig(_))
+            )
+        };
+        assert!(invalid("M 10 10 X 20"));
+        assert!(invalid(""));
+        // 整个在画布外面
+        assert!(invalid("M 300 300 L 400 300 L 400 400 Z"));
+
+        let wordcloud = WordCloud::default().with_rng_seed(1);
+        let layout = wordcloud
+            .generate_layout_from_text(
+                "apple apple apple banana banana cherry",
+                WordCloudSize::from_svg_path("M 50 20 H 150 V 80 H 50 Z", 200, 100).unwrap(),
+            )
+            .unwrap();
+        assert!(!layout.words.is_empty());
+        for word in &layout.words {
+            let (width, height) =
+                text::rotated_dimensions(word.glyphs.width, word.glyphs.height, word.angle);
+            assert!(word.position.x >= 50.0 && word.position.x + width as f32 <= 150.0);
+            assert!(word.position.y >= 20.0 && word.position.y + height as f32 <= 80.0);
+        }
+    }
+}
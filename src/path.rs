@@ -0,0 +1,58 @@
+use ab_glyph::{point, Point};
+use image::Rgba;
+
+/// A word drawn along a path at a fixed place. Path words are reserved on the canvas before
+/// the other words are placed around them
+#[derive(Clone, Debug)]
+pub struct PathWord {
+    pub text: String,
+    pub font_size: f32,
+    /// The baseline of the word, in canvas coordinates
+    pub path: WordPath,
+    pub color: Rgba<u8>,
+}
+
+#[derive(Clone, Debug)]
+pub enum WordPath {
+    Polyline(Vec<Point>),
+    /// A circular arc going from `start_angle` to `end_angle` (radians, clockwise on the
+    /// canvas from the positive x axis). E.g. `PI` to `2.0 * PI` arcs across the top of `center`
+    Arc {
+        center: Point,
+        radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+    },
+}
+
+impl WordPath {
+    /// 把路径转换为折线，x 缩放 `scale_x` 倍，y 缩放 `scale_y` 倍，圆弧大约每 2 像素一段
+    pub(crate) fn to_polyline(&self, scale_x: f32, scale_y: f32) -> Vec<Point> {
+        match self {
+            WordPath::Polyline(points) => points
+                .iter()
+                .map(|p| point(p.x * scale_x, p.y * scale_y))
+                .collect(),
+            WordPath::Arc {
+                center,
+                radius,
+                start_angle,
+                end_angle,
+            } => {
+                let sweep = end_angle - start_angle;
+                let segments =
+                    ((sweep.abs() * radius * scale_x.max(scale_y) / 2.0).ceil() as usize).max(1);
+
+                (0..=segments)
+                    .map(|i| {
+                        let angle = start_angle + sweep * i as f32 / segments as f32;
+                        point(
+                            (center.x + radius * angle.cos()) * scale_x,
+                            (center.y + radius * angle.sin()) * scale_y,
+                        )
+                    })
+                    .collect()
+            }
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use ab_glyph::{Font, FontVec, ScaleFont};
+use image::{
+    error::{EncodingError, ImageFormatHint},
+    ImageError, Rgba,
+};
+use printpdf::{
+    Color, IndirectFontRef, Mm, PdfDocument, PdfLayerReference, Pt, Rect, Rgb, TextMatrix,
+};
+
+use crate::{render::ScaledWord, WordCloud, WordCloudError, WordCloudSize};
+
+impl WordCloud {
+    /// Panics if the PDF can't be written, see `try_generate_pdf_from_text`
+    pub fn generate_pdf_from_text(&self, text: &str, size: WordCloudSize, scale: f32) -> Vec<u8> {
+        self.try_generate_pdf_from_text(text, size, scale)
+            .expect("Unable to generate word cloud")
+    }
+
+    /// Generates a word cloud as a one page PDF where every word is selectable text in the
+    /// embedded font, for print. One pixel of the image `generate_from_text` would make is one
+    /// point (1/72 inch) on the page. Colors are drawn opaque and glyph effects such as shadows,
+    /// outlines and `with_path_word` words are left out. Fonts from a collection other than its
+    /// first face can't be embedded
+    pub fn try_generate_pdf_from_text(
+        &self,
+        text: &str,
+        size: WordCloudSize,
+        scale: f32,
+    ) -> Result<Vec<u8>, WordCloudError> {
+        let layout = self.generate_layout_from_text(text, size)?;
+        let scaled = self.scale_layout(&layout, scale, |word, rng| self.word_color(word, rng))?;
+        let (width, height) = (scaled.width as f32, scaled.height as f32);
+
+        let (document, page, layer) = PdfDocument::new(
+            "Word cloud",
+            Mm::from(Pt(width)),
+            Mm::from(Pt(height)),
+            "Words",
+        );
+        let layer = document.get_page(page).get_layer(layer);
+
+        if self.background_color.0[3] != 0 {
+            layer.set_fill_color(pdf_color(self.background_color));
+            layer.add_rect(Rect::new(
+                Mm(0.0),
+                Mm(0.0),
+                Mm::from(Pt(width)),
+                Mm::from(Pt(height)),
+            ));
+        }
+
+        // 同一个字体只嵌入一次
+        let mut fonts: HashMap<*const FontVec, IndirectFontRef> = HashMap::new();
+        for word in &scaled.words {
+            let key = word.word.font as *const FontVec;
+            let font = match fonts.get(&key) {
+                Some(font) => font.clone(),
+                None => {
+                    if self.font_index != 0 && std::ptr::eq(word.word.font, &self.font) {
+                        return Err(WordCloudError::InvalidConfig(
+                            "Only the first font of a collection can be embedded in a PDF"
+                                .to_string(),
+                        ));
+                    }
+                    let font = document
+                        .add_external_font(word.word.font.font_data())
+                        .map_err(pdf_error)?;
+                    fonts.insert(key, font.clone());
+                    font
+                }
+            };
+
+            self.write_pdf_word(&layer, &font, word, height);
+        }
+
+        document.save_to_bytes().map_err(pdf_error)
+    }
+
+    /// 每一行写成一段文字，基线从图片坐标换到左下角是原点的 PDF 坐标
+    fn write_pdf_word(
+        &self,
+        layer: &PdfLayerReference,
+        font: &IndirectFontRef,
+        word: &ScaledWord,
+        page_height: f32,
+    ) {
+        let Some(first_glyph) = word.glyphs.glyphs.first() else {
+            return;
+        };
+        let scaled_font = word.word.font.as_scaled(word.font_size);
+        // ab_glyph 的字号是字形的高，PDF 的字号是 em 的大小
+        let units_per_em = word.word.font.units_per_em().unwrap_or(1000.0);
+        let font_size = scaled_font.scale_factor().vertical * units_per_em;
+        let v_advance = scaled_font.height() + scaled_font.line_gap();
+
+        // 旋转以前的框绕中心逆时针转 `angle` 度，放在外框里
+        let (width, height) = (word.glyphs.width as f32, word.glyphs.height as f32);
+        let (box_width, box_height) =
+            crate::text::rotated_dimensions(word.glyphs.width, word.glyphs.height, word.word.angle);
+        let center = (
+            word.position.x + box_width as f32 / 2.0,
+            word.position.y + box_height as f32 / 2.0,
+        );
+        let (sin, cos) = word.word.angle.to_radians().sin_cos();
+        let to_page = |x: f32, y: f32| {
+            let (dx, dy) = (x - width / 2.0, y - height / 2.0);
+            (
+                center.0 + dx * cos + dy * sin,
+                page_height - (center.1 - dx * sin + dy * cos),
+            )
+        };
+
+        layer.begin_text_section();
+        layer.set_font(font, font_size);
+        layer.set_fill_color(pdf_color(word.color));
+        if word.font_size.x != word.font_size.y {
+            layer.set_text_scaling(word.font_size.x / word.font_size.y * 100.0);
+        }
+        if self.letter_spacing != 0.0 {
+            // 字距是画布上的像素，跟着这个词横向的缩放
+            layer.set_character_spacing(
+                self.letter_spacing * word.font_size.x / word.word.font_size.x,
+            );
+        }
+        for (line, text) in word.word.text.split('\n').enumerate() {
+            let baseline = first_glyph.position.y + line as f32 * v_advance;
+            // 对齐以后每行开头不一样，取这一行最左边的字形
+            let left = word
+                .glyphs
+                .glyphs
+                .iter()
+                .filter(|glyph| (glyph.position.y - baseline).abs() < 0.5)
+                .map(|glyph| glyph.position.x)
+                .reduce(f32::min);
+            let Some(left) = left else {
+                continue;
+            };
+
+            let (x, y) = to_page(left, baseline);
+            layer.set_text_matrix(TextMatrix::TranslateRotate(Pt(x), Pt(y), word.word.angle));
+            layer.write_text(text.replace('\t', " "), font);
+        }
+        layer.end_text_section();
+    }
+}
+
+fn pdf_color(color: Rgba<u8>) -> Color {
+    let [r, g, b, _] = color.0;
+    Color::Rgb(Rgb::new(
+        r as f32 / 255.0,
+        g as f32 / 255.0,
+        b as f32 / 255.0,
+        None,
+    ))
+}
+
+fn pdf_error(err: printpdf::Error) -> WordCloudError {
+    WordCloudError::Encode(ImageError::Encoding(EncodingError::new(
+        ImageFormatHint::Name("PDF".to_string()),
+        err,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use printpdf::lopdf::{content::Content, Document, Object};
+
+    use crate::{WordCloud, WordCloudSize};
+
+    #[test]
+    fn pdf() {
+        let size = || WordCloudSize::FromDimensions {
+            width: 200,
+            height: 100,
+        };
+        let text = "apple apple apple banana banana cherry";
+        let wordcloud = WordCloud::default().with_rng_seed(1);
+        let pdf = wordcloud.generate_pdf_from_text(text, size(), 2.0);
+        assert!(pdf.starts_with(b"%PDF"));
+
+        let document = Document::load_mem(&pdf).unwrap();
+        let pages = document.get_pages();
+        assert_eq!(pages.len(), 1);
+        let page = *pages.values().next().unwrap();
+        let media_box = document
+            .get_object(page)
+            .and_then(Object::as_dict)
+            .and_then(|page| page.get(b"MediaBox"))
+            .and_then(Object::as_array)
+            .unwrap();
+        assert_eq!(media_box[2].as_float().unwrap().round(), 400.0);
+        assert_eq!(media_box[3].as_float().unwrap().round(), 200.0);
+
+        // 每个词一段文字，都在页面里
+        let content = Content::decode(&document.get_page_content(page).unwrap()).unwrap();
+        let runs = content
+            .operations
+            .iter()
+            .filter(|operation| operation.operator == "Tj")
+            .count();
+        let layout = wordcloud.generate_layout_from_text(text, size()).unwrap();
+        assert_eq!(runs, layout.words.len());
+        for operation in content.operations.iter().filter(|o| o.operator == "Tm") {
+            let x = operation.operands[4].as_float().unwrap();
+            let y = operation.operands[5].as_float().unwrap();
+            assert!((0.0..=400.0).contains(&x) && (0.0..=200.0).contains(&y));
+        }
+        // 字体嵌进去了，只嵌一次
+        let embedded = document
+            .objects
+            .values()
+            .filter_map(|object| object.as_dict().ok())
+            .filter(|dict| dict.has(b"FontFile2"))
+            .count();
+        assert_eq!(embedded, 1);
+    }
+}
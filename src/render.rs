@@ -0,0 +1,873 @@
+use ab_glyph::{point, Point, PxScale};
+use image::{GrayImage, Rgba, RgbaImage};
+use nanorand::WyRand;
+
+use crate::{text, DrawOrder, GlyphStyle, Layout, Word, WordCloud, WordCloudError};
+
+/// 按最终尺寸重新排版好的词，整张图和分块渲染共用
+pub(crate) struct ScaledLayout<'l> {
+    layout: &'l Layout<'l>,
+    pub width: u32,
+    pub height: u32,
+    scale_x: f32,
+    scale_y: f32,
+    pub words: Vec<ScaledWord<'l>>,
+    /// 布局大小的遮罩，只画在能放字的像素上
+    clip_mask: Option<&'l GrayImage>,
+    /// 超采样时缩小以后再画水印，水印的大小按最终的图算
+    draw_watermark: bool,
+}
+
+pub(crate) struct ScaledWord<'l> {
+    pub word: &'l Word<'l>,
+    pub glyphs: text::GlyphData,
+    pub font_size: PxScale,
+    pub position: Point,
+    pub color: Rgba<u8>,
+    /// 画出来的像素可能落到的范围 (left, top, right, bottom)，包括阴影和轮廓
+    bounds: (f32, f32, f32, f32),
+}
+
+impl WordCloud {
+    /// 算出最终画布的大小，按最终尺寸重新排版每个词并取好颜色
+    pub(crate) fn scale_layout<'l>(
+        &self,
+        layout: &'l Layout<'l>,
+        scale: f32,
+        color_func: impl FnMut(&Word, &mut WyRand) -> Rgba<u8>,
+    ) -> Result<ScaledLayout<'l>, WordCloudError> {
+        let (width, height, scale_x, scale_y) = self.output_size(layout, scale)?;
+
+        self.scale_layout_to(layout, (width, height), (scale_x, scale_y), color_func)
+    }
+
+    /// 最终画布的大小和横竖的缩放比例
+    fn output_size(
+        &self,
+        layout: &Layout,
+        scale: f32,
+    ) -> Result<(u32, u32, f32, f32), WordCloudError> {
+        Ok(match self.output_dimensions {
+            // 构建器会检查，`with_output_dimensions` 不会，这里再拦一次，不然缩放比例是 0
+            Some((width, height)) if width == 0 || height == 0 => {
+                return Err(WordCloudError::InvalidConfig(
+                    "output_dimensions must not be 0".to_string(),
+                ));
+            }
+            Some((width, height)) => (
+                width,
+                height,
+                width as f32 / layout.width as f32,
+                height as f32 / layout.height as f32,
+            ),
+            None => {
+                let (width, height) = self.scaled_dimensions(layout.width, layout.height, scale)?;
+                (width, height, scale, scale)
+            }
+        })
+    }
+
+    /// 按给定的画布大小和缩放比例重新排版
+    fn scale_layout_to<'l>(
+        &self,
+        layout: &'l Layout<'l>,
+        (width, height): (u32, u32),
+        (scale_x, scale_y): (f32, f32),
+        mut color_func: impl FnMut(&Word, &mut WyRand) -> Rgba<u8>,
+    ) -> Result<ScaledLayout<'l>, WordCloudError> {
+        let scale = uniform_scale(scale_x, scale_y);
+        let mut rng = layout.rng.clone();
+
+        let words = layout
+            .words
+            .iter()
+            .map(|word| {
+                // `word_colors` 里的词不管传进来的是哪个颜色函数都用固定的颜色
+                let color = match self.word_colors.get(word.text) {
+                    Some(color) => *color,
+                    None => color_func(word, &mut rng),
+                };
+
+                // 缩放时按最终尺寸重新排版，竖着的词字形的宽对应画布的高
+                let (glyphs, font_size, position) = if scale_x == 1.0 && scale_y == 1.0 {
+                    (word.glyphs.clone(), word.font_size, word.position)
+                } else {
+                    let (glyph_scale_x, glyph_scale_y) = if word.angle == 0.0 {
+                        (scale_x, scale_y)
+                    } else if word.angle.abs() == 90.0 {
+                        (scale_y, scale_x)
+                    } else {
+                        (scale, scale)
+                    };
+                    let font_size = PxScale {
+                        x: word.font_size.x * glyph_scale_x,
+                        y: word.font_size.y * glyph_scale_y,
+                    };
+                    let glyphs = text::text_to_glyphs(
+                        word.text,
+                        word.font,
+                        font_size,
+                        self.scaled_text_options(glyph_scale_x),
+                    );
+                    (
+                        glyphs,
+                        font_size,
+                        point(word.position.x * scale_x, word.position.y * scale_y),
+                    )
+                };
+
+                // 有的字形会超出排版的框，多留一些
+                let (width, height) =
+                    text::rotated_dimensions(glyphs.width, glyphs.height, word.angle);
+                let (shadow_left, shadow_top, shadow_right, shadow_bottom) = self
+                    .text_shadow
+                    .map(|shadow| shadow.extents(scale_x.max(scale_y)))
+                    .unwrap_or_default();
+                let padding = glyphs.height as f32 / 4.0 + 2.0;
+                let bounds = (
+                    position.x - shadow_left as f32 - padding,
+                    position.y - shadow_top as f32 - padding,
+                    position.x + (width + shadow_right) as f32 + padding,
+                    position.y + (height + shadow_bottom) as f32 + padding,
+                );
+
+                ScaledWord {
+                    word,
+                    glyphs,
+                    font_size,
+                    position,
+                    color,
+                    bounds,
+                }
+            })
+            .collect();
+
+        Ok(ScaledLayout {
+            layout,
+            width,
+            height,
+            scale_x,
+            scale_y,
+            words,
+            clip_mask: layout.clip_mask.as_ref(),
+            draw_watermark: true,
+        })
+    }
+
+    /// 按 `supersample` 倍的大小重新排版再画，然后把每 `supersample` x `supersample` 个像素
+    /// 平均成一个。颜色用 `scaled` 里已经取好的
+    fn render_supersampled(&self, scaled: &ScaledLayout) -> Result<RgbaImage, WordCloudError> {
+        let factor = self.supersample;
+        let (width, height) = (scaled.width, scaled.height);
+        let (large_width, large_height) = (width.checked_mul(factor), height.checked_mul(factor));
+        let (Some(large_width), Some(large_height)) = (large_width, large_height) else {
+            return Err(WordCloudError::CanvasTooLarge {
+                width: width as f64 * factor as f64,
+                height: height as f64 * factor as f64,
+            });
+        };
+
+        let mut colors = scaled.words.iter().map(|word| word.color);
+        let mut large = self.scale_layout_to(
+            scaled.layout,
+            (large_width, large_height),
+            (
+                scaled.scale_x * factor as f32,
+                scaled.scale_y * factor as f32,
+            ),
+            |_, _| colors.next().unwrap_or(Rgba([0, 0, 0, 0])),
+        )?;
+        large.draw_watermark = false;
+        let large = self.render_scaled_region(&large, 0, 0, large_width, large_height)?;
+
+        let mut image = downsample(&large, factor);
+        self.draw_watermark(&mut image, 0, 0, width, height);
+
+        Ok(image)
+    }
+
+    /// 画到一张新的最终画布上，设置了 `supersample` 时先画大再缩小
+    pub(crate) fn render_scaled_layout(
+        &self,
+        scaled: &ScaledLayout,
+    ) -> Result<RgbaImage, WordCloudError> {
+        if self.supersample > 1 {
+            return self.render_supersampled(scaled);
+        }
+
+        self.render_scaled_region(scaled, 0, 0, scaled.width, scaled.height)
+    }
+
+    /// 画最终画布上从 (`x`, `y`) 开始宽 `width` 高 `height` 的一块
+    pub(crate) fn render_scaled_region(
+        &self,
+        scaled: &ScaledLayout,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<RgbaImage, WordCloudError> {
+        let (width, height) = self.canvas_dimensions(width, height, 1.0)?;
+        let mut buffer = self.background(scaled, width, height, x, y);
+        self.draw_scaled_layout(scaled, &mut buffer, point(x as f32, y as f32));
+
+        Ok(buffer)
+    }
+
+    /// 铺好背景色或者背景渐变的一块画布，渐变按整张图上的位置算
+    fn background(
+        &self,
+        scaled: &ScaledLayout,
+        width: u32,
+        height: u32,
+        origin_x: u32,
+        origin_y: u32,
+    ) -> RgbaImage {
+        match &self.background_gradient {
+            Some(gradient) => RgbaImage::from_fn(width, height, |x, y| {
+                gradient.color_at(x + origin_x, y + origin_y, scaled.width, scaled.height)
+            }),
+            None => RgbaImage::from_pixel(width, height, self.background_color),
+        }
+    }
+
+    /// 把最终画布上从 `origin` 开始的一块画到 `buffer` 上，只画和这一块有重叠的词。
+    /// `origin` 是负的时候画布画在 `buffer` 里面偏右下的地方
+    pub(crate) fn draw_scaled_layout(
+        &self,
+        scaled: &ScaledLayout,
+        buffer: &mut RgbaImage,
+        origin: Point,
+    ) {
+        let (scale_x, scale_y) = (scaled.scale_x, scaled.scale_y);
+        // 阴影、轮廓这些没有方向的长度按平均的缩放算
+        let scale = uniform_scale(scale_x, scale_y);
+        // 路径上的词和水印按整像素移动
+        let (origin_x, origin_y) = (origin.x.round() as i64, origin.y.round() as i64);
+        let (right, bottom) = (
+            origin.x + buffer.width() as f32,
+            origin.y + buffer.height() as f32,
+        );
+
+        let fade = |mut color: Rgba<u8>| {
+            color.0[3] = (color.0[3] as f32 * self.global_alpha).round() as u8;
+            color
+        };
+
+        for path_word in &self.path_words {
+            let glyphs = self.path_word_glyphs(path_word, scale_x, scale_y);
+            let color = fade(path_word.color);
+            text::for_each_path_glyph_pixel(&glyphs, &self.font, |x, y, v| {
+                let (x, y) = (x - origin_x, y - origin_y);
+                if x < 0 || y < 0 {
+                    return;
+                }
+                if let Some(px) = buffer.get_pixel_mut_checked(x as u32, y as u32) {
+                    text::blend(px, color, v);
+                }
+            });
+        }
+
+        let mut context = text::DrawContext::new(self.font_index);
+        let mut order = scaled.words.iter().collect::<Vec<_>>();
+        match &self.draw_order {
+            DrawOrder::Placement => {}
+            DrawOrder::FrequencyDescending => {
+                order.sort_by(|a, b| b.word.frequency.total_cmp(&a.word.frequency))
+            }
+            DrawOrder::FrequencyAscending => {
+                order.sort_by(|a, b| a.word.frequency.total_cmp(&b.word.frequency))
+            }
+            DrawOrder::Custom(compare) => order.sort_by(|a, b| compare(a.word, b.word)),
+        }
+
+        for scaled_word in order {
+            let (left, top, word_right, word_bottom) = scaled_word.bounds;
+            if word_right <= origin.x || word_bottom <= origin.y || left >= right || top >= bottom {
+                continue;
+            }
+
+            // 画之前记下外框里遮罩外面的像素，画完再放回去
+            let outside_mask = scaled
+                .clip_mask
+                .map(|mask| pixels_outside_mask(buffer, mask, scaled, origin, scaled_word.bounds));
+
+            let word = scaled_word.word;
+            let glyphs = &scaled_word.glyphs;
+            let position = point(
+                scaled_word.position.x - origin.x,
+                scaled_word.position.y - origin.y,
+            );
+
+            if let Some(shadow) = &self.text_shadow {
+                text::draw_shadow_to_rgba_buffer(
+                    buffer,
+                    glyphs,
+                    word.font,
+                    position,
+                    word.angle,
+                    point(
+                        shadow.offset_x as f32 * scale_x,
+                        shadow.offset_y as f32 * scale_y,
+                    ),
+                    shadow.blur * scale,
+                    fade(shadow.color),
+                );
+            }
+
+            match self.glyph_style {
+                GlyphStyle::Fill => text::draw_glyphs_to_rgba_buffer(
+                    buffer,
+                    &mut context,
+                    glyphs,
+                    word.font,
+                    position,
+                    word.angle,
+                    fade(scaled_word.color),
+                ),
+                GlyphStyle::Outline { width } => text::draw_outline_to_rgba_buffer(
+                    buffer,
+                    glyphs,
+                    word.font,
+                    position,
+                    word.angle,
+                    width * scale,
+                    fade(scaled_word.color),
+                ),
+            }
+
+            for (x, y, px) in outside_mask.into_iter().flatten() {
+                buffer.put_pixel(x, y, px);
+            }
+        }
+
+        if scaled.draw_watermark {
+            self.draw_watermark(buffer, origin_x, origin_y, scaled.width, scaled.height);
+        }
+    }
+
+    /// Renders a layout in square tiles of `tile_size` pixels, for images too large to hold in
+    /// memory at once. `tile` is called with the column, row and image of every tile, left to
+    /// right and top to bottom. Tiles on the right and bottom edges may be smaller. Only the
+    /// words that overlap a tile are drawn into it
+    pub fn render_layout_tiled(
+        &self,
+        layout: &Layout,
+        scale: f32,
+        tile_size: u32,
+        mut tile: impl FnMut(u32, u32, RgbaImage),
+    ) -> Result<(), WordCloudError> {
+        if tile_size == 0 {
+            return Err(WordCloudError::InvalidConfig(
+                "tile_size must be greater than 0".to_string(),
+            ));
+        }
+
+        let scaled = self.scale_layout(layout, scale, |word, rng| self.word_color(word, rng))?;
+        for row in 0..scaled.height.div_ceil(tile_size) {
+            for col in 0..scaled.width.div_ceil(tile_size) {
+                let (x, y) = (col * tile_size, row * tile_size);
+                let buffer = self.render_scaled_region(
+                    &scaled,
+                    x,
+                    y,
+                    tile_size.min(scaled.width - x),
+                    tile_size.min(scaled.height - y),
+                )?;
+                tile(col, row, buffer);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Draws a layout at its own size into an existing image, with the top left corner of the
+    /// layout at `offset`. No background is drawn, and pixels that fall outside `target` are
+    /// skipped, so `offset` may be negative or put the layout partly past the edges
+    pub fn draw_layout_at(
+        &self,
+        layout: &Layout,
+        target: &mut RgbaImage,
+        offset: Point,
+        color_func: impl FnMut(&Word, &mut WyRand) -> Rgba<u8>,
+    ) -> Result<(), WordCloudError> {
+        // 按布局自己的大小画，不管 `output_dimensions`
+        let scaled = self.scale_layout_to(
+            layout,
+            (layout.width, layout.height),
+            (1.0, 1.0),
+            color_func,
+        )?;
+        self.draw_scaled_layout(&scaled, target, point(-offset.x, -offset.y));
+
+        Ok(())
+    }
+}
+
+/// `buffer` 上 `bounds`（最终画布坐标）里落在遮罩外面的像素和它们现在的颜色。
+/// 按像素中心在布局上的位置查遮罩
+fn pixels_outside_mask(
+    buffer: &RgbaImage,
+    mask: &GrayImage,
+    scaled: &ScaledLayout,
+    origin: Point,
+    (left, top, right, bottom): (f32, f32, f32, f32),
+) -> Vec<(u32, u32, Rgba<u8>)> {
+    let clamp_x = |x: f32| (x - origin.x).clamp(0.0, buffer.width() as f32) as u32;
+    let clamp_y = |y: f32| (y - origin.y).clamp(0.0, buffer.height() as f32) as u32;
+
+    // 画布外面（`origin` 是负的时候）也不能画
+    let to_mask = |v: u32, origin: f32, scale: f32| {
+        let v = (v as f32 + origin + 0.5) / scale;
+        (v >= 0.0).then_some(v as u32)
+    };
+
+    let mut outside = vec![];
+    for y in clamp_y(top.floor())..clamp_y(bottom.ceil()) {
+        let mask_y = to_mask(y, origin.y, scaled.scale_y);
+        for x in clamp_x(left.floor())..clamp_x(right.ceil()) {
+            let mask_x = to_mask(x, origin.x, scaled.scale_x);
+            let available = mask_x
+                .zip(mask_y)
+                .and_then(|(mask_x, mask_y)| mask.get_pixel_checked(mask_x, mask_y))
+                .is_some_and(|px| px.0[0] == 0);
+            if !available {
+                outside.push((x, y, *buffer.get_pixel(x, y)));
+            }
+        }
+    }
+
+    outside
+}
+
+/// 每 `factor` x `factor` 个像素取平均，颜色按不透明度加权，透明的像素不会把边缘染黑
+fn downsample(image: &RgbaImage, factor: u32) -> RgbaImage {
+    let (width, height) = (image.width() / factor, image.height() / factor);
+    let count = (factor * factor) as u64;
+
+    RgbaImage::from_fn(width, height, |x, y| {
+        let mut sums = [0u64; 4];
+        for dy in 0..factor {
+            for dx in 0..factor {
+                let [r, g, b, a] = image.get_pixel(x * factor + dx, y * factor + dy).0;
+                let a = a as u64;
+                sums[0] += r as u64 * a;
+                sums[1] += g as u64 * a;
+                sums[2] += b as u64 * a;
+                sums[3] += a;
+            }
+        }
+
+        let alpha = sums[3];
+        if alpha == 0 {
+            return Rgba([0, 0, 0, 0]);
+        }
+        let channel = |sum: u64| ((sum + alpha / 2) / alpha) as u8;
+        Rgba([
+            channel(sums[0]),
+            channel(sums[1]),
+            channel(sums[2]),
+            ((alpha + count / 2) / count) as u8,
+        ])
+    })
+}
+
+fn uniform_scale(scale_x: f32, scale_y: f32) -> f32 {
+    if scale_x == scale_y {
+        scale_x
+    } else {
+        (scale_x * scale_y).sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use ab_glyph::point;
+    use image::{GenericImage, GrayImage, Luma, Rgba, RgbaImage};
+    use nanorand::WyRand;
+
+    use super::downsample;
+    use crate::{
+        DrawOrder, GradientSpec, PathWord, Word, WordCloud, WordCloudError, WordCloudSize, WordPath,
+    };
+
+    #[test]
+    fn tiles_match_full_render() {
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(3)
+            .with_word_rotate_chance(0.5)
+            .with_text_shadow(2, 3, 1.5, Rgba([0, 0, 0, 128]));
+        let layout = wordcloud
+            .generate_layout_from_text(
+                "apple apple apple banana banana cherry date elderberry fig grape",
+                WordCloudSize::FromDimensions {
+                    width: 200,
+                    height: 100,
+                },
+            )
+            .unwrap();
+
+        let full =
+            wordcloud.render_layout(&layout, 1.5, |word, rng| wordcloud.word_color(word, rng));
+        let mut stitched = RgbaImage::new(full.width(), full.height());
+        let mut tiles = 0;
+        wordcloud
+            .render_layout_tiled(&layout, 1.5, 64, |col, row, tile| {
+                assert!(tile.width() <= 64 && tile.height() <= 64);
+                stitched.copy_from(&tile, col * 64, row * 64).unwrap();
+                tiles += 1;
+            })
+            .unwrap();
+
+        assert_eq!(tiles, 5 * 3);
+        assert!(stitched == full);
+
+        assert!(matches!(
+            wordcloud.render_layout_tiled(&layout, 1.0, 0, |_, _, _| {}),
+            Err(WordCloudError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn gradient_tiles_match_full_render() {
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(3)
+            .with_background_gradient(GradientSpec::radial(vec![
+                Rgba([20, 20, 60, 255]),
+                Rgba([0, 0, 0, 255]),
+            ]));
+        let layout = wordcloud
+            .generate_layout_from_text(
+                "apple apple apple banana banana cherry date elderberry fig grape",
+                WordCloudSize::FromDimensions {
+                    width: 200,
+                    height: 100,
+                },
+            )
+            .unwrap();
+
+        let full =
+            wordcloud.render_layout(&layout, 1.5, |word, rng| wordcloud.word_color(word, rng));
+        let mut stitched = RgbaImage::new(full.width(), full.height());
+        wordcloud
+            .render_layout_tiled(&layout, 1.5, 64, |col, row, tile| {
+                stitched.copy_from(&tile, col * 64, row * 64).unwrap();
+            })
+            .unwrap();
+
+        assert!(stitched == full);
+        // 背景渐变按整张图算，不是每块各画一个
+        assert_ne!(full.get_pixel(0, 0), full.get_pixel(150, 75));
+    }
+
+    #[test]
+    fn pixel_clip_to_mask() {
+        // 只有左半边能放字
+        let mask = GrayImage::from_fn(400, 200, |x, _| Luma([if x < 200 { 0 } else { 255 }]));
+        let background = Rgba([0, 0, 0, 255]);
+        let ink_on_right = |clip: bool, scale: f32| {
+            let wordcloud = WordCloud::default()
+                .with_rng_seed(1)
+                .with_pixel_clip_to_mask(clip);
+            let mut layout = wordcloud
+                .generate_layout_from_text(
+                    "apple apple apple banana banana cherry date",
+                    WordCloudSize::FromMask(mask.clone()),
+                )
+                .unwrap();
+            // 把第一个词挪到遮罩的边上
+            layout.words[0].position.x = 150.0;
+            let image = wordcloud.render_layout(&layout, scale, |_, _| Rgba([255, 0, 0, 255]));
+
+            let right = (200.0 * scale) as u32;
+            let (left, right): (Vec<_>, Vec<_>) = image
+                .enumerate_pixels()
+                .filter(|(_, _, px)| **px != background)
+                .partition(|(x, _, _)| *x < right);
+            assert!(!left.is_empty());
+            right.len()
+        };
+
+        assert!(ink_on_right(false, 1.0) > 0);
+        assert_eq!(ink_on_right(true, 1.0), 0);
+        assert_eq!(ink_on_right(true, 2.0), 0);
+    }
+
+    #[test]
+    fn global_alpha() {
+        let render = |alpha: f32| {
+            let wordcloud = WordCloud::default()
+                .with_rng_seed(1)
+                .with_transparent_background()
+                .with_global_alpha(alpha);
+            wordcloud.generate_from_text_with_color_func(
+                "apple apple apple banana banana cherry",
+                WordCloudSize::FromDimensions {
+                    width: 200,
+                    height: 100,
+                },
+                1.0,
+                |_, _| Rgba([255, 0, 0, 255]),
+            )
+        };
+
+        let opaque = render(1.0);
+        let faded = render(0.6);
+        assert!(opaque.pixels().any(|px| px.0[3] == 255));
+        for (full, px) in opaque.pixels().zip(faded.pixels()) {
+            if full.0[3] == 255 {
+                assert_eq!(px.0[3], 153);
+            }
+            // 相邻字形的抗锯齿边缘叠在一起时比直接乘 0.6 稍微深一点
+            assert!(px.0[3] <= full.0[3], "{full:?} {px:?}");
+            assert!(
+                px.0[3] as f32 >= full.0[3] as f32 * 0.6 - 1.0,
+                "{full:?} {px:?}"
+            );
+        }
+        assert!(render(0.0).pixels().all(|px| px.0[3] == 0));
+    }
+
+    #[test]
+    fn global_alpha_path_words() {
+        let path_word = |alpha: f32| {
+            WordCloud::default()
+                .with_rng_seed(1)
+                .with_transparent_background()
+                .with_global_alpha(alpha)
+                .with_path_word(PathWord {
+                    text: "banana".to_string(),
+                    font_size: 40.0,
+                    path: WordPath::Polyline(vec![point(0.0, 60.0), point(200.0, 60.0)]),
+                    color: Rgba([0, 0, 255, 255]),
+                })
+                .generate_from_text_with_color_func(
+                    "apple",
+                    WordCloudSize::FromDimensions {
+                        width: 200,
+                        height: 100,
+                    },
+                    1.0,
+                    |_, _| Rgba([255, 0, 0, 255]),
+                )
+        };
+        let blue = |image: &RgbaImage| {
+            image
+                .pixels()
+                .filter(|px| px.0[2] > 0 && px.0[0] == 0)
+                .map(|px| px.0[3])
+                .max()
+        };
+        assert_eq!(blue(&path_word(1.0)), Some(255));
+        assert_eq!(blue(&path_word(0.6)), Some(153));
+    }
+
+    #[test]
+    fn supersample() {
+        // 透明的像素不参与颜色的平均
+        let mut block = RgbaImage::new(4, 2);
+        block.put_pixel(0, 0, Rgba([255, 255, 255, 255]));
+        block.put_pixel(2, 0, Rgba([100, 0, 0, 255]));
+        block.put_pixel(3, 1, Rgba([0, 0, 200, 255]));
+        let small = downsample(&block, 2);
+        assert_eq!(small.dimensions(), (2, 1));
+        assert_eq!(small.get_pixel(0, 0), &Rgba([255, 255, 255, 64]));
+        assert_eq!(small.get_pixel(1, 0), &Rgba([50, 0, 100, 128]));
+
+        let text = "apple apple banana cherry date elderberry fig grape kiwi lemon mango";
+        let size = || WordCloudSize::FromDimensions {
+            width: 160,
+            height: 60,
+        };
+        let image = |factor| {
+            WordCloud::default()
+                .with_rng_seed(1)
+                .with_min_font_size(6.0)
+                .with_supersample(factor)
+                .generate_from_text(text, size(), 1.0)
+        };
+        let (plain, smooth) = (image(1), image(4));
+        assert_eq!(plain.dimensions(), smooth.dimensions());
+        assert_ne!(plain, smooth);
+
+        // 位置和颜色一样，只有边缘不同：墨水的总量差不多，每个像素差得不多
+        let brightness = |image: &RgbaImage| -> u64 {
+            image
+                .pixels()
+                .map(|px| px.0[..3].iter().map(|c| *c as u64).sum::<u64>())
+                .sum()
+        };
+        let (plain_ink, smooth_ink) = (brightness(&plain) as f64, brightness(&smooth) as f64);
+        assert!((smooth_ink / plain_ink - 1.0).abs() < 0.15);
+        let mean_diff = plain
+            .pixels()
+            .zip(smooth.pixels())
+            .map(|(a, b)| (0..3).map(|i| a.0[i].abs_diff(b.0[i]) as u64).sum::<u64>())
+            .sum::<u64>() as f64
+            / (plain.len() as f64);
+        assert!(mean_diff < 10.0, "{mean_diff}");
+
+        assert!(WordCloud::builder().supersample(0).build().is_err());
+    }
+
+    #[test]
+    fn draw_order() {
+        let (red, blue) = (Rgba([255, 0, 0, 255]), Rgba([0, 0, 255, 255]));
+        let red_pixels = |draw_order: DrawOrder| {
+            let wordcloud = WordCloud::default()
+                .with_rng_seed(1)
+                .with_draw_order(draw_order);
+            let mut layout = wordcloud
+                .generate_layout_from_text(
+                    "apple apple apple banana banana",
+                    WordCloudSize::FromDimensions {
+                        width: 200,
+                        height: 100,
+                    },
+                )
+                .unwrap();
+            // 把两个词叠在一起
+            layout.words[1].position = layout.words[0].position;
+
+            let image =
+                wordcloud.render_layout(
+                    &layout,
+                    1.0,
+                    |word, _| {
+                        if word.index == 0 {
+                            red
+                        } else {
+                            blue
+                        }
+                    },
+                );
+            image.pixels().filter(|px| **px == red).count()
+        };
+
+        let placement = red_pixels(DrawOrder::Placement);
+        let descending = red_pixels(DrawOrder::FrequencyDescending);
+        let ascending = red_pixels(DrawOrder::FrequencyAscending);
+        let custom = red_pixels(DrawOrder::Custom(Box::new(|a, b| b.index.cmp(&a.index))));
+        assert_eq!(placement, descending);
+        assert!(ascending > descending);
+        assert_eq!(custom, ascending);
+    }
+
+    #[test]
+    fn draw_layout_at() {
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_transparent_background();
+        let layout = wordcloud
+            .generate_layout_from_text(
+                "apple apple apple banana banana cherry",
+                WordCloudSize::FromDimensions {
+                    width: 200,
+                    height: 100,
+                },
+            )
+            .unwrap();
+        let color = |_: &Word, _: &mut WyRand| Rgba([255, 0, 0, 255]);
+        let alone = wordcloud.render_layout(&layout, 1.0, color);
+
+        for (offset_x, offset_y) in [(30, 20), (-50, -30), (150, 60)] {
+            let mut target = RgbaImage::new(260, 140);
+            wordcloud
+                .draw_layout_at(
+                    &layout,
+                    &mut target,
+                    point(offset_x as f32, offset_y as f32),
+                    color,
+                )
+                .unwrap();
+            for (x, y, px) in target.enumerate_pixels() {
+                let (x, y) = (x as i32 - offset_x, y as i32 - offset_y);
+                let expected = if (0..200).contains(&x) && (0..100).contains(&y) {
+                    *alone.get_pixel(x as u32, y as u32)
+                } else {
+                    Rgba([0, 0, 0, 0])
+                };
+                assert_eq!(*px, expected, "{offset_x} {offset_y} {x} {y}");
+            }
+        }
+
+        // 设置了输出大小也按布局自己的大小画
+        let mut plain = RgbaImage::new(260, 140);
+        let mut sized = RgbaImage::new(260, 140);
+        wordcloud
+            .draw_layout_at(&layout, &mut plain, point(30.0, 20.0), color)
+            .unwrap();
+        WordCloud::default()
+            .with_rng_seed(1)
+            .with_output_dimensions(400, 200)
+            .draw_layout_at(&layout, &mut sized, point(30.0, 20.0), color)
+            .unwrap();
+        assert!(plain == sized);
+    }
+
+    #[test]
+    fn word_colors_with_color_func() {
+        let brand = Rgba([0, 82, 155, 255]);
+        let fallback = Rgba([128, 128, 128, 255]);
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_word_colors(HashMap::from([("apple".to_string(), brand)]));
+        let layout = wordcloud
+            .generate_layout_from_text(
+                "apple apple apple banana banana cherry",
+                WordCloudSize::FromDimensions {
+                    width: 200,
+                    height: 100,
+                },
+            )
+            .unwrap();
+
+        // 传进来的颜色函数也不会盖掉 `word_colors`
+        let image = wordcloud.render_layout(&layout, 1.0, |_, _| fallback);
+        assert!(image.pixels().any(|px| *px == brand));
+        assert!(image.pixels().any(|px| *px == fallback));
+        let scaled = wordcloud
+            .scale_layout(&layout, 1.0, |_, _| fallback)
+            .unwrap();
+        for word in &scaled.words {
+            let expected = if word.word.text == "apple" {
+                brand
+            } else {
+                fallback
+            };
+            assert_eq!(word.color, expected, "{}", word.word.text);
+        }
+    }
+
+    #[test]
+    fn scaled_letter_spacing() {
+        // 字距是画布上的像素，放大两倍画时也宽两倍
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_word_rotate_chance(0.0)
+            .with_letter_spacing(4.0);
+        let layout = wordcloud
+            .generate_layout_from_text(
+                "apple apple apple banana banana cherry",
+                WordCloudSize::FromDimensions {
+                    width: 300,
+                    height: 150,
+                },
+            )
+            .unwrap();
+        let color = |_: &Word, _: &mut WyRand| Rgba([255, 255, 255, 255]);
+        let scaled = wordcloud.scale_layout(&layout, 2.0, color).unwrap();
+
+        for word in &scaled.words {
+            let gap = |glyphs: &[ab_glyph::Glyph]| glyphs[1].position.x - glyphs[0].position.x;
+            let expected = gap(&word.word.glyphs.glyphs) * 2.0;
+            assert!(
+                (gap(&word.glyphs.glyphs) - expected).abs() < 0.5,
+                "{}",
+                word.word.text
+            );
+        }
+    }
+}
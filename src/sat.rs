@@ -1,5 +1,21 @@
+//! 求和面积表和找空位，只依赖 `core` 和 `alloc`，不开 `std` feature 也能用（比如 WASM）
+
+use alloc::vec;
+
 use nanorand::{Rng, WyRand};
 
+/// The randomness placement needs, so any RNG can drive `find_space_for_rect`
+pub trait PlacementRng {
+    /// A uniformly distributed number in `0..=max`
+    fn generate_up_to(&mut self, max: u32) -> u32;
+}
+
+impl PlacementRng for WyRand {
+    fn generate_up_to(&mut self, max: u32) -> u32 {
+        self.generate_range(0..=max)
+    }
+}
+
 #[derive(Debug)]
 pub struct Rect {
     pub width: u32,
@@ -35,18 +51,95 @@ pub fn find_space_for_rect_masked(
     table_height: u32,
     skip_list: &[(usize, usize)],
     rect: &Rect,
-    rng: &mut WyRand,
+    rng: &mut impl PlacementRng,
+) -> Option<Point> {
+    find_space_for_rect_masked_filtered(
+        table,
+        table_width,
+        table_height,
+        skip_list,
+        rect,
+        rng,
+        |_, _| true,
+    )
+}
+
+/// 和 `find_space_for_rect_masked` 一样，`accept` 返回 false 的空位会被跳过
+pub fn find_space_for_rect_masked_filtered(
+    table: &[u32],
+    table_width: u32,
+    table_height: u32,
+    skip_list: &[(usize, usize)],
+    rect: &Rect,
+    rng: &mut impl PlacementRng,
+    accept: impl FnMut(u32, u32) -> bool,
+) -> Option<Point> {
+    find_candidates_for_rect(
+        table,
+        table_width,
+        table_height,
+        Some(skip_list),
+        rect,
+        1,
+        rng,
+        accept,
+    )
+    .pop()
+}
+
+/// 在图片寻找位置写字
+pub fn find_space_for_rect(
+    table: &[u32],
+    table_width: u32,
+    table_height: u32,
+    rect: &Rect,
+    rng: &mut impl PlacementRng,
 ) -> Option<Point> {
+    find_space_for_rect_filtered(table, table_width, table_height, rect, rng, |_, _| true)
+}
+
+/// 和 `find_space_for_rect` 一样，`accept` 返回 false 的空位会被跳过
+pub fn find_space_for_rect_filtered(
+    table: &[u32],
+    table_width: u32,
+    table_height: u32,
+    rect: &Rect,
+    rng: &mut impl PlacementRng,
+    accept: impl FnMut(u32, u32) -> bool,
+) -> Option<Point> {
+    find_candidates_for_rect(table, table_width, table_height, None, rect, 1, rng, accept).pop()
+}
+
+/// Up to `count` free positions for `rect`, picked uniformly at random from all free positions
+/// `accept` returns true for. `skip_list` limits every row to a `(left, right)` range of x.
+/// With a `count` of 1 this is the same as `find_space_for_rect`, using the same random numbers
+#[allow(clippy::too_many_arguments)]
+pub fn find_candidates_for_rect(
+    table: &[u32],
+    table_width: u32,
+    table_height: u32,
+    skip_list: Option<&[(usize, usize)]>,
+    rect: &Rect,
+    count: usize,
+    rng: &mut impl PlacementRng,
+    mut accept: impl FnMut(u32, u32) -> bool,
+) -> vec::Vec<Point> {
     let max_x = table_width - rect.width;
     let max_y = table_height - rect.height;
 
     let mut available_points: u32 = 0;
-    let mut random_pont = None;
+    let mut candidates = vec::Vec::with_capacity(count.min(64));
 
     // column based
     for y in 0..max_y {
-        let (furthest_left, furthest_right) = skip_list[y as usize];
-        for x in furthest_left..furthest_right.min(max_x as usize) {
+        let (furthest_left, furthest_right) = match skip_list {
+            Some(skip_list) => (
+                skip_list[y as usize].0,
+                skip_list[y as usize].1.min(max_x as usize),
+            ),
+            None => (0, max_x as usize),
+        };
+        for x in furthest_left..furthest_right {
             let empty = region_is_empty(
                 table,
                 table_width as usize,
@@ -55,36 +148,81 @@ pub fn find_space_for_rect_masked(
                 rect.width as usize,
                 rect.height as usize,
             );
-            if empty {
-                let random_num = rng.generate_range(0..=available_points);
-                if random_num == available_points {
-                    random_pont = Some(Point { x: x as u32, y });
+            if empty && accept(x as u32, y) {
+                // 蓄水池抽样，第 n 个空位有 count / (n + 1) 的机会留下
+                let random_num = rng.generate_up_to(available_points);
+                let point = Point { x: x as u32, y };
+                if candidates.len() < count {
+                    candidates.push(point);
+                } else {
+                    let slot = (available_points - random_num) as usize;
+                    if slot < count {
+                        candidates[slot] = point;
+                    }
                 }
                 available_points += 1;
             }
         }
     }
 
-    random_pont
+    candidates
 }
 
-/// 在图片寻找位置写字
-pub fn find_space_for_rect(
+/// The sum of the table over `rect` at (`x`, `y`) grown by `margin` on every side, clipped to
+/// the table. For a table built from 0/1 cells it's the number of occupied cells around the
+/// position, higher means more surrounded
+pub fn surrounding_occupancy(
+    table: &[u32],
+    table_width: u32,
+    table_height: u32,
+    point: &Point,
+    rect: &Rect,
+    margin: u32,
+) -> u32 {
+    // 求和面积表最后一行一列只能当右下角
+    let left = point.x.saturating_sub(margin) as usize;
+    let top = point.y.saturating_sub(margin) as usize;
+    let right = (point.x + rect.width + margin).min(table_width - 1) as usize;
+    let bottom = (point.y + rect.height + margin).min(table_height - 1) as usize;
+    let width = table_width as usize;
+
+    let tl = table[top * width + left];
+    let tr = table[top * width + right];
+    let bl = table[bottom * width + left];
+    let br = table[bottom * width + right];
+
+    (tl as i64 + br as i64 - tr as i64 - bl as i64) as u32
+}
+
+/// 找离 (`target_x`, `target_y`) 最近的空位，用来在原来的位置附近重新放一个词。
+/// `accept` 返回 false 的空位会被跳过
+pub fn find_nearest_space_for_rect(
     table: &[u32],
     table_width: u32,
     table_height: u32,
     rect: &Rect,
-    rng: &mut WyRand,
+    target_x: f32,
+    target_y: f32,
+    mut accept: impl FnMut(u32, u32) -> bool,
 ) -> Option<Point> {
+    if rect.width > table_width || rect.height > table_height {
+        return None;
+    }
     let max_x = table_width - rect.width;
     let max_y = table_height - rect.height;
 
-    let mut available_points: u32 = 0;
-    let mut random_pont = None;
-
-    // column based
+    let mut nearest: Option<(f32, Point)> = None;
     for y in 0..max_y {
         for x in 0..max_x {
+            let (dx, dy) = (x as f32 - target_x, y as f32 - target_y);
+            let distance = dx * dx + dy * dy;
+            if nearest
+                .as_ref()
+                .is_some_and(|(nearest_distance, _)| *nearest_distance <= distance)
+            {
+                continue;
+            }
+
             let empty = region_is_empty(
                 table,
                 table_width as usize,
@@ -93,17 +231,56 @@ pub fn find_space_for_rect(
                 rect.width as usize,
                 rect.height as usize,
             );
-            if empty {
-                let random_num = rng.generate_range(0..=available_points);
-                if random_num == available_points {
-                    random_pont = Some(Point { x, y });
+            if empty && accept(x, y) {
+                nearest = Some((distance, Point { x, y }));
+            }
+        }
+    }
+
+    nearest.map(|(_, point)| point)
+}
+
+/// The largest (by area) rect of free cells in a `width` by `height` grid, where non-zero
+/// cells are occupied
+pub fn largest_empty_rect(cells: &[u8], width: u32, height: u32) -> Rect {
+    // 每一行往上数连续空格子的高度，当成直方图求最大矩形
+    let width = width as usize;
+    let mut heights = vec![0u32; width];
+    let mut stack = vec::Vec::with_capacity(width + 1);
+    let mut largest = Rect {
+        width: 0,
+        height: 0,
+    };
+
+    for row in cells.chunks_exact(width).take(height as usize) {
+        for (h, cell) in heights.iter_mut().zip(row) {
+            *h = if *cell == 0 { *h + 1 } else { 0 };
+        }
+
+        stack.clear();
+        for x in 0..=width {
+            let h = if x < width { heights[x] } else { 0 };
+            while let Some(&top) = stack.last() {
+                if heights[top] < h {
+                    break;
+                }
+                stack.pop();
+                let left = stack.last().map_or(0, |left| left + 1);
+                let rect_width = (x - left) as u32;
+                if rect_width as u64 * heights[top] as u64
+                    > largest.width as u64 * largest.height as u64
+                {
+                    largest = Rect {
+                        width: rect_width,
+                        height: heights[top],
+                    };
                 }
-                available_points += 1;
             }
+            stack.push(x);
         }
     }
 
-    random_pont
+    largest
 }
 
 /// https://blog.demofox.org/2018/04/16/prefix-sums-and-summed-area-tables/
@@ -0,0 +1,139 @@
+use crate::{text, Layout};
+
+/// Rates how well a layout is packed on a `width` by `height` canvas, from 0.0 to 1.0, so runs
+/// with different seeds or settings can be compared. The mean of three parts:
+///
+/// - fill: how much of the canvas the words' bounding boxes cover
+/// - balance: how close the words' center of mass is to the center of the canvas
+/// - hierarchy: how many pairs of words with different frequencies have the more frequent
+///   word in the larger font
+pub fn layout_score(layout: &Layout, width: u32, height: u32) -> f32 {
+    if layout.words.is_empty() || width == 0 || height == 0 {
+        return 0.0;
+    }
+
+    let boxes = word_boxes(layout);
+    let words_area = boxes.iter().map(|(_, _, w, h)| w * h).sum::<f32>();
+    let fill = fill_ratio(layout, width, height);
+
+    // 按面积加权的重心离画布中心越远越差，到角上是 0
+    let balance = if words_area > 0.0 {
+        let (sum_x, sum_y) = boxes
+            .iter()
+            .fold((0.0, 0.0), |(sum_x, sum_y), (x, y, w, h)| {
+                let area = w * h;
+                (sum_x + (x + w / 2.0) * area, sum_y + (y + h / 2.0) * area)
+            });
+        let (half_width, half_height) = (width as f32 / 2.0, height as f32 / 2.0);
+        let dx = sum_x / words_area - half_width;
+        let dy = sum_y / words_area - half_height;
+        let distance = (dx * dx + dy * dy).sqrt() / half_width.hypot(half_height);
+        (1.0 - distance).max(0.0)
+    } else {
+        0.0
+    };
+
+    // 频率不同的每一对词里，频率高的字号不比频率低的小
+    let (mut ordered, mut pairs) = (0usize, 0usize);
+    for (i, a) in layout.words.iter().enumerate() {
+        for b in &layout.words[i + 1..] {
+            if a.frequency == b.frequency {
+                continue;
+            }
+            let (more, less) = if a.frequency > b.frequency {
+                (a, b)
+            } else {
+                (b, a)
+            };
+            pairs += 1;
+            if more.font_size.y >= less.font_size.y {
+                ordered += 1;
+            }
+        }
+    }
+    let hierarchy = if pairs == 0 {
+        1.0
+    } else {
+        ordered as f32 / pairs as f32
+    };
+
+    (fill + balance + hierarchy) / 3.0
+}
+
+/// 词的外框 (x, y, width, height)
+fn word_boxes(layout: &Layout) -> Vec<(f32, f32, f32, f32)> {
+    layout
+        .words
+        .iter()
+        .map(|word| {
+            let (w, h) =
+                text::rotated_dimensions(word.glyphs.width, word.glyphs.height, word.angle);
+            (word.position.x, word.position.y, w as f32, h as f32)
+        })
+        .collect()
+}
+
+/// 词的外框占画布面积的比例，最多 1.0
+pub(crate) fn fill_ratio(layout: &Layout, width: u32, height: u32) -> f32 {
+    if width == 0 || height == 0 {
+        return 0.0;
+    }
+    let canvas_area = width as f32 * height as f32;
+    let words_area = word_boxes(layout)
+        .iter()
+        .map(|(_, _, w, h)| w * h)
+        .sum::<f32>();
+
+    (words_area / canvas_area).min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use ab_glyph::point;
+
+    use super::layout_score;
+    use crate::{WordCloud, WordCloudSize};
+
+    #[test]
+    fn score_prefers_better_packing() {
+        let wordcloud = WordCloud::default().with_rng_seed(1);
+        let generate = || {
+            wordcloud
+                .generate_layout_from_text(
+                    "apple apple apple banana banana cherry date elderberry fig grape",
+                    WordCloudSize::FromDimensions {
+                        width: 400,
+                        height: 200,
+                    },
+                )
+                .unwrap()
+        };
+
+        let layout = generate();
+        let score = layout_score(&layout, 400, 200);
+        assert!(score > 0.0 && score <= 1.0, "{score}");
+
+        // 所有词挤到左上角
+        let mut cornered = generate();
+        for word in &mut cornered.words {
+            word.position = point(0.0, 0.0);
+        }
+        assert!(layout_score(&cornered, 400, 200) < score);
+
+        // 字号和频率反过来
+        let mut inverted = generate();
+        let sizes = inverted
+            .words
+            .iter()
+            .map(|word| word.font_size)
+            .collect::<Vec<_>>();
+        for (word, size) in inverted.words.iter_mut().zip(sizes.into_iter().rev()) {
+            word.font_size = size;
+        }
+        assert!(layout_score(&inverted, 400, 200) < score);
+
+        let mut empty = generate();
+        empty.words.clear();
+        assert_eq!(layout_score(&empty, 400, 200), 0.0);
+    }
+}
@@ -1,5 +1,7 @@
 use ab_glyph::{point, Font, FontVec, Glyph, GlyphId, Point, PxScale, ScaleFont};
-use image::{GrayImage, Luma, Pixel, Rgba, RgbaImage};
+use image::{GrayImage, Luma, Rgba, RgbaImage};
+
+use crate::{Align, MissingGlyphPolicy};
 
 #[derive(Clone, Debug)]
 pub struct GlyphData {
@@ -8,20 +10,73 @@ pub struct GlyphData {
     pub height: u32,
 }
 
+/// 一次渲染里所有字形共用的状态。彩色字形（`emoji`）要按字体在字体集里的序号用 swash
+/// 重新打开字体，swash 缩放用的缓存也留给下一个字形
+pub struct DrawContext {
+    #[cfg(feature = "emoji")]
+    face_index: u32,
+    #[cfg(feature = "emoji")]
+    scale_context: swash::scale::ScaleContext,
+}
+
+impl DrawContext {
+    /// `face_index` 是字体在字体集里的序号，普通字体文件是 0
+    #[cfg_attr(not(feature = "emoji"), allow(unused_variables))]
+    pub fn new(face_index: u32) -> Self {
+        DrawContext {
+            #[cfg(feature = "emoji")]
+            face_index,
+            #[cfg(feature = "emoji")]
+            scale_context: swash::scale::ScaleContext::new(),
+        }
+    }
+}
+
+/// 排版时用到的设置
+#[derive(Clone, Copy, Debug)]
+pub struct TextOptions {
+    /// 制表符是几个空格宽
+    pub tab_width: usize,
+    pub missing_glyph: MissingGlyphPolicy,
+    /// 字之间额外的距离，像素
+    pub letter_spacing: f32,
+    /// 多行时每一行在最宽的一行里怎么对齐
+    pub align: Align,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        TextOptions {
+            tab_width: 4,
+            missing_glyph: MissingGlyphPolicy::Keep,
+            letter_spacing: 0.0,
+            align: Align::Left,
+        }
+    }
+}
+
 //把文本转换为字体，方便画图
-pub fn text_to_glyphs(text: &str, font: &FontVec, scale: PxScale) -> GlyphData {
+pub fn text_to_glyphs(
+    text: &str,
+    font: &FontVec,
+    scale: PxScale,
+    options: TextOptions,
+) -> GlyphData {
     let scaled_font = font.as_scaled(scale);
 
     let mut glyphs: Vec<Glyph> = vec![];
-    layout_paragraph(scaled_font, point(0.0, 0.0), text, &mut glyphs);
-
-    let glyphs_height = scaled_font.height().ceil() as u32;
-    let glyphs_width = {
-        let min_x = glyphs.first().unwrap().position.x;
-        let last_glyph = glyphs.last().unwrap();
-        let max_x = last_glyph.position.x + scaled_font.h_advance(last_glyph.id);
-        (max_x - min_x).ceil() as u32
-    };
+    layout_paragraph(scaled_font, point(0.0, 0.0), text, options, &mut glyphs);
+
+    // 多行时每多一行往下移一个行距，和 `layout_paragraph` 一样
+    let line_count = text.matches('\n').count() + 1;
+    let v_advance = scaled_font.height() + scaled_font.line_gap();
+    let glyphs_height = (scaled_font.height() + (line_count - 1) as f32 * v_advance).ceil() as u32;
+    // 字形都被跳过时宽度是 0。宽度从原点算起，行首的制表符也占位置，字形不会画到右边外面
+    let glyphs_width = glyphs
+        .iter()
+        .map(|glyph| glyph.position.x + scaled_font.h_advance(glyph.id))
+        .fold(0.0, f32::max)
+        .ceil() as u32;
 
     GlyphData {
         glyphs,
@@ -30,108 +85,1252 @@ pub fn text_to_glyphs(text: &str, font: &FontVec, scale: PxScale) -> GlyphData {
     }
 }
 
+/// 彩色字形（emoji）：从 CBDT/sbix/COLR 表中渲染，返回以字形基线为原点的像素
+#[cfg(feature = "emoji")]
+fn for_each_color_glyph_pixel(
+    context: &mut DrawContext,
+    font: &FontVec,
+    glyph: &Glyph,
+    mut f: impl FnMut(f32, f32, [u8; 4]),
+) -> bool {
+    use swash::scale::{image::Content, Render, Source, StrikeWith};
+
+    let Some(font_ref) = swash::FontRef::from_index(font.as_slice(), context.face_index as usize)
+    else {
+        return false;
+    };
+    let Some(units_per_em) = font.units_per_em() else {
+        return false;
+    };
+    let ppem = font.as_scaled(glyph.scale).scale_factor().vertical * units_per_em;
+
+    let mut scaler = context
+        .scale_context
+        .builder(font_ref)
+        .size(ppem)
+        .hint(false)
+        .build();
+    let image = match Render::new(&[
+        Source::ColorOutline(0),
+        Source::ColorBitmap(StrikeWith::BestFit),
+    ])
+    .render(&mut scaler, glyph.id.0)
+    {
+        Some(image) if image.content == Content::Color => image,
+        _ => return false,
+    };
+
+    let placement = image.placement;
+    let left = glyph.position.x + placement.left as f32;
+    let top = glyph.position.y - placement.top as f32;
+    for (i, px) in image.data.chunks_exact(4).enumerate() {
+        if px[3] == 0 {
+            continue;
+        }
+        let x = (i as u32 % placement.width) as f32;
+        let y = (i as u32 / placement.width) as f32;
+        f(left + x, top + y, [px[0], px[1], px[2], px[3]]);
+    }
+
+    true
+}
+
+/// 不用先画再旋转、可以直接按字形画的方向
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Orientation {
+    Horizontal,
+    /// 逆时针 90 度，画布的 x 对应字形的 y，画布的 y 对应 `width - x`
+    Counterclockwise,
+    /// 顺时针 90 度，画布的 x 对应 `height - y`，画布的 y 对应字形的 x
+    Clockwise,
+}
+
+impl Orientation {
+    /// 0 度和 ±90 度，其它角度要先画出整个词再旋转
+    fn from_angle(angle: f32) -> Option<Self> {
+        if angle == 0.0 {
+            Some(Orientation::Horizontal)
+        } else if angle == 90.0 {
+            Some(Orientation::Counterclockwise)
+        } else if angle == -90.0 {
+            Some(Orientation::Clockwise)
+        } else {
+            None
+        }
+    }
+}
+
+/// 把字形内的局部坐标转换为画布坐标，`size` 是没旋转时整个词的宽高
+#[cfg(feature = "emoji")]
+fn to_canvas(
+    local_x: f32,
+    local_y: f32,
+    point: Point,
+    size: (u32, u32),
+    orientation: Orientation,
+) -> (i64, i64) {
+    let (x, y) = match orientation {
+        Orientation::Horizontal => (point.x + local_x, point.y + local_y),
+        Orientation::Counterclockwise => (local_y + point.x, size.0 as f32 + point.y - local_x),
+        Orientation::Clockwise => (size.1 as f32 + point.x - local_y, point.y + local_x),
+    };
+
+    (x.floor() as i64, y.floor() as i64)
+}
+
+/// 把 `point` 的小数部分挪到字形的位置上，让 ab_glyph 按实际的浮点位置光栅化，
+/// 返回移动后的字形和取整后的位置
+fn subpixel_glyph(mut glyph: Glyph, point: Point, orientation: Orientation) -> (Glyph, Point) {
+    match orientation {
+        Orientation::Horizontal => {
+            let origin = ab_glyph::point(point.x.floor(), point.y.floor());
+            glyph.position.x += point.x - origin.x;
+            glyph.position.y += point.y - origin.y;
+            (glyph, origin)
+        }
+        Orientation::Counterclockwise => {
+            let origin = ab_glyph::point(point.x.floor(), point.y.ceil());
+            glyph.position.x += origin.y - point.y;
+            glyph.position.y += point.x - origin.x;
+            (glyph, origin)
+        }
+        Orientation::Clockwise => {
+            let origin = ab_glyph::point(point.x.ceil(), point.y.floor());
+            glyph.position.x += point.y - origin.y;
+            glyph.position.y += origin.x - point.x;
+            (glyph, origin)
+        }
+    }
+}
+
+/// 字形光栅里的 (x, y) 在画布上的位置，`point` 已经取整，词在画布左边或上边时是负的
+fn to_canvas_pixel(
+    x: u32,
+    y: u32,
+    glyph_min: Point,
+    point: Point,
+    size: (u32, u32),
+    orientation: Orientation,
+) -> (i64, i64) {
+    let (x, y) = (x as i64, y as i64);
+    let (min_x, min_y) = (glyph_min.x as i64, glyph_min.y as i64);
+    let (point_x, point_y) = (point.x as i64, point.y as i64);
+    match orientation {
+        Orientation::Horizontal => (point_x + min_x + x, point_y + min_y + y),
+        Orientation::Counterclockwise => (y + point_x + min_y, size.0 as i64 + point_y - min_x - x),
+        Orientation::Clockwise => (size.1 as i64 + point_x - min_y - y - 1, point_y + min_x + x),
+    }
+}
+
+/// 把字形覆盖到的像素设成 `pixel`。覆盖率不到 `threshold` 的边缘像素不画
+#[allow(clippy::too_many_arguments)]
+#[cfg_attr(not(feature = "emoji"), allow(unused_variables))]
 pub fn draw_glyphs_to_gray_buffer(
     buffer: &mut GrayImage,
+    context: &mut DrawContext,
     glyph_data: GlyphData,
     font: &FontVec,
     point: Point,
-    rotate: bool,
+    angle: f32,
+    pixel: Luma<u8>,
+    threshold: f32,
 ) {
-    let width = glyph_data.width;
+    let Some(orientation) = Orientation::from_angle(angle) else {
+        for_each_rotated_pixel(&glyph_data, font, point, angle, |x, y, v| {
+            if v < threshold {
+                return;
+            }
+            if let Some(px) = buffer.get_pixel_mut_checked(x, y) {
+                *px = pixel
+            }
+        });
+        return;
+    };
+
+    let size = (glyph_data.width, glyph_data.height);
     for glyph in glyph_data.glyphs {
+        #[cfg(feature = "emoji")]
+        {
+            let drawn = for_each_color_glyph_pixel(context, font, &glyph, |x, y, _| {
+                let (final_x, final_y) = to_canvas(x, y, point, size, orientation);
+                if final_x >= 0 && final_y >= 0 {
+                    if let Some(px) = buffer.get_pixel_mut_checked(final_x as u32, final_y as u32) {
+                        *px = pixel;
+                    }
+                }
+            });
+            if drawn {
+                continue;
+            }
+        }
+
+        let (glyph, point) = subpixel_glyph(glyph, point, orientation);
         if let Some(outlined) = font.outline_glyph(glyph) {
             let bounds = outlined.px_bounds();
 
             outlined.draw(|x, y, v| {
-                if v < 0.05 {
+                if v < threshold {
+                    return;
+                }
+
+                let (final_x, final_y) =
+                    to_canvas_pixel(x, y, bounds.min, point, size, orientation);
+                if final_x < 0 || final_y < 0 {
                     return;
                 }
+                if let Some(px) = buffer.get_pixel_mut_checked(final_x as u32, final_y as u32) {
+                    *px = pixel
+                }
+            })
+        }
+    }
+}
 
-                let (final_x, final_y) = if rotate {
-                    // (
-                    //     y + point.x as u32 + bounds.min.y as u32,
-                    //     width + point.y as u32 - bounds.min.x as u32 - x,
-                    // )
-                    (
-                        y + point.x as u32 + bounds.min.y as u32,
-                        width + point.y as u32 - bounds.min.x as u32 - x,
-                    )
-                } else {
-                    (
-                        point.x as u32 + bounds.min.x as u32 + x,
-                        point.y as u32 + bounds.min.y as u32 + y,
-                    )
-                };
-                let px = buffer.get_pixel_mut(final_x, final_y);
-                *px = Luma([1])
+/// 把字形的覆盖率 (0 - 255) 画到灰度图上，用来生成阴影
+fn draw_glyph_coverage_to_gray_buffer(
+    buffer: &mut GrayImage,
+    glyph_data: &GlyphData,
+    font: &FontVec,
+    point: Point,
+    orientation: Orientation,
+) {
+    let size = (glyph_data.width, glyph_data.height);
+    for glyph in glyph_data.glyphs.iter().cloned() {
+        let (glyph, point) = subpixel_glyph(glyph, point, orientation);
+        if let Some(outlined) = font.outline_glyph(glyph) {
+            let bounds = outlined.px_bounds();
+
+            outlined.draw(|x, y, v| {
+                let (final_x, final_y) =
+                    to_canvas_pixel(x, y, bounds.min, point, size, orientation);
+                if final_x < 0 || final_y < 0 {
+                    return;
+                }
+                if let Some(px) = buffer.get_pixel_mut_checked(final_x as u32, final_y as u32) {
+                    px.0[0] = px.0[0].max((v.min(1.0) * 255.0) as u8);
+                }
             })
         }
     }
 }
 
+/// 宽 `width` 高 `height` 的矩形逆时针旋转 `angle` 度后的外接矩形
+pub fn rotated_dimensions(width: u32, height: u32, angle: f32) -> (u32, u32) {
+    if angle == 0.0 {
+        return (width, height);
+    }
+    if angle == 90.0 || angle == -90.0 {
+        return (height, width);
+    }
+
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let (sin, cos) = (sin.abs(), cos.abs());
+    let (width, height) = (width as f32, height as f32);
+    // 减掉一点误差，免得 180 度这种角度多出一个像素
+    (
+        (width * cos + height * sin - 1e-3).ceil() as u32,
+        (width * sin + height * cos - 1e-3).ceil() as u32,
+    )
+}
+
+/// 把没旋转的覆盖率图绕中心逆时针旋转 `angle` 度，四周留 `padding`
+fn rotate_coverage(flat: &GrayImage, angle: f32, padding: u32) -> GrayImage {
+    let (width, height) = rotated_dimensions(flat.width(), flat.height(), angle);
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let center = point(
+        width as f32 / 2.0 + padding as f32,
+        height as f32 / 2.0 + padding as f32,
+    );
+    let flat_center = point(flat.width() as f32 / 2.0, flat.height() as f32 / 2.0);
+    let sample = |x: i64, y: i64| {
+        if x < 0 || y < 0 || x >= flat.width() as i64 || y >= flat.height() as i64 {
+            0.0
+        } else {
+            flat.get_pixel(x as u32, y as u32).0[0] as f32
+        }
+    };
+
+    GrayImage::from_fn(width + padding * 2, height + padding * 2, |x, y| {
+        // 反向旋转回没旋转时的坐标
+        let dx = x as f32 + 0.5 - center.x;
+        let dy = y as f32 + 0.5 - center.y;
+        let local_x = dx * cos - dy * sin + flat_center.x - 0.5;
+        let local_y = dx * sin + dy * cos + flat_center.y - 0.5;
+
+        let (x0, y0) = (local_x.floor(), local_y.floor());
+        let (fx, fy) = (local_x - x0, local_y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let v = sample(x0, y0) * (1.0 - fx) * (1.0 - fy)
+            + sample(x0 + 1, y0) * fx * (1.0 - fy)
+            + sample(x0, y0 + 1) * (1.0 - fx) * fy
+            + sample(x0 + 1, y0 + 1) * fx * fy;
+        Luma([v.round() as u8])
+    })
+}
+
+/// 任意角度的词先画出整个词旋转后的覆盖率，再从 `point`（外接矩形的左上角）贴到画布上
+fn for_each_rotated_pixel(
+    glyph_data: &GlyphData,
+    font: &FontVec,
+    point: Point,
+    angle: f32,
+    mut f: impl FnMut(u32, u32, f32),
+) {
+    let coverage = glyph_coverage(glyph_data, font, angle, 0);
+    for (x, y, px) in coverage.enumerate_pixels() {
+        if px.0[0] == 0 {
+            continue;
+        }
+
+        let (final_x, final_y) = (
+            point.x.floor() as i64 + x as i64,
+            point.y.floor() as i64 + y as i64,
+        );
+        if final_x < 0 || final_y < 0 {
+            continue;
+        }
+        f(final_x as u32, final_y as u32, px.0[0] as f32 / 255.0);
+    }
+}
+
+/// 把整个词的覆盖率画到一张四周留了 `padding` 的灰度图上，词逆时针旋转 `angle` 度
+fn glyph_coverage(glyph_data: &GlyphData, font: &FontVec, angle: f32, padding: u32) -> GrayImage {
+    let Some(orientation) = Orientation::from_angle(angle) else {
+        return rotate_coverage(&glyph_coverage(glyph_data, font, 0.0, 0), angle, padding);
+    };
+
+    let (width, height) = rotated_dimensions(glyph_data.width, glyph_data.height, angle);
+
+    let mut coverage = GrayImage::new(width + padding * 2, height + padding * 2);
+    draw_glyph_coverage_to_gray_buffer(
+        &mut coverage,
+        glyph_data,
+        font,
+        ab_glyph::point(padding as f32, padding as f32),
+        orientation,
+    );
+
+    coverage
+}
+
+/// 只画字形的轮廓（空心字），`stroke_width` 是轮廓向内的宽度
+pub fn draw_outline_to_rgba_buffer(
+    buffer: &mut RgbaImage,
+    glyph_data: &GlyphData,
+    font: &FontVec,
+    point: Point,
+    angle: f32,
+    stroke_width: f32,
+    pixel: Rgba<u8>,
+) {
+    let coverage = glyph_coverage(glyph_data, font, angle, 1);
+    let radius = stroke_width.round().max(1.0) as i64;
+    let is_outside = |x: i64, y: i64| {
+        x < 0
+            || y < 0
+            || coverage
+                .get_pixel_checked(x as u32, y as u32)
+                .is_none_or(|px| px.0[0] < 128)
+    };
+
+    for (x, y, px) in coverage.enumerate_pixels() {
+        if px.0[0] == 0 {
+            continue;
+        }
+
+        // 离字形外部不超过 radius 的像素才属于轮廓
+        let (x, y) = (x as i64, y as i64);
+        let on_edge = (-radius..=radius).any(|dy| {
+            (-radius..=radius)
+                .any(|dx| dx * dx + dy * dy <= radius * radius && is_outside(x + dx, y + dy))
+        });
+        if !on_edge {
+            continue;
+        }
+
+        let (final_x, final_y) = (
+            point.x.floor() as i64 + x - 1,
+            point.y.floor() as i64 + y - 1,
+        );
+        if final_x < 0 || final_y < 0 {
+            continue;
+        }
+
+        if let Some(dst) = buffer.get_pixel_mut_checked(final_x as u32, final_y as u32) {
+            blend(dst, pixel, px.0[0] as f32 / 255.0);
+        }
+    }
+}
+
+/// 在 `point + offset` 处画出字形的阴影，`blur` 是高斯模糊的 sigma
+#[allow(clippy::too_many_arguments)]
+pub fn draw_shadow_to_rgba_buffer(
+    buffer: &mut RgbaImage,
+    glyph_data: &GlyphData,
+    font: &FontVec,
+    point: Point,
+    angle: f32,
+    offset: Point,
+    blur: f32,
+    color: Rgba<u8>,
+) {
+    for_each_shadow_pixel(glyph_data, font, point, angle, offset, blur, |dst, v| {
+        if let Some(dst) = buffer.get_pixel_mut_checked(dst.0, dst.1) {
+            blend(dst, color, v);
+        }
+    });
+}
+
+/// 把模糊过的阴影盖到的像素设成 `pixel`，覆盖率低于 `threshold` 的不算
+#[allow(clippy::too_many_arguments)]
+pub fn draw_shadow_to_gray_buffer(
+    buffer: &mut GrayImage,
+    glyph_data: &GlyphData,
+    font: &FontVec,
+    point: Point,
+    angle: f32,
+    offset: Point,
+    blur: f32,
+    pixel: Luma<u8>,
+    threshold: f32,
+) {
+    for_each_shadow_pixel(glyph_data, font, point, angle, offset, blur, |dst, v| {
+        if v < threshold {
+            return;
+        }
+        if let Some(dst) = buffer.get_pixel_mut_checked(dst.0, dst.1) {
+            *dst = pixel;
+        }
+    });
+}
+
+/// 阴影在画布上的每个像素和它的覆盖率，模糊的部分也算
+fn for_each_shadow_pixel(
+    glyph_data: &GlyphData,
+    font: &FontVec,
+    point: Point,
+    angle: f32,
+    offset: Point,
+    blur: f32,
+    mut f: impl FnMut((u32, u32), f32),
+) {
+    let padding = (blur * 3.0).ceil() as u32;
+    let mut coverage = glyph_coverage(glyph_data, font, angle, padding);
+    if blur > 0.0 {
+        coverage = image::imageops::blur(&coverage, blur);
+    }
+
+    let left = point.x + offset.x - padding as f32;
+    let top = point.y + offset.y - padding as f32;
+    for (x, y, px) in coverage.enumerate_pixels() {
+        if px.0[0] == 0 {
+            continue;
+        }
+
+        let (final_x, final_y) = (left + x as f32, top + y as f32);
+        if final_x < 0.0 || final_y < 0.0 {
+            continue;
+        }
+
+        f((final_x as u32, final_y as u32), px.0[0] as f32 / 255.0);
+    }
+}
+
+#[cfg_attr(not(feature = "emoji"), allow(unused_variables))]
 pub fn draw_glyphs_to_rgba_buffer(
     buffer: &mut RgbaImage,
-    glyph_data: GlyphData,
+    context: &mut DrawContext,
+    glyph_data: &GlyphData,
     font: &FontVec,
     point: Point,
-    rotate: bool,
+    angle: f32,
     pixel: Rgba<u8>,
 ) {
-    let width = glyph_data.width;
-    for glyph in glyph_data.glyphs {
+    let Some(orientation) = Orientation::from_angle(angle) else {
+        for_each_rotated_pixel(glyph_data, font, point, angle, |x, y, v| {
+            if let Some(px) = buffer.get_pixel_mut_checked(x, y) {
+                blend(px, pixel, v);
+            }
+        });
+        return;
+    };
+
+    let size = (glyph_data.width, glyph_data.height);
+    for glyph in glyph_data.glyphs.iter().cloned() {
+        #[cfg(feature = "emoji")]
+        {
+            let drawn = for_each_color_glyph_pixel(context, font, &glyph, |x, y, color| {
+                let (final_x, final_y) = to_canvas(x, y, point, size, orientation);
+                if final_x < 0 || final_y < 0 {
+                    return;
+                }
+                if let Some(px) = buffer.get_pixel_mut_checked(final_x as u32, final_y as u32) {
+                    blend(px, Rgba(color), 1.0);
+                }
+            });
+            if drawn {
+                continue;
+            }
+        }
+
+        let (glyph, point) = subpixel_glyph(glyph, point, orientation);
         if let Some(outlined) = font.outline_glyph(glyph) {
             let bounds = outlined.px_bounds();
 
             outlined.draw(|x, y, v| {
-                let (final_x, final_y) = if rotate {
-                    (
-                        y + point.x as u32 + bounds.min.y as u32,
-                        width + point.y as u32 - bounds.min.x as u32 - x,
-                    )
-                } else {
-                    (
-                        point.x as u32 + bounds.min.x as u32 + x,
-                        point.y as u32 + bounds.min.y as u32 + y,
-                    )
-                };
-                if let Some(px) = buffer.get_pixel_mut_checked(final_x, final_y) {
-                    px.apply2(&pixel, |old, new| {
-                        ((v * new as f32) + (1.0 - v) * old as f32) as u8
-                    });
-                    if px != &Rgba::from([0; 4]) {
-                        px.0[3] = 0xFF;
-                    }
+                let (final_x, final_y) =
+                    to_canvas_pixel(x, y, bounds.min, point, size, orientation);
+                if final_x < 0 || final_y < 0 {
+                    return;
+                }
+                if let Some(px) = buffer.get_pixel_mut_checked(final_x as u32, final_y as u32) {
+                    blend(px, pixel, v);
                 }
             })
         }
     }
 }
 
-pub fn layout_paragraph<F, SF>(font: SF, position: Point, text: &str, target: &mut Vec<Glyph>)
-where
+/// 沿路径排列的字形，绕 `origin`（基线起点）旋转 `angle` 弧度
+#[derive(Clone, Debug)]
+pub struct PathGlyph {
+    pub glyph: Glyph,
+    pub origin: Point,
+    pub angle: f32,
+}
+
+/// 先按直线排版，再把每个字形的中点放到折线 `path` 上，顺着切线方向旋转。
+/// 超出路径的字形沿着最后一段继续往前排
+pub fn text_to_path_glyphs(
+    text: &str,
+    font: &FontVec,
+    scale: PxScale,
+    path: &[Point],
+    options: TextOptions,
+) -> Vec<PathGlyph> {
+    let scaled_font = font.as_scaled(scale);
+
+    let mut glyphs: Vec<Glyph> = vec![];
+    layout_paragraph(scaled_font, point(0.0, 0.0), text, options, &mut glyphs);
+
+    glyphs
+        .into_iter()
+        .map(|mut glyph| {
+            let half_advance = scaled_font.h_advance(glyph.id) / 2.0;
+            let (center, tangent) = point_along_path(path, glyph.position.x + half_advance);
+
+            glyph.position = point(0.0, 0.0);
+            PathGlyph {
+                glyph,
+                origin: center - scale_point(tangent, half_advance),
+                angle: tangent.y.atan2(tangent.x),
+            }
+        })
+        .collect()
+}
+
+/// 折线上距离起点 `distance` 的点，以及那里的单位切线
+fn point_along_path(path: &[Point], distance: f32) -> (Point, Point) {
+    let start = path.first().copied().unwrap_or(point(0.0, 0.0));
+    let mut travelled = 0.0;
+    let mut last = (start, point(1.0, 0.0));
+
+    for segment in path.windows(2) {
+        let delta = segment[1] - segment[0];
+        let length = (delta.x * delta.x + delta.y * delta.y).sqrt();
+        if length <= f32::EPSILON {
+            continue;
+        }
+
+        let tangent = scale_point(delta, 1.0 / length);
+        if travelled + length >= distance {
+            return (
+                segment[0] + scale_point(tangent, distance - travelled),
+                tangent,
+            );
+        }
+        travelled += length;
+        last = (segment[1], tangent);
+    }
+
+    let (end, tangent) = last;
+    (end + scale_point(tangent, distance - travelled), tangent)
+}
+
+fn scale_point(p: Point, factor: f32) -> Point {
+    point(p.x * factor, p.y * factor)
+}
+
+/// 对旋转后字形覆盖到的每个画布像素调用 `f(x, y, coverage)`，用双线性插值从未旋转的覆盖率里反向采样
+pub fn for_each_path_glyph_pixel(
+    path_glyphs: &[PathGlyph],
+    font: &FontVec,
+    mut f: impl FnMut(i64, i64, f32),
+) {
+    for path_glyph in path_glyphs {
+        let Some(outlined) = font.outline_glyph(path_glyph.glyph.clone()) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        let (width, height) = (bounds.width() as usize, bounds.height() as usize);
+        let mut coverage = vec![0.0; width * height];
+        outlined.draw(|x, y, v| coverage[y as usize * width + x as usize] = v);
+        let sample = |x: i64, y: i64| {
+            if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+                0.0
+            } else {
+                coverage[y as usize * width + x as usize]
+            }
+        };
+
+        let (sin, cos) = path_glyph.angle.sin_cos();
+        let rotate = |p: Point| point(p.x * cos - p.y * sin, p.x * sin + p.y * cos);
+        let corners = [
+            rotate(bounds.min),
+            rotate(point(bounds.max.x, bounds.min.y)),
+            rotate(bounds.max),
+            rotate(point(bounds.min.x, bounds.max.y)),
+        ];
+        let min_x = corners.iter().map(|c| c.x).fold(f32::MAX, f32::min) + path_glyph.origin.x;
+        let min_y = corners.iter().map(|c| c.y).fold(f32::MAX, f32::min) + path_glyph.origin.y;
+        let max_x = corners.iter().map(|c| c.x).fold(f32::MIN, f32::max) + path_glyph.origin.x;
+        let max_y = corners.iter().map(|c| c.y).fold(f32::MIN, f32::max) + path_glyph.origin.y;
+
+        for y in min_y.floor() as i64..=max_y.ceil() as i64 {
+            for x in min_x.floor() as i64..=max_x.ceil() as i64 {
+                // 反向旋转回字形的局部坐标
+                let dx = x as f32 + 0.5 - path_glyph.origin.x;
+                let dy = y as f32 + 0.5 - path_glyph.origin.y;
+                let local_x = dx * cos + dy * sin - bounds.min.x - 0.5;
+                let local_y = -dx * sin + dy * cos - bounds.min.y - 0.5;
+
+                let (x0, y0) = (local_x.floor(), local_y.floor());
+                let (fx, fy) = (local_x - x0, local_y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let v = sample(x0, y0) * (1.0 - fx) * (1.0 - fy)
+                    + sample(x0 + 1, y0) * fx * (1.0 - fy)
+                    + sample(x0, y0 + 1) * (1.0 - fx) * fy
+                    + sample(x0 + 1, y0 + 1) * fx * fy;
+                if v > 0.0 {
+                    f(x, y, v);
+                }
+            }
+        }
+    }
+}
+
+/// 按 source-over 把 `color` 叠加到 `dst` 上，实际的不透明度是 `coverage` 乘以颜色的 alpha。
+/// 背景透明时，字的边缘也是半透明的
+pub fn blend(dst: &mut Rgba<u8>, color: Rgba<u8>, coverage: f32) {
+    let src_alpha = coverage.clamp(0.0, 1.0) * color.0[3] as f32 / 255.0;
+    if src_alpha <= 0.0 {
+        return;
+    }
+
+    let dst_alpha = dst.0[3] as f32 / 255.0;
+    let out_alpha = src_alpha + dst_alpha * (1.0 - src_alpha);
+    for i in 0..3 {
+        let channel = (color.0[i] as f32 * src_alpha
+            + dst.0[i] as f32 * dst_alpha * (1.0 - src_alpha))
+            / out_alpha;
+        dst.0[i] = channel.round() as u8;
+    }
+    dst.0[3] = (out_alpha * 255.0).round() as u8;
+}
+
+/// 制表符把光标往前移 `tab_width` 个空格的宽度，字体里没有的字按 `missing_glyph` 处理
+pub fn layout_paragraph<F, SF>(
+    font: SF,
+    position: Point,
+    text: &str,
+    options: TextOptions,
+    target: &mut Vec<Glyph>,
+) where
     F: Font,
     SF: ScaleFont<F>,
 {
     let v_advance = font.height() + font.line_gap();
     let mut caret = position + point(0.0, font.ascent());
     let mut last_glyph: Option<GlyphId> = None;
+    // 每一行第一个字形的下标和行宽，排完以后再对齐
+    let first_glyph = target.len();
+    let mut lines = vec![];
+    let mut line_start = first_glyph;
     for c in text.chars() {
         if c.is_control() {
             if c == '\n' {
                 //进行换行
+                lines.push((line_start, caret.x - position.x));
+                line_start = target.len();
                 caret = point(position.x, caret.y + v_advance);
+            } else if c == '\t' {
+                caret.x += font.h_advance(font.glyph_id(' ')) * options.tab_width as f32;
+                last_glyph = None;
             }
             continue;
         }
 
+        // 字形 0 是 .notdef，一般画成方框
+        let c = match options.missing_glyph {
+            _ if font.glyph_id(c).0 != 0 => c,
+            MissingGlyphPolicy::Keep => c,
+            MissingGlyphPolicy::Skip => continue,
+            MissingGlyphPolicy::Replace(replacement) => replacement,
+        };
+
         let mut glyph = font.scaled_glyph(c);
         if let Some(previous) = last_glyph.take() {
             caret.x += font.kern(previous, glyph.id);
         }
         glyph.position = caret;
         last_glyph = Some(glyph.id);
-        caret.x += font.h_advance(glyph.id);
+        if options.letter_spacing == 0.0 {
+            caret.x += font.h_advance(glyph.id);
+        } else {
+            // 字距是负的时候，最多收到下一个字贴着这个字的右边
+            let ink_right = font
+                .font()
+                .outline(glyph.id)
+                .map_or(0.0, |outline| outline.bounds.max.x * font.h_scale_factor());
+            caret.x += (font.h_advance(glyph.id) + options.letter_spacing).max(ink_right);
+        }
 
         target.push(glyph);
     }
+    lines.push((line_start, caret.x - position.x));
+
+    let factor = match options.align {
+        Align::Left => return,
+        Align::Center => 0.5,
+        Align::Right => 1.0,
+    };
+    let widest = lines.iter().map(|(_, width)| *width).fold(0.0, f32::max);
+    for (i, (start, width)) in lines.iter().enumerate() {
+        let end = lines.get(i + 1).map_or(target.len(), |(next, _)| *next);
+        let shift = (widest - width) * factor;
+        for glyph in &mut target[*start..end] {
+            glyph.position.x += shift;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+    use image::{GrayImage, Luma, Rgba, RgbaImage};
+
+    use super::{
+        draw_glyphs_to_gray_buffer, draw_glyphs_to_rgba_buffer, draw_outline_to_rgba_buffer,
+        draw_shadow_to_rgba_buffer, rotated_dimensions, text_to_glyphs, DrawContext, TextOptions,
+    };
+    use crate::{Align, MissingGlyphPolicy};
+
+    fn test_font() -> FontVec {
+        FontVec::try_from_vec(include_bytes!("../fonts/Dengb.ttf").to_vec()).unwrap()
+    }
+
+    #[test]
+    fn tab_width() {
+        let font = test_font();
+        let scale = PxScale::from(20.0);
+        let space = font.as_scaled(scale).h_advance(font.glyph_id(' '));
+
+        let options = |tab_width| TextOptions {
+            tab_width,
+            ..Default::default()
+        };
+
+        let no_tab = text_to_glyphs("a\tb", &font, scale, options(0));
+        let tab = text_to_glyphs("a\tb", &font, scale, options(4));
+
+        assert_eq!(tab.glyphs.len(), 2);
+        let gap = tab.glyphs[1].position.x - no_tab.glyphs[1].position.x;
+        assert!((gap - space * 4.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn leading_tab() {
+        let font = test_font();
+        let scale = PxScale::from(20.0);
+        let space = font.as_scaled(scale).h_advance(font.glyph_id(' '));
+        let glyphs = text_to_glyphs("\tb", &font, scale, TextOptions::default());
+        let b = text_to_glyphs("b", &font, scale, TextOptions::default());
+        assert!(glyphs.width as f32 >= b.width as f32 + space * 4.0 - 1.0);
+
+        // 行首的制表符算在宽度里，字画在占的位置里面
+        for angle in [0.0, 90.0, -90.0] {
+            let mut image = RgbaImage::new(120, 120);
+            draw_glyphs_to_rgba_buffer(
+                &mut image,
+                &mut DrawContext::new(0),
+                &glyphs,
+                &font,
+                point(10.0, 10.0),
+                angle,
+                Rgba([255, 255, 255, 255]),
+            );
+            let (width, height) = rotated_dimensions(glyphs.width, glyphs.height, angle);
+            assert!(image.pixels().any(|px| px.0[3] != 0));
+            for (x, y, px) in image.enumerate_pixels() {
+                if px.0[3] != 0 {
+                    assert!((10..10 + width).contains(&x), "{angle} {x} {y}");
+                    assert!((10..10 + height).contains(&y), "{angle} {x} {y}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn subpixel_position() {
+        let font = test_font();
+        let options = TextOptions::default();
+        let glyphs = text_to_glyphs("news", &font, PxScale::from(8.0), options);
+
+        // 覆盖率的重心，小数部分的位置不再被截掉，重心跟着移动
+        let centroid = |x: f32, angle: f32| {
+            let mut image = RgbaImage::new(40, 40);
+            draw_glyphs_to_rgba_buffer(
+                &mut image,
+                &mut DrawContext::new(0),
+                &glyphs,
+                &font,
+                point(x, 10.0),
+                angle,
+                Rgba([255, 255, 255, 255]),
+            );
+            let (sum, total) =
+                image
+                    .enumerate_pixels()
+                    .fold((0.0, 0.0), |(sum, total), (x, _, px)| {
+                        let alpha = px.0[3] as f32;
+                        (sum + x as f32 * alpha, total + alpha)
+                    });
+            sum / total
+        };
+
+        for angle in [0.0, 90.0, -90.0] {
+            let whole = centroid(10.0, angle);
+            for offset in [0.25, 0.5, 0.75] {
+                let shift = centroid(10.0 + offset, angle) - whole;
+                assert!((shift - offset).abs() < 0.1, "{angle} {offset} {shift}");
+            }
+        }
+    }
+
+    #[test]
+    fn subpixel_coverage() {
+        // 8 像素的字和放大 4 倍画再缩回来的结果比，保留小数部分的位置时覆盖率更接近
+        let font = test_font();
+        let coverage = |size: f32, x: f32, y: f32, factor: u32| {
+            let glyphs = text_to_glyphs("news", &font, PxScale::from(size), TextOptions::default());
+            let mut image = RgbaImage::new(40 * factor, 30 * factor);
+            draw_glyphs_to_rgba_buffer(
+                &mut image,
+                &mut DrawContext::new(0),
+                &glyphs,
+                &font,
+                point(x, y),
+                0.0,
+                Rgba([255, 255, 255, 255]),
+            );
+            let mut coverage = vec![0.0; 40 * 30];
+            for (x, y, px) in image.enumerate_pixels() {
+                let i = (y / factor * 40 + x / factor) as usize;
+                coverage[i] += px.0[3] as f32 / (factor * factor) as f32;
+            }
+            coverage
+        };
+        let error = |a: &[f32], b: &[f32]| a.iter().zip(b).map(|(a, b)| (a - b).abs()).sum::<f32>();
+
+        for (x, y) in [(10.25, 10.0), (10.5, 10.0), (10.75, 10.5), (10.5, 10.5)] {
+            let reference = coverage(32.0, x * 4.0, y * 4.0, 4);
+            let subpixel = error(&coverage(8.0, x, y, 1), &reference);
+            // 改之前位置的小数部分被截掉，和画在取整的位置上一样
+            let truncated = error(&coverage(8.0, x.floor(), y.floor(), 1), &reference);
+            assert!(subpixel * 4.0 < truncated, "{x} {y} {subpixel} {truncated}");
+        }
+    }
+
+    #[test]
+    fn clockwise_rotation() {
+        let font = test_font();
+        let options = TextOptions::default();
+        let glyphs = text_to_glyphs("news", &font, PxScale::from(20.0), options);
+        assert_eq!(
+            rotated_dimensions(glyphs.width, glyphs.height, -90.0),
+            (glyphs.height, glyphs.width)
+        );
+
+        let draw = |angle: f32| {
+            let mut image = GrayImage::new(60, 80);
+            draw_glyphs_to_gray_buffer(
+                &mut image,
+                &mut DrawContext::new(0),
+                glyphs.clone(),
+                &font,
+                point(10.0, 10.0),
+                angle,
+                Luma([1]),
+                0.05,
+            );
+            image
+        };
+        let counterclockwise = draw(90.0);
+        let clockwise = draw(-90.0);
+
+        // 两个方向占的像素一样多，都在外接矩形里，字的顶部分别朝左和朝右
+        let ink = |image: &GrayImage| image.pixels().filter(|px| px.0[0] != 0).count();
+        assert_eq!(ink(&counterclockwise), ink(&clockwise));
+        for (x, y, px) in clockwise.enumerate_pixels() {
+            if px.0[0] != 0 {
+                assert!((10..10 + glyphs.height).contains(&x), "{x} {y}");
+                assert!((10..10 + glyphs.width).contains(&y), "{x} {y}");
+            }
+        }
+        let first_column = |image: &GrayImage| {
+            (0..image.width())
+                .find(|x| (0..image.height()).any(|y| image.get_pixel(*x, y).0[0] != 0))
+        };
+        let last_column = |image: &GrayImage| {
+            (0..image.width())
+                .rev()
+                .find(|x| (0..image.height()).any(|y| image.get_pixel(*x, y).0[0] != 0))
+        };
+        // 基线以下没有字，逆时针时空在右边，顺时针时空在左边
+        assert!(first_column(&clockwise).unwrap() > 10);
+        assert!(last_column(&counterclockwise).unwrap() < 10 + glyphs.height - 1);
+    }
+
+    #[test]
+    fn partly_off_canvas() {
+        let font = test_font();
+        let options = TextOptions::default();
+        let glyphs = text_to_glyphs("news", &font, PxScale::from(20.0), options);
+
+        let draw = |x: f32, y: f32, angle: f32| {
+            let mut image = GrayImage::new(80, 80);
+            draw_glyphs_to_gray_buffer(
+                &mut image,
+                &mut DrawContext::new(0),
+                glyphs.clone(),
+                &font,
+                point(x, y),
+                angle,
+                Luma([1]),
+                0.05,
+            );
+            image
+        };
+
+        // 词伸出画布左边和上边时，留在画布上的部分和整个画在里面时一样，不会挤到第 0 行或列
+        for angle in [0.0, 90.0, -90.0] {
+            for (x, y) in [(-6.0, -6.0), (-6.5, -6.5)] {
+                let inside = draw(x + 16.0, y + 16.0, angle);
+                let outside = draw(x, y, angle);
+                assert!(outside.pixels().any(|px| px.0[0] != 0), "{angle} {x}");
+                for (x, y, px) in outside.enumerate_pixels() {
+                    let expected = inside
+                        .get_pixel_checked(x + 16, y + 16)
+                        .map_or(0, |px| px.0[0]);
+                    assert_eq!(px.0[0], expected, "{angle} {x} {y}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn missing_glyph() {
+        let font = test_font();
+        let scale = PxScale::from(20.0);
+        // 扩展 B 区的汉字，自带的字体里没有
+        let missing = '\u{2A6D6}';
+        assert_eq!(font.glyph_id(missing).0, 0);
+
+        let glyphs = |missing_glyph| {
+            let options = TextOptions {
+                missing_glyph,
+                ..Default::default()
+            };
+            text_to_glyphs(&format!("a{missing}b"), &font, scale, options)
+        };
+
+        let kept = glyphs(MissingGlyphPolicy::Keep);
+        assert_eq!(kept.glyphs[1].id.0, 0);
+
+        let skipped = glyphs(MissingGlyphPolicy::Skip);
+        assert_eq!(skipped.glyphs.len(), 2);
+        assert!(skipped.width < kept.width);
+        assert!(skipped.glyphs.iter().all(|glyph| glyph.id.0 != 0));
+
+        let replaced = glyphs(MissingGlyphPolicy::Replace('?'));
+        assert_eq!(replaced.glyphs[1].id, font.glyph_id('?'));
+
+        let empty = text_to_glyphs(
+            &missing.to_string(),
+            &font,
+            scale,
+            TextOptions {
+                missing_glyph: MissingGlyphPolicy::Skip,
+                ..Default::default()
+            },
+        );
+        assert_eq!((empty.glyphs.len(), empty.width), (0, 0));
+    }
+
+    #[test]
+    fn letter_spacing() {
+        let font = test_font();
+        let glyphs = |letter_spacing| {
+            let options = TextOptions {
+                letter_spacing,
+                ..Default::default()
+            };
+            text_to_glyphs("word", &font, PxScale::from(20.0), options)
+        };
+
+        let normal = glyphs(0.0);
+        let spaced = glyphs(5.0);
+        // 4 个字之间有 3 个间距，每个是 5 像素
+        assert!(spaced.width.abs_diff(normal.width + 15) <= 1);
+
+        // 收得再紧，字形也不会叠在一起
+        let tight = glyphs(-10.0);
+        assert!(tight.width < normal.width);
+        for pair in tight.glyphs.windows(2) {
+            let outline = font.outline(pair[0].id).unwrap();
+            let ink_right =
+                outline.bounds.max.x * font.as_scaled(PxScale::from(20.0)).h_scale_factor();
+            assert!(pair[1].position.x - pair[0].position.x >= ink_right - 0.01);
+        }
+    }
+
+    #[test]
+    fn occupancy_threshold() {
+        let font = test_font();
+        let options = TextOptions::default();
+        let glyphs = text_to_glyphs("news", &font, PxScale::from(20.0), options);
+
+        let occupied = |threshold: f32| {
+            let mut image = GrayImage::new(80, 40);
+            draw_glyphs_to_gray_buffer(
+                &mut image,
+                &mut DrawContext::new(0),
+                glyphs.clone(),
+                &font,
+                point(10.0, 10.0),
+                0.0,
+                Luma([1]),
+                threshold,
+            );
+            image.pixels().filter(|px| px.0[0] != 0).count()
+        };
+
+        // 抗锯齿的边缘不算占位
+        assert!(occupied(0.5) < occupied(0.0));
+        assert!(occupied(0.5) > 0);
+    }
+
+    #[test]
+    fn multi_line_align() {
+        let font = test_font();
+        let scale = PxScale::from(20.0);
+        let layout = |align| {
+            let options = TextOptions {
+                align,
+                ..Default::default()
+            };
+            text_to_glyphs("ab\nabcd", &font, scale, options)
+        };
+
+        let left = layout(Align::Left);
+        let line_width = |glyphs: &[ab_glyph::Glyph]| {
+            let last = glyphs.last().unwrap();
+            last.position.x + font.as_scaled(scale).h_advance(last.id) - glyphs[0].position.x
+        };
+        let gap = line_width(&left.glyphs[2..]) - line_width(&left.glyphs[..2]);
+        assert!(gap > 0.0);
+        assert_eq!(left.glyphs[0].position.x, left.glyphs[2].position.x);
+
+        // 短的那行移动，最宽的那行不动，总宽度不变
+        for (align, shift) in [(Align::Center, gap / 2.0), (Align::Right, gap)] {
+            let aligned = layout(align);
+            assert!(
+                (aligned.glyphs[0].position.x - left.glyphs[0].position.x - shift).abs() < 0.01
+            );
+            assert_eq!(aligned.glyphs[2].position.x, left.glyphs[2].position.x);
+            assert_eq!(aligned.width, left.width);
+        }
+    }
+
+    #[test]
+    fn text_shadow() {
+        let font = test_font();
+        let options = TextOptions::default();
+        let glyphs = text_to_glyphs("news", &font, PxScale::from(20.0), options);
+
+        // 有颜色的像素的外框
+        let ink = |image: &RgbaImage| {
+            image
+                .enumerate_pixels()
+                .filter(|(_, _, px)| px.0[3] != 0)
+                .fold((u32::MAX, u32::MAX, 0, 0), |(x0, y0, x1, y1), (x, y, _)| {
+                    (x0.min(x), y0.min(y), x1.max(x), y1.max(y))
+                })
+        };
+        let shadow = |blur: f32| {
+            let mut image = RgbaImage::new(100, 60);
+            draw_shadow_to_rgba_buffer(
+                &mut image,
+                &glyphs,
+                &font,
+                point(10.0, 10.0),
+                0.0,
+                point(6.0, 4.0),
+                blur,
+                Rgba([0, 0, 0, 255]),
+            );
+            image
+        };
+        let mut fill = RgbaImage::new(100, 60);
+        draw_glyphs_to_rgba_buffer(
+            &mut fill,
+            &mut DrawContext::new(0),
+            &glyphs,
+            &font,
+            point(10.0, 10.0),
+            0.0,
+            Rgba([255, 255, 255, 255]),
+        );
+
+        let (x0, y0, x1, y1) = ink(&fill);
+        let sharp = ink(&shadow(0.0));
+        assert!(sharp.0.abs_diff(x0 + 6) <= 1 && sharp.1.abs_diff(y0 + 4) <= 1);
+        assert!(sharp.2.abs_diff(x1 + 6) <= 1 && sharp.3.abs_diff(y1 + 4) <= 1);
+        // 模糊的阴影向四周散开
+        let blurred = ink(&shadow(2.0));
+        assert!(blurred.0 < sharp.0 && blurred.1 < sharp.1);
+        assert!(blurred.2 > sharp.2 && blurred.3 > sharp.3);
+    }
+
+    #[test]
+    fn outline_only() {
+        let font = test_font();
+        let options = TextOptions::default();
+        // 字号大一些，笔画比轮廓宽
+        let glyphs = text_to_glyphs("news", &font, PxScale::from(80.0), options);
+        let white = Rgba([255, 255, 255, 255]);
+
+        let mut fill = RgbaImage::new(260, 120);
+        draw_glyphs_to_rgba_buffer(
+            &mut fill,
+            &mut DrawContext::new(0),
+            &glyphs,
+            &font,
+            point(10.0, 10.0),
+            0.0,
+            white,
+        );
+        let mut outline = RgbaImage::new(260, 120);
+        draw_outline_to_rgba_buffer(
+            &mut outline,
+            &glyphs,
+            &font,
+            point(10.0, 10.0),
+            0.0,
+            2.0,
+            white,
+        );
+
+        let ink = |image: &RgbaImage| image.pixels().filter(|px| px.0[3] != 0).count();
+        assert!(ink(&outline) > 0 && ink(&outline) < ink(&fill));
+        // 轮廓只在字形里面，字形中间是空的
+        let mut hollow = 0;
+        for (x, y, px) in outline.enumerate_pixels() {
+            if px.0[3] != 0 {
+                assert!(fill.get_pixel(x, y).0[3] != 0, "{x} {y}");
+            }
+            let solid = (-3..=3).all(|dy: i32| {
+                (-3..=3).all(|dx: i32| {
+                    fill.get_pixel_checked(x.wrapping_add_signed(dx), y.wrapping_add_signed(dy))
+                        .is_some_and(|px| px.0[3] == 255)
+                })
+            });
+            if solid {
+                assert_eq!(px.0[3], 0, "{x} {y}");
+                hollow += 1;
+            }
+        }
+        assert!(hollow > 0);
+    }
+
+    #[cfg(feature = "emoji")]
+    #[test]
+    fn color_emoji() {
+        // 只有 CBDT 位图，没有轮廓
+        let font =
+            FontVec::try_from_vec(include_bytes!("../fonts/NotoColorEmoji-Partial.ttf").to_vec())
+                .unwrap();
+        let options = TextOptions::default();
+        let glyphs = text_to_glyphs("\u{a9}", &font, PxScale::from(40.0), options);
+        assert!(font.outline_glyph(glyphs.glyphs[0].clone()).is_none());
+
+        for angle in [0.0, 90.0] {
+            let mut image = RgbaImage::new(60, 60);
+            draw_glyphs_to_rgba_buffer(
+                &mut image,
+                &mut DrawContext::new(0),
+                &glyphs,
+                &font,
+                point(10.0, 10.0),
+                angle,
+                Rgba([255, 0, 0, 255]),
+            );
+            // 用的是字形自己的颜色，不是填充色
+            assert!(
+                image.pixels().any(|px| px.0[3] != 0 && px.0[1] != 0),
+                "{angle}"
+            );
+        }
+    }
+
+    #[cfg(feature = "emoji")]
+    #[test]
+    fn color_emoji_in_collection() {
+        // 字体集里第 0 个是普通字体，第 1 个是彩色字体，表的位置要加上字体在文件里的位置
+        let faces = [
+            include_bytes!("../fonts/Dengb.ttf").as_slice(),
+            include_bytes!("../fonts/NotoColorEmoji-Partial.ttf").as_slice(),
+        ];
+        let mut ttc = b"ttcf\0\x01\0\0\0\0\0\x02".to_vec();
+        let mut start = 12 + 4 * faces.len();
+        let mut fonts = vec![];
+        for face in faces {
+            ttc.extend_from_slice(&(start as u32).to_be_bytes());
+            let mut font = face.to_vec();
+            let tables = u16::from_be_bytes([font[4], font[5]]) as usize;
+            for record in 0..tables {
+                let at = 12 + record * 16 + 8;
+                let offset = u32::from_be_bytes(font[at..at + 4].try_into().unwrap());
+                font[at..at + 4].copy_from_slice(&(offset + start as u32).to_be_bytes());
+            }
+            font.resize(font.len().next_multiple_of(4), 0);
+            start += font.len();
+            fonts.push(font);
+        }
+        ttc.extend(fonts.concat());
+
+        let font = FontVec::try_from_vec_and_index(ttc, 1).unwrap();
+        let glyphs = text_to_glyphs("\u{a9}", &font, PxScale::from(40.0), TextOptions::default());
+        let mut image = RgbaImage::new(60, 60);
+        draw_glyphs_to_rgba_buffer(
+            &mut image,
+            &mut DrawContext::new(1),
+            &glyphs,
+            &font,
+            point(10.0, 10.0),
+            0.0,
+            Rgba([255, 0, 0, 255]),
+        );
+        assert!(image.pixels().any(|px| px.0[3] != 0 && px.0[1] != 0));
+    }
 }
@@ -1,20 +1,173 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    io::{self, BufRead},
+};
 
 use jieba_rs::Jieba;
 use regex::Regex;
 
+/// 归一化前对词频做的变换，用来压缩高频词和低频词之间的差距
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FrequencyTransform {
+    #[default]
+    Linear,
+    Sqrt,
+    Log,
+}
+
+impl FrequencyTransform {
+    fn apply(&self, count: f32) -> f32 {
+        match self {
+            FrequencyTransform::Linear => count,
+            FrequencyTransform::Sqrt => count.sqrt(),
+            FrequencyTransform::Log => count.ln_1p(),
+        }
+    }
+}
+
+/// How jieba splits text into words
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TokenizeMode {
+    /// The most likely split, every character belongs to one word
+    #[default]
+    Default,
+    /// Like `Default`, plus the shorter dictionary words inside long words, e.g. for search
+    /// keywords. Overlapping words are all counted
+    Search,
+    /// Every dictionary word found anywhere in the text, overlapping. Ignores `with_hmm`
+    All,
+}
+
+/// Which casing of a word is shown when different casings are counted together, see
+/// `ChineseTokenizer::with_case_sensitive`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CasingPref {
+    /// The casing that occurs most often
+    #[default]
+    MostFrequent,
+    /// The casing with the most uppercase letters, e.g. "NASA" over "Nasa", for acronyms
+    PreferUppercase,
+    /// The casing with the fewest uppercase letters
+    PreferLowercase,
+    /// The casing that occurs first in the text
+    FirstSeen,
+}
+
+impl CasingPref {
+    /// 按偏好比较两种写法，偏好的排在前面，一样时是 `Equal`。`first_seen` 越小出现得越早
+    pub(crate) fn compare(
+        &self,
+        a: &str,
+        b: &str,
+        first_seen: impl Fn(&str) -> Option<usize>,
+    ) -> Ordering {
+        let uppercase = |word: &str| word.chars().filter(|c| c.is_uppercase()).count();
+        match self {
+            CasingPref::MostFrequent => Ordering::Equal,
+            CasingPref::PreferUppercase => uppercase(b).cmp(&uppercase(a)),
+            CasingPref::PreferLowercase => uppercase(a).cmp(&uppercase(b)),
+            CasingPref::FirstSeen => first_seen(a).cmp(&first_seen(b)),
+        }
+    }
+}
+
+/// What to do with words made only of digits, like "42" or full-width "４２". Words that mix
+/// digits and letters, like "COVID19" or "3D", are always kept. Full-width digits are counted
+/// as half-width, so "４２" and "42" are the same word, shown as "42" when the text has it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NumberPolicy {
+    #[default]
+    Drop,
+    Keep,
+    /// Drop numbers except 4-digit years from 1900 to 2100
+    KeepYears,
+}
+
+impl NumberPolicy {
+    fn keeps(&self, word: &str) -> bool {
+        match self {
+            NumberPolicy::Keep => true,
+            NumberPolicy::Drop => !is_number(word),
+            NumberPolicy::KeepYears => !is_number(word) || is_year(word),
+        }
+    }
+}
+
+fn borrow_keys(map: &HashMap<String, usize>) -> HashMap<&str, usize> {
+    map.iter()
+        .map(|(key, value)| (key.as_str(), *value))
+        .collect()
+}
+
+/// `char::is_numeric` 也包括全角数字和 "½" 这种
+fn is_number(word: &str) -> bool {
+    word.chars().all(char::is_numeric)
+}
+
+/// 只有半角和全角数字的词换成半角，其他的词是 None
+fn half_width_digits(word: &str) -> Option<String> {
+    word.chars()
+        .map(|c| match c {
+            '0'..='9' => Some(c),
+            '０'..='９' => char::from_u32(c as u32 - '０' as u32 + '0' as u32),
+            _ => None,
+        })
+        .collect()
+}
+
+/// 全角数字按半角算
+fn is_year(word: &str) -> bool {
+    match half_width_digits(word) {
+        Some(digits) if digits.len() == 4 => digits
+            .parse::<u32>()
+            .is_ok_and(|year| (1900..=2100).contains(&year)),
+        _ => false,
+    }
+}
+
+/// 结巴按这些范围认汉字
+fn has_han(str: &str) -> bool {
+    str.chars().any(|char| {
+        matches!(
+            char,
+            '\u{3400}'..='\u{4DBF}'
+                | '\u{4E00}'..='\u{9FFF}'
+                | '\u{F900}'..='\u{FAFF}'
+                | '\u{20000}'..='\u{2FA1F}'
+        )
+    })
+}
+
 pub struct ChineseTokenizer {
     //分词正则
     regex: Regex,
     pub jieba: Jieba,
     pub filter: HashSet<String>,
     pub min_word_length: usize,
+    pub max_word_length: usize,
+    /// Numbers are kept when this is false, whatever `number_policy` says
+    #[deprecated(note = "use `number_policy` instead")]
     pub exclude_numbers: bool,
+    pub number_policy: NumberPolicy,
     pub max_words: usize,
+    pub min_frequency: usize,
+    pub min_normalized_frequency: f32,
     pub repeat: bool,
+    pub case_sensitive: bool,
+    pub casing_preference: CasingPref,
+    pub frequency_transform: FrequencyTransform,
+    pub dedup_substrings: bool,
+    pub hmm: bool,
+    pub mode: TokenizeMode,
+    pub pos_filter: HashSet<String>,
+    pub word_weights: HashMap<String, f32>,
+    pub exclude_patterns: Vec<Regex>,
 }
 
 impl Default for ChineseTokenizer {
+    #[allow(deprecated)]
     fn default() -> Self {
         let regex = Regex::new("\\w[\\w']*").expect("Unable to compile tokenization regex");
 
@@ -23,9 +176,22 @@ impl Default for ChineseTokenizer {
             jieba: Jieba::new(),
             filter: Default::default(),
             min_word_length: 0,
+            max_word_length: 0,
             exclude_numbers: true,
+            number_policy: NumberPolicy::Drop,
             max_words: 200,
+            min_frequency: 0,
+            min_normalized_frequency: 0.0,
             repeat: false,
+            case_sensitive: false,
+            casing_preference: CasingPref::MostFrequent,
+            frequency_transform: FrequencyTransform::Linear,
+            dedup_substrings: false,
+            hmm: false,
+            mode: TokenizeMode::Default,
+            pos_filter: HashSet::new(),
+            word_weights: HashMap::new(),
+            exclude_patterns: Vec::new(),
         }
     }
 }
@@ -41,6 +207,19 @@ impl<'a> ChineseTokenizer {
         self
     }
 
+    /// Drops words longer than `size` chars, e.g. long compounds that take up a whole line.
+    /// 0 means no limit
+    pub fn with_max_word_length(mut self, size: usize) -> Self {
+        self.max_word_length = size;
+        self
+    }
+
+    /// Keeps only words of `min` to `max` chars, both inclusive. Same as `with_min_word_len`
+    /// and `with_max_word_length` together
+    pub fn with_word_length_range(self, min: usize, max: usize) -> Self {
+        self.with_min_word_len(min).with_max_word_length(max)
+    }
+
     pub fn with_regex(mut self, value: Regex) -> Self {
         self.regex = value;
         self
@@ -51,75 +230,310 @@ impl<'a> ChineseTokenizer {
         self
     }
 
+    /// Drops words counted fewer than `value` times, e.g. 2 to leave out words that occur
+    /// only once. Casings counted together are added up first
+    pub fn with_min_frequency(mut self, value: usize) -> Self {
+        self.min_frequency = value;
+        self
+    }
+
+    /// Drops words whose normalized frequency, after the transform and word weights, is below
+    /// `value`, from 0.0 to 1.0
+    pub fn with_min_normalized_frequency(mut self, value: f32) -> Self {
+        self.min_normalized_frequency = value;
+        self
+    }
+
+    /// Drops every word `pattern` matches anywhere in it, e.g. `\d` for words with a digit or
+    /// `^[a-zA-Z]$` for single letters. Can be called more than once, a word any of the
+    /// patterns match is dropped. Pieces of text without Chinese are matched as a whole before
+    /// they are split at punctuation, so with a `with_regex` that keeps URLs in one piece
+    /// `https?://` drops whole URLs
+    pub fn with_exclude_pattern(mut self, pattern: Regex) -> Self {
+        self.exclude_patterns.push(pattern);
+        self
+    }
+
     pub fn with_filter(mut self, value: &[&str]) -> Self {
-        self.filter = value.iter().map(|el| el.to_lowercase()).collect();
+        self.filter = value.iter().map(|el| el.to_string()).collect();
 
         self
     }
 
-    pub fn with_exclude_numbers(mut self, value: bool) -> Self {
-        self.exclude_numbers = value;
+    /// Same as `with_number_policy(NumberPolicy::Drop)` for true and `NumberPolicy::Keep` for false
+    pub fn with_exclude_numbers(self, value: bool) -> Self {
+        self.with_number_policy(if value {
+            NumberPolicy::Drop
+        } else {
+            NumberPolicy::Keep
+        })
+    }
+
+    #[allow(deprecated)]
+    pub fn with_number_policy(mut self, value: NumberPolicy) -> Self {
+        self.exclude_numbers = value != NumberPolicy::Keep;
+        self.number_policy = value;
         self
     }
 
+    /// 旧的 `exclude_numbers` 是 false 时保留所有数字
+    #[allow(deprecated)]
+    fn effective_number_policy(&self) -> NumberPolicy {
+        if self.exclude_numbers {
+            self.number_policy
+        } else {
+            NumberPolicy::Keep
+        }
+    }
+
     pub fn with_repeat(mut self, value: bool) -> Self {
         self.repeat = value;
         self
     }
 
-    fn tokenize(&'a self, text: &'a str) -> impl IntoIterator<Item = &str> {
+    /// 区分大小写时 "IT" 和 "it" 分开统计，过滤词也按原样比较
+    pub fn with_case_sensitive(mut self, value: bool) -> Self {
+        self.case_sensitive = value;
+        self
+    }
+
+    pub fn with_frequency_transform(mut self, value: FrequencyTransform) -> Self {
+        self.frequency_transform = value;
+        self
+    }
+
+    /// 统计完词频后，把被更长的词包含、且次数不比它多的词并进那个长词里，
+    /// 比如 "经济" 并进 "经济发展"
+    pub fn with_dedup_substrings(mut self, value: bool) -> Self {
+        self.dedup_substrings = value;
+        self
+    }
+
+    /// 用 HMM 识别词典里没有的词，比如人名和新词，不会被拆成单个的字。
+    /// 分词更准，但结果依赖 HMM 模型，换了 jieba 版本可能不一样，所以默认关闭
+    pub fn with_hmm(mut self, value: bool) -> Self {
+        self.hmm = value;
+        self
+    }
+
+    /// 只留下词性在 `allowed_tags` 里的词，比如 `["n", "nr", "ns"]` 只留名词、人名和地名。
+    /// 词性用 jieba 的标注，词典里没有的词是 "x"，英文是 "eng"，数字是 "m"
+    pub fn with_pos_filter(mut self, allowed_tags: &[&str]) -> Self {
+        self.pos_filter = allowed_tags.iter().map(|tag| tag.to_string()).collect();
+        self
+    }
+
+    /// Which casing is shown when words are counted case-insensitively. Casings that tie
+    /// fall back to the most frequent one
+    pub fn with_casing_preference(mut self, value: CasingPref) -> Self {
+        self.casing_preference = value;
+        self
+    }
+
+    pub fn with_tokenize_mode(mut self, value: TokenizeMode) -> Self {
+        self.mode = value;
+        self
+    }
+
+    /// 归一化之后、按 `max_words` 截断之前把这个词的词频乘以 `weight`，
+    /// 可以让出现次数少的词也排在前面、画得很大
+    pub fn with_word_weight(mut self, word: &str, weight: f32) -> Self {
+        self.word_weights.insert(word.to_string(), weight);
+        self
+    }
+
+    pub fn with_word_weights(mut self, weights: HashMap<String, f32>) -> Self {
+        self.word_weights.extend(weights);
+        self
+    }
+
+    fn tokenize(&'a self, text: &'a str) -> impl IntoIterator<Item = &'a str> {
         let mut iter: Box<dyn Iterator<Item = &str>> = Box::new(
             self.regex
                 .find_iter(text)
                 .map(|mat| mat.as_str())
                 .filter(|str| !str.is_empty())
-                .flat_map(|str| self.jieba.cut(str, false)),
+                // 没有汉字的片段结巴只会按标点切开，先整段判断，网址之类的才能整个去掉
+                .filter(|str| has_han(str) || !self.is_excluded(str))
+                // 结巴会把全角数字拆成一个个字，纯数字不用分词
+                .flat_map(|str| {
+                    if is_number(str) {
+                        return vec![str];
+                    }
+                    match self.mode {
+                        TokenizeMode::Default => self.jieba.cut(str, self.hmm),
+                        TokenizeMode::Search => self.jieba.cut_for_search(str, self.hmm),
+                        TokenizeMode::All => self.jieba.cut_all(str),
+                    }
+                }),
         );
 
-        if self.min_word_length > 0 {
+        if self.min_word_length > 0 || self.max_word_length > 0 {
             iter = Box::new(iter.filter(|str| {
                 let chars = str.chars().count();
                 chars >= self.min_word_length
+                    && (self.max_word_length == 0 || chars <= self.max_word_length)
             }));
         }
 
-        if self.exclude_numbers {
-            iter = Box::new(iter.filter(move |word| !word.chars().all(char::is_numeric)));
+        let number_policy = self.effective_number_policy();
+        if number_policy != NumberPolicy::Keep {
+            iter = Box::new(iter.filter(move |word| number_policy.keeps(word)));
         }
 
-        if !self.filter.is_empty() {
-            iter = Box::new(iter.filter(|str| {
-                let lower_case = str.to_lowercase();
-                !self.filter.contains(&lower_case)
+        if !self.exclude_patterns.is_empty() {
+            iter = Box::new(iter.filter(|word| !self.is_excluded(word)));
+        }
+
+        if !self.pos_filter.is_empty() {
+            // 每个词单独标注，搜索模式切出来的重叠的词也能用
+            iter = Box::new(iter.filter(|word| {
+                self.jieba
+                    .tag(word, self.hmm)
+                    .first()
+                    .is_some_and(|tag| self.pos_filter.contains(tag.tag))
             }));
         }
 
+        if !self.filter.is_empty() {
+            if self.case_sensitive {
+                iter = Box::new(iter.filter(|str| !self.filter.contains(*str)));
+            } else {
+                let filter = self
+                    .filter
+                    .iter()
+                    .map(|el| el.to_lowercase())
+                    .collect::<HashSet<_>>();
+                iter = Box::new(iter.filter(move |str| {
+                    let lower_case = str.to_lowercase();
+                    !filter.contains(&lower_case)
+                }));
+            }
+        }
+
         iter
     }
 
+    fn is_excluded(&self, word: &str) -> bool {
+        self.exclude_patterns
+            .iter()
+            .any(|pattern| pattern.is_match(word))
+    }
+
     pub fn get_word_frequencies(&'a self, text: &'a str) -> HashMap<&'a str, usize> {
         let mut frequencies = HashMap::new();
+        let mut first_seen = HashMap::new();
 
         for word in self.tokenize(text) {
+            let next = first_seen.len();
+            first_seen.entry(word).or_insert(next);
             let entry = frequencies.entry(word).or_insert(0);
             *entry += 1;
         }
 
-        let common_cased_map = Self::keep_common_case(&frequencies);
+        let mut frequencies = self.keep_common_case(&frequencies, &first_seen);
+        frequencies.retain(|_, count| *count >= self.min_frequency);
+
+        if self.dedup_substrings {
+            dedup_substrings(frequencies)
+        } else {
+            frequencies
+        }
+    }
+
+    /// 按行流式读取文本并统计词频，整个文本不会同时放在内存里
+    pub fn get_word_frequencies_from_reader(
+        &self,
+        reader: impl BufRead,
+    ) -> io::Result<HashMap<String, usize>> {
+        let (frequencies, first_seen) = self.count_words_from_reader(reader)?;
+
+        let mut frequencies: HashMap<String, usize> = if self.case_sensitive {
+            frequencies
+        } else {
+            self.keep_common_case(&borrow_keys(&frequencies), &borrow_keys(&first_seen))
+                .into_iter()
+                .map(|(word, count)| (word.to_string(), count))
+                .collect()
+        };
+        frequencies.retain(|_, count| *count >= self.min_frequency);
+
+        if self.dedup_substrings {
+            Ok(dedup_substrings(frequencies))
+        } else {
+            Ok(frequencies)
+        }
+    }
+
+    /// Like `get_word_frequencies_from_reader`, but every casing of a word keeps its own count
+    /// and `min_frequency` and `dedup_substrings` aren't applied, so the counts of several
+    /// texts can be merged before the casing is chosen, see
+    /// `FrequencyMap::with_consistent_word_case_across_corpus`
+    pub fn get_word_frequencies_by_case_from_reader(
+        &self,
+        reader: impl BufRead,
+    ) -> io::Result<HashMap<String, usize>> {
+        self.count_words_from_reader(reader)
+            .map(|(frequencies, _)| frequencies)
+    }
+
+    /// 每种写法的次数和第一次出现的顺序。每攒够 `STREAM_CHUNK_SIZE` 字节的整行才交给结巴分词
+    fn count_words_from_reader(
+        &self,
+        mut reader: impl BufRead,
+    ) -> io::Result<(HashMap<String, usize>, HashMap<String, usize>)> {
+        const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+        let mut frequencies = HashMap::<String, usize>::new();
+        let mut first_seen = HashMap::<String, usize>::new();
+        let mut chunk = String::with_capacity(STREAM_CHUNK_SIZE);
+        loop {
+            let read = reader.read_line(&mut chunk)?;
 
-        common_cased_map
+            if chunk.len() >= STREAM_CHUNK_SIZE || (read == 0 && !chunk.is_empty()) {
+                for word in self.tokenize(&chunk) {
+                    // 这里的词是自己的，纯数字可以直接换成半角
+                    let digits = half_width_digits(word);
+                    let word = digits.as_deref().unwrap_or(word);
+                    match frequencies.get_mut(word) {
+                        Some(count) => *count += 1,
+                        None => {
+                            first_seen.insert(word.to_string(), first_seen.len());
+                            frequencies.insert(word.to_string(), 1);
+                        }
+                    }
+                }
+                chunk.clear();
+            }
+
+            if read == 0 {
+                break;
+            }
+        }
+
+        Ok((frequencies, first_seen))
     }
 
-    fn keep_common_case(map: &HashMap<&'a str, usize>) -> HashMap<&'a str, usize> {
+    /// `first_seen` 是每种写法第一次出现的顺序。纯数字的全角和半角写法也算一个词，有半角的就用半角
+    fn keep_common_case(
+        &self,
+        map: &HashMap<&'a str, usize>,
+        first_seen: &HashMap<&str, usize>,
+    ) -> HashMap<&'a str, usize> {
         type CaseCounts<'a> = HashMap<&'a str, usize>;
 
         let mut common_cases = HashMap::<String, CaseCounts>::new();
         for (key, val) in map {
-            common_cases
-                .entry(key.to_lowercase())
-                .or_default()
-                .insert(key, *val);
+            let group = half_width_digits(key).unwrap_or_else(|| {
+                if self.case_sensitive {
+                    key.to_string()
+                } else {
+                    key.to_lowercase()
+                }
+            });
+            common_cases.entry(group).or_default().insert(key, *val);
         }
+        let half_width = |word: &str| half_width_digits(word).as_deref() == Some(word);
 
         common_cases
             .values()
@@ -130,11 +544,15 @@ impl<'a> ChineseTokenizer {
                     .collect();
 
                 most_common_case.sort_by(|a, b| {
-                    if a.1 != b.1 {
-                        (b.1).partial_cmp(&a.1).unwrap()
-                    } else {
-                        (b.0).partial_cmp(a.0).unwrap()
-                    }
+                    let preferred = self
+                        .casing_preference
+                        .compare(a.0, b.0, |word| first_seen.get(word).copied());
+                    // 数字先用半角的写法，最后按字符串比较，结果不受 HashMap 顺序影响
+                    half_width(b.0)
+                        .cmp(&half_width(a.0))
+                        .then(preferred)
+                        .then(b.1.cmp(&a.1))
+                        .then(b.0.cmp(a.0))
                 });
 
                 let occurrence_sum = val.values().sum();
@@ -145,35 +563,88 @@ impl<'a> ChineseTokenizer {
     }
 
     pub fn get_normalized_word_frequencies(&'a self, text: &'a str) -> Vec<(&'a str, f32)> {
-        let frequencies = self.get_word_frequencies(text);
+        self.normalize_frequencies(self.get_word_frequencies(text))
+    }
 
-        if frequencies.is_empty() {
-            return vec![];
+    /// 乘上权重以后再归一化一次，最大的还是 1.0
+    fn apply_word_weights(&self, normalized_freqs: &mut [(&str, f32)]) {
+        let weights = if self.case_sensitive {
+            self.word_weights.clone()
+        } else {
+            self.word_weights
+                .iter()
+                .map(|(word, weight)| (word.to_lowercase(), *weight))
+                .collect()
+        };
+
+        for (word, freq) in normalized_freqs.iter_mut() {
+            let weight = if self.case_sensitive {
+                weights.get(*word)
+            } else {
+                weights.get(&word.to_lowercase())
+            };
+            if let Some(weight) = weight {
+                *freq *= weight.max(0.0);
+            }
         }
 
-        let max_freq = *frequencies
-            .values()
-            .max()
-            .expect("Can't not find max frequency") as f32;
+        let max_freq = normalized_freqs
+            .iter()
+            .map(|(_, freq)| *freq)
+            .fold(0.0, f32::max);
+        if max_freq > 1.0 {
+            for (_, freq) in normalized_freqs.iter_mut() {
+                *freq /= max_freq;
+            }
+        }
+    }
+
+    /// 把词频归一化到 0.0 - 1.0 并排序，应用 `max_words` 和 `repeat`
+    pub fn normalize_frequencies(
+        &self,
+        frequencies: HashMap<&'a str, usize>,
+    ) -> Vec<(&'a str, f32)> {
+        self.normalize_weighted_frequencies(
+            frequencies
+                .into_iter()
+                .map(|(word, count)| (word, count as f32))
+                .collect(),
+        )
+    }
+
+    /// 和 `normalize_frequencies` 一样，次数乘过权重以后可以是小数
+    pub(crate) fn normalize_weighted_frequencies(
+        &self,
+        mut frequencies: HashMap<&'a str, f32>,
+    ) -> Vec<(&'a str, f32)> {
+        // 权重是 0 的词不算，全是 0 时没有词可画
+        frequencies.retain(|_, count| *count > 0.0);
+        let max_freq = self
+            .frequency_transform
+            .apply(frequencies.values().copied().fold(0.0, f32::max));
+        if max_freq <= 0.0 {
+            return vec![];
+        }
 
         let mut normalized_freqs: Vec<(&str, f32)> = frequencies
             .into_iter()
-            .map(|(key, val)| (key, val as f32 / max_freq))
+            .map(|(key, val)| (key, self.frequency_transform.apply(val) / max_freq))
             .collect();
 
-        normalized_freqs.sort_by(|a, b| {
-            if a.1 != b.1 {
-                (b.1).partial_cmp(&a.1).unwrap()
-            } else {
-                (a.0).partial_cmp(b.0).unwrap()
-            }
-        });
+        if !self.word_weights.is_empty() {
+            self.apply_word_weights(&mut normalized_freqs);
+        }
+        if self.min_normalized_frequency > 0.0 {
+            normalized_freqs.retain(|(_, freq)| *freq >= self.min_normalized_frequency);
+        }
+
+        normalized_freqs.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
 
         if self.max_words > 0 {
             normalized_freqs.truncate(self.max_words);
         }
 
-        if self.repeat && normalized_freqs.len() < self.max_words as usize {
+        if self.repeat && !normalized_freqs.is_empty() && normalized_freqs.len() < self.max_words {
             let times_extend =
                 ((self.max_words as f32 / normalized_freqs.len() as f32).ceil()) as u32 - 1;
 
@@ -196,14 +667,74 @@ impl<'a> ChineseTokenizer {
     }
 }
 
+/// 每个词找包含它、次数不少于它的词里次数最多的那个（一样多时取更长的），
+/// 顺着链条并到最终的词上。只枚举每个词的子串，不用两两比较
+fn dedup_substrings<K>(frequencies: HashMap<K, usize>) -> HashMap<K, usize>
+where
+    K: AsRef<str> + Eq + Hash + Clone,
+{
+    let lookup = frequencies
+        .iter()
+        .map(|(word, count)| (word.as_ref(), (word, *count)))
+        .collect::<HashMap<&str, _>>();
+
+    let mut targets = HashMap::<&K, (&K, usize, usize)>::new();
+    for (longer, longer_count) in lookup.values() {
+        let text = longer.as_ref();
+        let longer_len = text.chars().count();
+        let boundaries = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain([text.len()])
+            .collect::<Vec<_>>();
+
+        for start in 0..boundaries.len() - 1 {
+            for end in start + 1..boundaries.len() {
+                if end - start == longer_len {
+                    continue;
+                }
+                let Some((shorter, shorter_count)) =
+                    lookup.get(&text[boundaries[start]..boundaries[end]])
+                else {
+                    continue;
+                };
+                if longer_count < shorter_count {
+                    continue;
+                }
+
+                let better = targets
+                    .get(shorter)
+                    .is_none_or(|(_, count, len)| (*longer_count, longer_len) > (*count, *len));
+                if better {
+                    targets.insert(shorter, (longer, *longer_count, longer_len));
+                }
+            }
+        }
+    }
+
+    let mut deduped = HashMap::with_capacity(frequencies.len());
+    for (word, count) in &frequencies {
+        let mut target = word;
+        while let Some((longer, _, _)) = targets.get(target) {
+            target = longer;
+        }
+        *deduped.entry(target.clone()).or_insert(0) += count;
+    }
+
+    deduped
+}
+
 #[cfg(test)]
 mod tests {
     use std::{
+        collections::HashMap,
         fs::{self, OpenOptions},
         io::Write,
     };
 
-    use super::ChineseTokenizer;
+    use regex::Regex;
+
+    use super::{CasingPref, ChineseTokenizer, FrequencyTransform, NumberPolicy, TokenizeMode};
 
     #[test]
     fn wukong() {
@@ -232,4 +763,282 @@ mod tests {
         path.write_all(format!("all:{:?}\n", frequencies.len()).as_bytes())
             .unwrap();
     }
+
+    #[test]
+    fn case_sensitive() {
+        let text = "IT it It";
+
+        let tokenizer = ChineseTokenizer::default().with_case_sensitive(true);
+        let frequencies = tokenizer.get_word_frequencies(text);
+        assert_eq!(frequencies.len(), 3);
+        assert_eq!(frequencies["IT"], 1);
+        assert_eq!(frequencies["it"], 1);
+        assert_eq!(frequencies["It"], 1);
+
+        let tokenizer = ChineseTokenizer::default();
+        let frequencies = tokenizer.get_word_frequencies(text);
+        assert_eq!(frequencies.len(), 1);
+        assert_eq!(frequencies.values().sum::<usize>(), 3);
+    }
+
+    #[test]
+    fn casing_preference() {
+        let text = "nasa Nasa NASA Nasa";
+        let shown = |preference| {
+            let tokenizer = ChineseTokenizer::default().with_casing_preference(preference);
+            let frequencies = tokenizer.get_word_frequencies(text);
+            assert_eq!(frequencies.len(), 1);
+            let (word, count) = frequencies.into_iter().next().unwrap();
+            assert_eq!(count, 4);
+            word.to_string()
+        };
+
+        assert_eq!(shown(CasingPref::MostFrequent), "Nasa");
+        assert_eq!(shown(CasingPref::PreferUppercase), "NASA");
+        assert_eq!(shown(CasingPref::PreferLowercase), "nasa");
+        assert_eq!(shown(CasingPref::FirstSeen), "nasa");
+
+        let tokenizer =
+            ChineseTokenizer::default().with_casing_preference(CasingPref::PreferUppercase);
+        let frequencies = tokenizer
+            .get_word_frequencies_from_reader(text.as_bytes())
+            .unwrap();
+        assert!(frequencies.contains_key("NASA"));
+    }
+
+    #[test]
+    fn min_frequency() {
+        let text = "apple apple apple Pear pear plum";
+
+        let tokenizer = ChineseTokenizer::default().with_min_frequency(2);
+        let frequencies = tokenizer.get_normalized_word_frequencies(text);
+        assert_eq!(frequencies.len(), 2);
+        assert!(frequencies.iter().all(|(word, _)| *word != "plum"));
+        let streamed = tokenizer
+            .get_word_frequencies_from_reader(text.as_bytes())
+            .unwrap();
+        assert!(!streamed.contains_key("plum"));
+
+        let tokenizer = ChineseTokenizer::default().with_min_normalized_frequency(0.5);
+        let frequencies = tokenizer.get_normalized_word_frequencies(text);
+        assert_eq!(frequencies, vec![("apple", 1.0), ("pear", 2.0 / 3.0)]);
+    }
+
+    #[test]
+    fn frequency_transform() {
+        let text = "apple apple apple apple pear";
+
+        let tokenizer = ChineseTokenizer::default();
+        let linear = tokenizer.get_normalized_word_frequencies(text);
+
+        let tokenizer =
+            ChineseTokenizer::default().with_frequency_transform(FrequencyTransform::Sqrt);
+        let sqrt = tokenizer.get_normalized_word_frequencies(text);
+
+        assert_eq!(linear, vec![("apple", 1.0), ("pear", 0.25)]);
+        assert_eq!(sqrt, vec![("apple", 1.0), ("pear", 0.5)]);
+    }
+
+    #[test]
+    fn dedup_substrings() {
+        let tokenizer = ChineseTokenizer::default();
+        let frequencies = HashMap::from([("经济", 2), ("经济发展", 3), ("发展", 5), ("社会", 1)]);
+
+        let deduped = super::dedup_substrings(frequencies);
+        assert_eq!(
+            deduped,
+            HashMap::from([("经济发展", 5), ("发展", 5), ("社会", 1)])
+        );
+
+        let tokenizer = tokenizer.with_word("经济发展").with_dedup_substrings(true);
+        let frequencies = tokenizer.get_word_frequencies("经济发展 经济发展 经济");
+        assert_eq!(frequencies, HashMap::from([("经济发展", 3)]));
+    }
+
+    #[test]
+    fn word_weights() {
+        let text = "apple apple apple apple pear pear plum";
+
+        let tokenizer = ChineseTokenizer::default()
+            .with_max_words(2)
+            .with_word_weight("Plum", 8.0);
+        let frequencies = tokenizer.get_normalized_word_frequencies(text);
+
+        assert_eq!(frequencies, vec![("plum", 1.0), ("apple", 0.5)]);
+    }
+
+    #[test]
+    fn word_length_range() {
+        let text = "一带一路倡议书 经济 经济 发展 我";
+        let tokenizer = ChineseTokenizer::default().with_word("一带一路倡议书");
+        assert!(tokenizer
+            .get_word_frequencies(text)
+            .contains_key("一带一路倡议书"));
+
+        let tokenizer = tokenizer.with_max_word_length(5);
+        let frequencies = tokenizer.get_word_frequencies(text);
+        assert!(!frequencies.contains_key("一带一路倡议书"));
+        assert_eq!(frequencies.get("经济"), Some(&2));
+
+        let tokenizer = tokenizer.with_word_length_range(2, 5);
+        let frequencies = tokenizer.get_word_frequencies(text);
+        assert_eq!(frequencies, HashMap::from([("经济", 2), ("发展", 1)]));
+    }
+
+    #[test]
+    fn number_policy() {
+        let text = "2024 ２０２４ 1850 123 １２３ ５６ COVID19 3D";
+        let words = |tokenizer: ChineseTokenizer| {
+            let mut words = tokenizer
+                .with_case_sensitive(true)
+                .get_word_frequencies(text)
+                .into_iter()
+                .map(|(word, count)| (word.to_string(), count))
+                .collect::<Vec<_>>();
+            words.sort();
+            words
+        };
+        let policy = |policy| words(ChineseTokenizer::default().with_number_policy(policy));
+        let counts = |words: &[(&str, usize)]| {
+            words
+                .iter()
+                .map(|(word, count)| (word.to_string(), *count))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            policy(NumberPolicy::Drop),
+            counts(&[("3D", 1), ("COVID19", 1)])
+        );
+        // 全角的数字和半角的算一个词，只有全角的时候照原样
+        assert_eq!(
+            policy(NumberPolicy::KeepYears),
+            counts(&[("2024", 2), ("3D", 1), ("COVID19", 1)])
+        );
+        assert_eq!(
+            policy(NumberPolicy::Keep),
+            counts(&[
+                ("123", 2),
+                ("1850", 1),
+                ("2024", 2),
+                ("3D", 1),
+                ("COVID19", 1),
+                ("５６", 1)
+            ])
+        );
+
+        // 按行读的时候都换成半角
+        let from_reader = ChineseTokenizer::default()
+            .with_number_policy(NumberPolicy::Keep)
+            .get_word_frequencies_from_reader(text.as_bytes())
+            .unwrap();
+        assert_eq!(from_reader.get("56"), Some(&1));
+        assert_eq!(from_reader.get("123"), Some(&2));
+
+        // 旧的字段还能用，false 时保留所有数字
+        #[allow(deprecated)]
+        let keep_all = ChineseTokenizer {
+            exclude_numbers: false,
+            ..Default::default()
+        };
+        assert_eq!(words(keep_all), policy(NumberPolicy::Keep));
+        #[allow(deprecated)]
+        let exclude_numbers = ChineseTokenizer::default()
+            .with_number_policy(NumberPolicy::KeepYears)
+            .exclude_numbers;
+        assert!(exclude_numbers);
+    }
+
+    #[test]
+    fn hmm() {
+        let text = "我们请来了王小鸥老师";
+
+        let tokenizer = ChineseTokenizer::default();
+        let frequencies = tokenizer.get_word_frequencies(text);
+        assert!(!frequencies.contains_key("王小鸥"));
+        assert!(frequencies.contains_key("鸥"));
+
+        let tokenizer = ChineseTokenizer::default().with_hmm(true);
+        let frequencies = tokenizer.get_word_frequencies(text);
+        assert_eq!(frequencies.get("王小鸥"), Some(&1));
+    }
+
+    #[test]
+    fn tokenize_mode() {
+        let text = "小明硕士毕业于中国科学院计算所";
+        let count = |mode| {
+            let tokenizer = ChineseTokenizer::default().with_tokenize_mode(mode);
+            let frequencies = tokenizer.get_word_frequencies(text);
+            (
+                frequencies.values().sum::<usize>(),
+                frequencies.contains_key("科学"),
+            )
+        };
+
+        let (default, default_has_part) = count(TokenizeMode::Default);
+        let (search, search_has_part) = count(TokenizeMode::Search);
+        let (all, all_has_part) = count(TokenizeMode::All);
+        assert!(default < search && search < all, "{default} {search} {all}");
+        assert!(!default_has_part && search_has_part && all_has_part);
+    }
+
+    #[test]
+    fn pos_filter() {
+        let text = "我们在北京天安门广场见到了李小龙和他的朋友们";
+
+        let tokenizer = ChineseTokenizer::default();
+        let all = tokenizer.get_word_frequencies(text);
+        let tokenizer = ChineseTokenizer::default().with_pos_filter(&["n", "nr", "ns"]);
+        let nouns = tokenizer.get_word_frequencies(text);
+
+        assert!(nouns.len() < all.len());
+        assert!(nouns.contains_key("北京天安门广场"));
+        assert!(nouns.contains_key("李小龙"));
+        assert!(nouns.contains_key("朋友"));
+        for particle in ["我们", "了", "和", "的"] {
+            assert!(all.contains_key(particle), "{particle}");
+            assert!(!nouns.contains_key(particle), "{particle}");
+        }
+    }
+
+    #[test]
+    fn exclude_pattern() {
+        let text = "see https://example.com/rust 2024 and 42 times rust2 在2024年学习";
+        let tokenizer = ChineseTokenizer::default()
+            .with_number_policy(NumberPolicy::Keep)
+            .with_regex(Regex::new(r"https?://\S+|\w[\w']*").unwrap());
+        let all = tokenizer.get_word_frequencies(text);
+        assert!(all.contains_key("2024") && all.contains_key("example"));
+
+        let tokenizer = tokenizer
+            .with_exclude_pattern(Regex::new(r"^\d+$").unwrap())
+            .with_exclude_pattern(Regex::new(r"https?://").unwrap());
+        let frequencies = tokenizer.get_word_frequencies(text);
+        for word in frequencies.keys() {
+            assert!(!word.chars().all(|char| char.is_ascii_digit()), "{word}");
+        }
+        for part in ["https", "example", "com"] {
+            assert!(!frequencies.contains_key(part), "{part}");
+        }
+        // 只去掉匹配的词，同一段里的其他词还在
+        for word in ["see", "times", "rust2", "学习"] {
+            assert!(frequencies.contains_key(word), "{word}");
+        }
+    }
+
+    #[test]
+    fn frequencies_from_reader() {
+        let text = "apple banana\napple\n\nApple cherry";
+        let tokenizer = ChineseTokenizer::default();
+
+        let streamed = tokenizer
+            .get_word_frequencies_from_reader(text.as_bytes())
+            .unwrap();
+        let in_memory = tokenizer.get_word_frequencies(text);
+
+        assert_eq!(streamed.len(), in_memory.len());
+        for (word, count) in in_memory {
+            assert_eq!(streamed[word], count);
+        }
+    }
 }
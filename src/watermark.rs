@@ -0,0 +1,192 @@
+use ab_glyph::{point, PxScale};
+use image::{Rgba, RgbaImage};
+
+use crate::{text, WordCloud};
+
+/// An image or a line of text drawn over the finished word cloud in one of its corners, e.g.
+/// for attribution, see `WordCloud::with_watermark`
+#[derive(Clone, Debug)]
+pub struct WatermarkSpec {
+    pub content: WatermarkContent,
+    pub corner: Corner,
+    /// The distance from the edges of the image, in pixels
+    pub margin: u32,
+    /// 0.0 is invisible, 1.0 is drawn as is
+    pub opacity: f32,
+}
+
+#[derive(Clone, Debug)]
+pub enum WatermarkContent {
+    Image(RgbaImage),
+    /// Drawn in the word cloud's font, `font_size` is in pixels of the final image
+    Text {
+        text: String,
+        font_size: f32,
+        color: Rgba<u8>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+impl WatermarkSpec {
+    pub fn image(image: RgbaImage) -> Self {
+        Self::new(WatermarkContent::Image(image))
+    }
+
+    pub fn text(text: &str, font_size: f32, color: Rgba<u8>) -> Self {
+        Self::new(WatermarkContent::Text {
+            text: text.to_string(),
+            font_size,
+            color,
+        })
+    }
+
+    fn new(content: WatermarkContent) -> Self {
+        WatermarkSpec {
+            content,
+            corner: Corner::default(),
+            margin: 8,
+            opacity: 0.5,
+        }
+    }
+
+    pub fn with_corner(mut self, value: Corner) -> Self {
+        self.corner = value;
+        self
+    }
+
+    pub fn with_margin(mut self, value: u32) -> Self {
+        self.margin = value;
+        self
+    }
+
+    /// Clamped to 0.0 - 1.0
+    pub fn with_opacity(mut self, value: f32) -> Self {
+        self.opacity = value.clamp(0.0, 1.0);
+        self
+    }
+}
+
+impl WordCloud {
+    /// 把水印画到最终画布上从 (`origin_x`, `origin_y`) 开始的一块上，
+    /// 整张图是 `width` x `height`
+    pub(crate) fn draw_watermark(
+        &self,
+        buffer: &mut RgbaImage,
+        origin_x: i64,
+        origin_y: i64,
+        width: u32,
+        height: u32,
+    ) {
+        let Some(watermark) = &self.watermark else {
+            return;
+        };
+
+        let rendered;
+        let image = match &watermark.content {
+            WatermarkContent::Image(image) => image,
+            WatermarkContent::Text {
+                text,
+                font_size,
+                color,
+            } => {
+                let glyphs = text::text_to_glyphs(
+                    text,
+                    &self.font,
+                    PxScale::from(*font_size),
+                    self.text_options(),
+                );
+                let mut image = RgbaImage::new(glyphs.width, glyphs.height);
+                text::draw_glyphs_to_rgba_buffer(
+                    &mut image,
+                    &mut text::DrawContext::new(self.font_index),
+                    &glyphs,
+                    &self.font,
+                    point(0.0, 0.0),
+                    0.0,
+                    *color,
+                );
+                rendered = image;
+                &rendered
+            }
+        };
+
+        // 水印在整张图上的左上角，放不下时贴着左边或上边
+        let margin = watermark.margin as i64;
+        let (left, top) = match watermark.corner {
+            Corner::TopLeft => (margin, margin),
+            Corner::TopRight => (width as i64 - image.width() as i64 - margin, margin),
+            Corner::BottomLeft => (margin, height as i64 - image.height() as i64 - margin),
+            Corner::BottomRight => (
+                width as i64 - image.width() as i64 - margin,
+                height as i64 - image.height() as i64 - margin,
+            ),
+        };
+        let (left, top) = (left.max(0) - origin_x, top.max(0) - origin_y);
+
+        for (x, y, px) in image.enumerate_pixels() {
+            let (final_x, final_y) = (left + x as i64, top + y as i64);
+            if final_x < 0 || final_y < 0 {
+                continue;
+            }
+            if let Some(dst) = buffer.get_pixel_mut_checked(final_x as u32, final_y as u32) {
+                text::blend(dst, *px, watermark.opacity);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::{Corner, WatermarkSpec};
+    use crate::{text, WordCloud, WordCloudSize};
+
+    #[test]
+    fn watermark_in_corner() {
+        let size = || WordCloudSize::FromDimensions {
+            width: 200,
+            height: 100,
+        };
+        let text = "apple apple banana";
+        let plain = WordCloud::default()
+            .with_rng_seed(1)
+            .generate_from_text(text, size(), 1.0);
+
+        let white = Rgba([255, 255, 255, 255]);
+        let wordcloud = WordCloud::default().with_rng_seed(1).with_watermark(
+            WatermarkSpec::image(RgbaImage::from_pixel(10, 10, white))
+                .with_corner(Corner::BottomRight)
+                .with_margin(4)
+                .with_opacity(0.5),
+        );
+        let image = wordcloud.generate_from_text(text, size(), 1.0);
+        for (x, y, px) in image.enumerate_pixels() {
+            let mut expected = *plain.get_pixel(x, y);
+            if (186..196).contains(&x) && (86..96).contains(&y) {
+                text::blend(&mut expected, white, 0.5);
+            }
+            assert_eq!(*px, expected, "{x} {y}");
+        }
+
+        let red = Rgba([255, 0, 0, 255]);
+        let wordcloud = WordCloud::default().with_rng_seed(1).with_watermark(
+            WatermarkSpec::text("(c) wcloud", 12.0, red)
+                .with_corner(Corner::TopLeft)
+                .with_opacity(1.0),
+        );
+        let image = wordcloud.generate_from_text(text, size(), 1.0);
+        assert!(image
+            .enumerate_pixels()
+            .any(|(x, y, px)| x < 80 && y < 24 && *px == red));
+        assert_eq!(image.get_pixel(150, 80), plain.get_pixel(150, 80));
+    }
+}
@@ -0,0 +1,4418 @@
+//! 词云本身：放词、排版和画图，要 `std` feature
+
+use ab_glyph::{point, FontVec, Point, PxScale, VariableFont};
+use image::{imageops::FilterType, GrayImage, Luma, Rgba, RgbaImage};
+use nanorand::{Rng, WyRand};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fs,
+    path::PathBuf,
+    sync::Mutex,
+    time::Instant,
+};
+
+use crate::{
+    mask, sat, sat::Rect, score, text, text::GlyphData, ChineseTokenizer, ColorProfile,
+    ColorStrategy, FrequencyMap, GradientSpec, Limit, PathWord, ResourceLimits, WatermarkSpec,
+    WordCloudError,
+};
+
+#[derive(Clone)]
+pub struct Word<'a> {
+    pub text: &'a str,
+    pub font: &'a FontVec,
+    pub font_size: PxScale,
+    pub glyphs: GlyphData,
+    pub rotated: bool,
+    /// How far a rotated word is turned counterclockwise, in degrees. 90, or -90 with
+    /// `with_bidirectional_rotation`, unless `with_rotation_range` is set. 0 for words that
+    /// aren't rotated
+    pub angle: f32,
+    /// The top left of the word, or of its bounding box when it's rotated
+    pub position: Point,
+    pub frequency: f32,
+    pub index: usize,
+}
+
+/// The placed words of a word cloud, in canvas coordinates
+pub struct Layout<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub words: Vec<Word<'a>>,
+    /// Words that didn't fit and were left out under `DropPolicy::Silent`
+    pub dropped: Vec<String>,
+    pub(crate) rng: WyRand,
+    /// 放词时用的占位图，`replace_word` 要用
+    canvas: Canvas,
+    /// 开了 `pixel_clip_to_mask` 时处理过的遮罩，画字时挡住遮罩外面的像素
+    pub(crate) clip_mask: Option<GrayImage>,
+}
+
+pub type ColorFunc = dyn Fn(&Word, &mut WyRand) -> Rgba<u8> + Send + Sync;
+
+/// Decides from a word and its normalized frequency whether it is rotated on the first placement attempt
+pub type RotationDecider = dyn Fn(&str, f32) -> bool + Send + Sync;
+
+/// Decides whether a word may be drawn at a position, given its top-left corner and its size
+/// in canvas coordinates. Rejected positions are skipped and the search goes on
+pub type PlacementFilter = dyn Fn(&str, &Point, &Rect) -> bool + Send + Sync;
+
+/// Compares two words for `DrawOrder::Custom`
+pub type DrawOrderCompare = dyn Fn(&Word, &Word) -> Ordering + Send + Sync;
+
+/// Called with the fraction of words placed or dropped so far, see `WordCloud::with_progress`
+pub type ProgressFunc = dyn FnMut(f32) + Send;
+
+/// What to do with words that can't be placed, because they would be smaller than `min_font_size`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DropPolicy {
+    /// Leave them out of the word cloud
+    #[default]
+    Silent,
+    /// Fail the generation with `WordCloudError::WordsDropped`, or with
+    /// `WordCloudError::WordTooLarge` if the most frequent word doesn't fit the canvas at all
+    Error,
+}
+
+/// What to do with characters the font has no glyph for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MissingGlyphPolicy {
+    /// Draw the font's placeholder glyph, usually an empty box
+    #[default]
+    Keep,
+    /// Leave the character out of the word
+    Skip,
+    /// Draw this character instead
+    Replace(char),
+}
+
+/// How the lines of a word with line breaks line up with each other
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// A drop shadow drawn behind every word
+#[derive(Clone, Copy, Debug)]
+pub struct TextShadow {
+    pub offset_x: i32,
+    pub offset_y: i32,
+    /// The sigma of the gaussian blur, 0.0 for a hard shadow
+    pub blur: f32,
+    pub color: Rgba<u8>,
+}
+
+impl TextShadow {
+    /// The extra space (left, top, right, bottom) the shadow needs around a word at `scale`
+    pub(crate) fn extents(&self, scale: f32) -> (u32, u32, u32, u32) {
+        let blur = (self.blur * 3.0 * scale).ceil() as u32;
+        let offset_x = (self.offset_x as f32 * scale).round() as i32;
+        let offset_y = (self.offset_y as f32 * scale).round() as i32;
+
+        (
+            (-offset_x).max(0) as u32 + blur,
+            (-offset_y).max(0) as u32 + blur,
+            offset_x.max(0) as u32 + blur,
+            offset_y.max(0) as u32 + blur,
+        )
+    }
+}
+
+/// A shape words are kept inside of on a `WordCloudSize::FromDimensions` canvas, without
+/// needing a mask image. Positions outside of it are treated like the white parts of a mask
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShapeBias {
+    /// The ellipse touching the middle of every edge of the canvas
+    Ellipse,
+    /// The diamond with its corners in the middle of every edge of the canvas
+    Diamond,
+}
+
+/// The order words are placed in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PlacementOrder {
+    /// The most frequent words are placed first and the rest fills the space around them
+    #[default]
+    LargestFirst,
+    /// The least frequent words are placed first, so they all fit before the large words
+    /// take up the space. Large words shrink until they fit in what's left
+    SmallestFirst,
+}
+
+/// How the font size of each word is chosen
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SizeStrategy {
+    /// The font size follows the frequency, see `with_relative_font_scaling`
+    #[default]
+    Frequency,
+    /// Every word gets about the same bounding box area as the most frequent word, whatever
+    /// its frequency, so short words get bigger fonts than long ones
+    EqualArea,
+}
+
+/// The order words are drawn in, which decides which word ends up on top where words or
+/// their shadows touch
+#[derive(Default)]
+pub enum DrawOrder {
+    /// The order they were placed in
+    #[default]
+    Placement,
+    /// The most frequent word first, so the least frequent words are on top
+    FrequencyDescending,
+    /// The least frequent word first, so the most frequent words are on top
+    FrequencyAscending,
+    /// Words that compare as less are drawn first
+    Custom(Box<DrawOrderCompare>),
+}
+
+/// How the glyphs of a word are drawn
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum GlyphStyle {
+    #[default]
+    Fill,
+    /// Hollow letters, only the edge of each glyph `width` pixels wide is drawn
+    Outline { width: f32 },
+}
+
+// TODO: Figure out a better way to structure this
+pub enum WordCloudSize {
+    FromDimensions {
+        width: u32,
+        height: u32,
+    },
+    FromMask(GrayImage),
+    /// Like `FromMask`, with the mask scaled first so its longest side is `max_dimension`
+    /// pixels, keeping its aspect ratio and its hard edges
+    FromMaskScaled {
+        mask: GrayImage,
+        max_dimension: u32,
+    },
+}
+
+#[cfg(feature = "svg")]
+impl WordCloudSize {
+    /// A `FromMask` made by filling an SVG path such as `M 10 10 L 90 10 Z` on a `width` x
+    /// `height` canvas, words go inside the path. Fails with `WordCloudError::InvalidConfig` if
+    /// the path can't be parsed or doesn't cover any pixel
+    pub fn from_svg_path(path: &str, width: u32, height: u32) -> Result<Self, WordCloudError> {
+        mask::svg_path_mask(path, width, height).map(WordCloudSize::FromMask)
+    }
+}
+
+/// 量第一个词宽高比时的字号占画布高的比例，没有设置 `first_word_height_fraction` 时也用来
+/// 估算字号
+const DEFAULT_FIRST_WORD_HEIGHT_FRACTION: f32 = 0.55;
+
+pub struct WordCloud {
+    pub(crate) tokenizer: ChineseTokenizer,
+    pub(crate) background_color: Rgba<u8>,
+    pub(crate) background_gradient: Option<GradientSpec>,
+    pub(crate) watermark: Option<WatermarkSpec>,
+    pub font: FontVec,
+    /// `font` 在字体集里是第几个，重新解析字体时要用
+    pub(crate) font_index: u32,
+    pub(crate) min_font_size: f32,
+    pub(crate) max_font_size: Option<f32>,
+    pub(crate) max_font_size_ratio: Option<f32>,
+    /// 没有设置时第一个词从和画布一样宽开始
+    pub(crate) first_word_height_fraction: Option<f32>,
+    pub(crate) font_step: f32,
+    pub(crate) visual_margin: u32,
+    /// 负的时候词的外框可以互相重叠一点
+    pub(crate) collision_margin: i32,
+    /// 每个词的间距再加上这个乘以词频
+    pub(crate) frequency_margin_scale: f32,
+    pub(crate) word_rotate_chance: f64,
+    pub(crate) relative_font_scaling: f32,
+    pub(crate) rng_seed: Option<u64>,
+    pub(crate) preview_scale: f32,
+    pub(crate) color_func: Option<Box<ColorFunc>>,
+    pub(crate) color_strategy: ColorStrategy,
+    pub(crate) word_colors: HashMap<String, Rgba<u8>>,
+    pub(crate) drop_policy: DropPolicy,
+    pub(crate) text_shadow: Option<TextShadow>,
+    pub(crate) mask_invert: bool,
+    pub(crate) mask_edge_margin: u32,
+    pub(crate) mask_threshold: u8,
+    pub(crate) balance_across_regions: bool,
+    pub(crate) pixel_clip_to_mask: bool,
+    pub(crate) exclusion_mask: Option<GrayImage>,
+    pub(crate) rotation_decider: Option<Box<RotationDecider>>,
+    pub(crate) rotation_range: Option<(f32, f32, f32)>,
+    pub(crate) bidirectional_rotation: bool,
+    pub(crate) glyph_style: GlyphStyle,
+    pub(crate) global_alpha: f32,
+    pub(crate) max_canvas_bytes: usize,
+    pub(crate) resource_limits: ResourceLimits,
+    pub(crate) path_words: Vec<PathWord>,
+    font_variations: Vec<([u8; 4], f32)>,
+    frequency_variation: Option<([u8; 4], f32, f32)>,
+    variation_fonts: Vec<FontVec>,
+    pub(crate) relocate_threshold: Option<f32>,
+    pub(crate) placement_filter: Option<Box<PlacementFilter>>,
+    pub(crate) repeat_max_copies: Option<usize>,
+    pub(crate) min_same_word_distance: f32,
+    pub(crate) occupancy_threshold: f32,
+    pub(crate) shape_bias: Option<ShapeBias>,
+    pub(crate) text_align: Align,
+    pub(crate) placement_candidates: usize,
+    pub(crate) auto_enlarge: bool,
+    pub(crate) fill_empty_space: bool,
+    pub(crate) deterministic_seed: bool,
+    pub(crate) tab_width: usize,
+    pub(crate) missing_glyph_policy: MissingGlyphPolicy,
+    pub(crate) letter_spacing: f32,
+    pub(crate) placement_order: PlacementOrder,
+    pub(crate) size_strategy: SizeStrategy,
+    pub(crate) draw_order: DrawOrder,
+    pub(crate) output_dimensions: Option<(u32, u32)>,
+    pub(crate) supersample: u32,
+    pub(crate) flatten_color: Option<Rgba<u8>>,
+    pub(crate) output_color_profile: ColorProfile,
+    progress: Option<Mutex<Box<ProgressFunc>>>,
+}
+
+impl Default for WordCloud {
+    fn default() -> Self {
+        let font = FontVec::try_from_vec(include_bytes!("../fonts/Dengb.ttf").to_vec()).unwrap();
+
+        WordCloud {
+            tokenizer: ChineseTokenizer::default(),
+            background_color: Rgba([0, 0, 0, 255]),
+            background_gradient: None,
+            watermark: None,
+            font,
+            font_index: 0,
+            min_font_size: 4.0,
+            max_font_size: None,
+            max_font_size_ratio: None,
+            first_word_height_fraction: None,
+            font_step: 1.0,
+            visual_margin: 2,
+            collision_margin: 2,
+            frequency_margin_scale: 0.0,
+            word_rotate_chance: 0.10,
+            relative_font_scaling: 0.5,
+            rng_seed: None,
+            preview_scale: 1.0,
+            color_func: None,
+            color_strategy: ColorStrategy::Random,
+            word_colors: HashMap::new(),
+            drop_policy: DropPolicy::Silent,
+            text_shadow: None,
+            mask_invert: false,
+            mask_edge_margin: 0,
+            mask_threshold: 0,
+            balance_across_regions: false,
+            pixel_clip_to_mask: false,
+            exclusion_mask: None,
+            rotation_decider: None,
+            rotation_range: None,
+            bidirectional_rotation: false,
+            glyph_style: GlyphStyle::Fill,
+            global_alpha: 1.0,
+            max_canvas_bytes: 1 << 30,
+            resource_limits: ResourceLimits::default(),
+            path_words: vec![],
+            font_variations: vec![],
+            frequency_variation: None,
+            variation_fonts: vec![],
+            relocate_threshold: None,
+            placement_filter: None,
+            repeat_max_copies: None,
+            min_same_word_distance: 0.0,
+            occupancy_threshold: 0.05,
+            shape_bias: None,
+            text_align: Align::Left,
+            placement_candidates: 1,
+            auto_enlarge: false,
+            fill_empty_space: false,
+            deterministic_seed: false,
+            tab_width: 4,
+            missing_glyph_policy: MissingGlyphPolicy::Keep,
+            letter_spacing: 0.0,
+            placement_order: PlacementOrder::LargestFirst,
+            size_strategy: SizeStrategy::Frequency,
+            draw_order: DrawOrder::Placement,
+            output_dimensions: None,
+            supersample: 1,
+            flatten_color: None,
+            output_color_profile: ColorProfile::Srgb,
+            progress: None,
+        }
+    }
+}
+
+impl WordCloud {
+    pub fn with_tokenizer(mut self, value: ChineseTokenizer) -> Self {
+        self.tokenizer = value;
+        self
+    }
+
+    pub fn with_max_font_size(mut self, value: Option<f32>) -> Self {
+        self.max_font_size = value;
+        self
+    }
+
+    /// Caps the font size at `value * canvas height`, so the same settings work across canvas
+    /// sizes. When `max_font_size` is set as well, the smaller of the two wins
+    pub fn with_max_font_size_ratio(mut self, value: f32) -> Self {
+        assert!(value > 0.0, "Max font size ratio must be greater than 0");
+        self.max_font_size_ratio = Some(value);
+        self
+    }
+
+    /// Starts the first word at a font size of `value * canvas height`, clamped to 0.1 - 0.95,
+    /// or smaller if the word would not fit the canvas width. By default the first word starts
+    /// as wide as the canvas. Only the starting size changes, words are still shrunk until
+    /// they fit. `max_font_size` and `max_font_size_ratio` still cap the result, so a large
+    /// fraction has no effect when they are smaller
+    pub fn with_first_word_height_fraction(mut self, value: f32) -> Self {
+        self.first_word_height_fraction = Some(value.clamp(0.1, 0.95));
+        self
+    }
+
+    pub fn with_font(mut self, value: FontVec) -> Self {
+        self.font = value;
+        self.font_index = 0;
+        self.apply_font_variations();
+        self
+    }
+
+    /// Sets a variation axis of a variable font, e.g. `with_font_variation("wght", 700.0)`.
+    /// Ignored when the font has no such axis
+    pub fn with_font_variation(mut self, axis: &str, value: f32) -> Self {
+        let tag = axis_tag(axis);
+        self.font_variations.retain(|(other, _)| *other != tag);
+        self.font_variations.push((tag, value));
+        self.apply_font_variations();
+        self
+    }
+
+    /// Varies an axis of a variable font with the word frequency, from `min` for the least
+    /// frequent words to `max` for the most frequent, e.g. `("wght", 300.0, 900.0)` to make
+    /// frequent words bolder. The axis is sampled at a few steps, each being a copy of the font
+    pub fn with_frequency_font_variation(mut self, axis: &str, min: f32, max: f32) -> Self {
+        self.frequency_variation = Some((axis_tag(axis), min, max));
+        self.apply_font_variations();
+        self
+    }
+
+    /// 把变体设置应用到字体上，并重新生成按词频变化的那几份字体
+    fn apply_font_variations(&mut self) {
+        const FREQUENCY_VARIATION_STEPS: usize = 5;
+
+        for (tag, value) in &self.font_variations {
+            self.font.set_variation(tag, *value);
+        }
+
+        self.variation_fonts.clear();
+        if let Some((tag, min, max)) = self.frequency_variation {
+            for step in 0..FREQUENCY_VARIATION_STEPS {
+                // FontVec 不能 clone，只能从字节重新解析
+                let Ok(mut font) =
+                    FontVec::try_from_vec_and_index(self.font.as_slice().to_vec(), self.font_index)
+                else {
+                    continue;
+                };
+                for (tag, value) in &self.font_variations {
+                    font.set_variation(tag, *value);
+                }
+                let t = step as f32 / (FREQUENCY_VARIATION_STEPS - 1) as f32;
+                font.set_variation(&tag, min + (max - min) * t);
+                self.variation_fonts.push(font);
+            }
+        }
+    }
+
+    /// The font a word with normalized frequency `freq` is drawn with
+    fn font_for_frequency(&self, freq: f32) -> &FontVec {
+        if self.variation_fonts.is_empty() {
+            return &self.font;
+        }
+
+        let step = (freq.clamp(0.0, 1.0) * (self.variation_fonts.len() - 1) as f32).round();
+        &self.variation_fonts[step as usize]
+    }
+
+    pub fn with_min_font_size(mut self, value: f32) -> Self {
+        self.min_font_size = value;
+        self
+    }
+
+    /// Sets the canvas color, opaque black by default, the same as the command line tool. Words
+    /// are composited over it using their glyph coverage and color alpha, so with a transparent
+    /// background the gaps stay transparent and word edges are partially transparent
+    pub fn with_background_color(mut self, value: Rgba<u8>) -> Self {
+        self.background_color = value;
+        self
+    }
+
+    /// Fills the canvas with a gradient instead of the background color, at the final
+    /// resolution. Panics if the gradient has no stops
+    pub fn with_background_gradient(mut self, value: GradientSpec) -> Self {
+        assert!(
+            !value.stops.is_empty(),
+            "A gradient needs at least one stop"
+        );
+        self.background_gradient = Some(value);
+        self
+    }
+
+    /// Draws an image or a line of text over a corner of the finished word cloud, after all
+    /// the words
+    pub fn with_watermark(mut self, value: WatermarkSpec) -> Self {
+        self.watermark = Some(value);
+        self
+    }
+
+    /// Same as `with_background_color(Rgba([0, 0, 0, 0]))`
+    pub fn with_transparent_background(self) -> Self {
+        self.with_background_color(Rgba([0, 0, 0, 0]))
+    }
+
+    /// Sets both the visual and the collision margin
+    pub fn with_word_margin(mut self, value: u32) -> Self {
+        self.visual_margin = value;
+        self.collision_margin = value as i32;
+        self
+    }
+
+    /// Like `with_word_margin`, but negative values let the bounding boxes of neighboring
+    /// words overlap by that many pixels, e.g. to close the gaps left by the wide side
+    /// bearings of CJK fonts. The space reserved for a word never shrinks below 1 pixel
+    pub fn with_tight_margin(mut self, value: i32) -> Self {
+        self.visual_margin = value.max(0) as u32;
+        self.collision_margin = value;
+        self
+    }
+
+    /// Sets the offset at which a word is drawn inside the space reserved for it.
+    /// Clamped to the collision margin so glyphs never leave their reserved rect
+    pub fn with_visual_margin(mut self, value: u32) -> Self {
+        self.visual_margin = value;
+        self
+    }
+
+    /// Sets the extra space added around each word when searching for free space
+    pub fn with_collision_margin(mut self, value: u32) -> Self {
+        self.collision_margin = value as i32;
+        self
+    }
+
+    /// Gives each word a margin of `base + scale * frequency` pixels, where the frequency is
+    /// normalized so the most frequent word has 1.0. Frequent words get more room around them
+    /// and the long tail packs tightly
+    pub fn with_frequency_scaled_margin(mut self, base: u32, scale: f32) -> Self {
+        self.visual_margin = base;
+        self.collision_margin = base as i32;
+        self.frequency_margin_scale = scale;
+        self
+    }
+
+    /// Before a word shrinks below `value`, it's tried in the other orientation at its current
+    /// size first, so words stay readable instead of getting smaller right away. Words are
+    /// still shrunk toward `min_font_size` afterwards, and dropped below it
+    pub fn with_relocate_before_shrink_below(mut self, value: f32) -> Self {
+        self.relocate_threshold = Some(value);
+        self
+    }
+
+    /// Places each word at most `value` times when the tokenizer repeats words, so one word
+    /// can't fill the remaining space on its own. Unlimited by default
+    pub fn with_repeat_max_copies(mut self, value: usize) -> Self {
+        self.repeat_max_copies = Some(value);
+        self
+    }
+
+    /// Enlarges the words of sparse clouds: when the placed words cover little of the canvas,
+    /// placement is redone with a larger starting font size, searching for the largest one
+    /// that still places every word
+    pub fn with_auto_enlarge(mut self, value: bool) -> Self {
+        self.auto_enlarge = value;
+        self
+    }
+
+    /// Picks `value` random free positions for every word and keeps the one most surrounded by
+    /// words and the mask, for a tighter looking cloud. 1 by default, which places words at a
+    /// random free position
+    pub fn with_placement_candidates(mut self, value: usize) -> Self {
+        self.placement_candidates = value.max(1);
+        self
+    }
+
+    /// Keeps words inside `value` instead of filling the whole rectangle, for a rounded cloud
+    /// without a mask. Ignored when generating from a mask
+    pub fn with_shape_bias(mut self, value: ShapeBias) -> Self {
+        self.shape_bias = Some(value);
+        self
+    }
+
+    /// Keeps copies of the same word at least `value` pixels apart, measured between their
+    /// bounding boxes, so repeated words don't end up next to each other. Positions closer
+    /// than that are skipped and the search goes on. 0 by default
+    pub fn with_min_same_word_distance(mut self, value: f32) -> Self {
+        self.min_same_word_distance = value;
+        self
+    }
+
+    /// How much of a pixel a glyph has to cover, from 0.0 to 1.0, for the pixel to block other
+    /// words. Higher values let faint anti-aliased edges overlap and pack words tighter.
+    /// 0.05 by default
+    pub fn with_occupancy_threshold(mut self, value: f32) -> Self {
+        self.occupancy_threshold = value.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Only places repeated copies of words (see `ChineseTokenizer::with_repeat`) while the
+    /// largest empty rect of the canvas still holds the word at `min_font_size`, instead of
+    /// squeezing tiny copies into every sliver until `max_words` is reached
+    pub fn with_fill_empty_space(mut self, value: bool) -> Self {
+        self.fill_empty_space = value;
+        self
+    }
+
+    /// Sets how many spaces wide a tab in a word is (4 by default)
+    pub fn with_tab_width(mut self, value: usize) -> Self {
+        self.tab_width = value;
+        self
+    }
+
+    /// Sets what happens to characters the font has no glyph for, e.g. rare CJK characters or
+    /// emoji. Words that end up with no characters at all are drawn as nothing
+    pub fn with_missing_glyph_policy(mut self, value: MissingGlyphPolicy) -> Self {
+        self.missing_glyph_policy = value;
+        self
+    }
+
+    /// Adds `value` pixels of extra space between the letters of every word, scaled with the
+    /// image like the margins. Negative values tighten words, but letters never overlap
+    pub fn with_letter_spacing(mut self, value: f32) -> Self {
+        self.letter_spacing = value;
+        self
+    }
+
+    /// Aligns the lines of words that contain line breaks, e.g. pinned titles, to the left,
+    /// center or right of their longest line. Left by default
+    pub fn with_text_align(mut self, value: Align) -> Self {
+        self.text_align = value;
+        self
+    }
+
+    pub fn with_placement_order(mut self, value: PlacementOrder) -> Self {
+        self.placement_order = value;
+        self
+    }
+
+    pub fn with_size_strategy(mut self, value: SizeStrategy) -> Self {
+        self.size_strategy = value;
+        self
+    }
+
+    /// Sets the order words are drawn in without changing where they are placed
+    pub fn with_draw_order(mut self, value: DrawOrder) -> Self {
+        self.draw_order = value;
+        self
+    }
+
+    /// Renders the final image at exactly `width` by `height` pixels instead of `scale` times
+    /// the placement canvas. The layout is stretched to fit when the aspect ratios differ.
+    /// Rendering fails with `WordCloudError::InvalidConfig` if either is 0
+    pub fn with_output_dimensions(mut self, width: u32, height: u32) -> Self {
+        self.output_dimensions = Some((width, height));
+        self
+    }
+
+    /// Draws the final image `factor` times larger and averages it back down to its size, for
+    /// smoother edges on small words. Unlike `scale` the image doesn't get bigger. 1, the
+    /// default, turns it off. Images streamed in strips or tiles aren't supersampled
+    pub fn with_supersample(mut self, factor: u32) -> Self {
+        self.supersample = factor;
+        self
+    }
+
+    /// Sets the color transparent pixels are composited over when saving to a format without
+    /// alpha (JPEG, BMP). Defaults to the background color
+    pub fn with_flatten_color(mut self, value: Rgba<u8>) -> Self {
+        self.flatten_color = Some(value);
+        self
+    }
+
+    /// Sets the color space saved PNGs are tagged with, so viewers show the exact colors.
+    /// Defaults to sRGB
+    pub fn with_output_color_profile(mut self, value: ColorProfile) -> Self {
+        self.output_color_profile = value;
+        self
+    }
+
+    pub fn with_font_step(mut self, value: f32) -> Self {
+        self.font_step = value;
+        self
+    }
+
+    pub fn with_word_rotate_chance(mut self, value: f64) -> Self {
+        self.word_rotate_chance = value;
+        self
+    }
+    pub fn with_relative_font_scaling(mut self, value: f32) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&value),
+            "Relative scaling must be between 0 and 1"
+        );
+        self.relative_font_scaling = value;
+        self
+    }
+    pub fn with_rng_seed(mut self, value: u64) -> Self {
+        self.rng_seed.replace(value);
+        self
+    }
+
+    /// Same as `with_rng_seed` with a hash of `value`, e.g. a user name or an article slug. The
+    /// same string gives the same seed with every version and on every platform
+    pub fn with_seed_str(self, value: &str) -> Self {
+        self.with_rng_seed(fnv1a(FNV_OFFSET, value.as_bytes()))
+    }
+
+    /// Without an `rng_seed`, derives the seed from the words, their frequencies and the
+    /// settings, so the same text always gives the same word cloud and different texts differ
+    pub fn with_deterministic_seed(mut self, value: bool) -> Self {
+        self.deterministic_seed = value;
+        self
+    }
+
+    /// Runs placement on a canvas downscaled by `value` (e.g. 0.25) and maps the
+    /// positions back up for the final render. Much faster, but packing is looser;
+    /// final quality requires a preview scale of 1.0
+    pub fn with_preview_scale(mut self, value: f32) -> Self {
+        assert!(
+            value > 0.0 && value <= 1.0,
+            "Preview scale must be greater than 0 and at most 1"
+        );
+        self.preview_scale = value;
+        self
+    }
+
+    /// Sets the color function used by `generate_from_text`, e.g. `random_hsl_color(..)`
+    pub fn with_color_closure(
+        mut self,
+        value: impl Fn(&Word, &mut WyRand) -> Rgba<u8> + Send + Sync + 'static,
+    ) -> Self {
+        self.color_func = Some(Box::new(value));
+        self
+    }
+
+    /// Sets how words are colored when no color closure is set
+    pub fn with_color_strategy(mut self, value: ColorStrategy) -> Self {
+        self.color_strategy = value;
+        self
+    }
+
+    /// Draws the words in `value` in exactly their color, e.g. a brand name in the brand
+    /// color, also when a color closure is passed to e.g. `render_layout`. Other words get
+    /// their color from the color closure as usual. Matched against `Word::text`, so with the
+    /// default case-insensitive tokenizer use the casing the word is displayed with
+    pub fn with_word_colors(mut self, value: HashMap<String, Rgba<u8>>) -> Self {
+        self.word_colors = value;
+        self
+    }
+
+    pub fn with_drop_policy(mut self, value: DropPolicy) -> Self {
+        self.drop_policy = value;
+        self
+    }
+
+    /// Draws a shadow offset by (`offset_x`, `offset_y`) behind every word, blurred with a
+    /// gaussian of sigma `blur`. Words reserve room for their shadow, blur included, and later
+    /// words are kept off it
+    pub fn with_text_shadow(
+        mut self,
+        offset_x: i32,
+        offset_y: i32,
+        blur: f32,
+        color: Rgba<u8>,
+    ) -> Self {
+        self.text_shadow = Some(TextShadow {
+            offset_x,
+            offset_y,
+            blur: blur.max(0.0),
+            color,
+        });
+        self
+    }
+
+    /// Treats white (instead of black) mask pixels as the space words can be placed in
+    pub fn with_mask_invert(mut self, value: bool) -> Self {
+        self.mask_invert = value;
+        self
+    }
+
+    /// Keeps words at least `value` pixels inside the edge of the mask by shrinking the space
+    /// of the mask inward along its outline, so words don't look like they spill out of it
+    pub fn with_mask_edge_margin(mut self, value: u32) -> Self {
+        self.mask_edge_margin = value;
+        self
+    }
+
+    /// Counts mask pixels up to `value` as black, so the dark gray edges of an anti-aliased or
+    /// compressed mask are available for words too. 0 by default, only pure black is available
+    pub fn with_mask_threshold(mut self, value: u8) -> Self {
+        self.mask_threshold = value;
+        self
+    }
+
+    /// For masks made of several separate shapes, shares the words out between the shapes by
+    /// their area, instead of letting the largest words go wherever they fit first. Each word
+    /// goes to the shape that is least filled for its size when there is room there
+    pub fn with_balance_across_regions(mut self, value: bool) -> Self {
+        self.balance_across_regions = value;
+        self
+    }
+
+    /// Leaves out every drawn pixel outside the available space of the mask or shape, so no
+    /// ink spills over its outline, at the cost of cutting off the odd glyph edge. Shadows and
+    /// outlines are clipped too. Does nothing without a mask or `with_shape_bias`
+    pub fn with_pixel_clip_to_mask(mut self, value: bool) -> Self {
+        self.pixel_clip_to_mask = value;
+        self
+    }
+
+    /// Keeps words out of the non-black pixels of `value`, e.g. where a watermark goes. Works
+    /// with both `FromDimensions` and `FromMask`, and is stretched to the canvas if the sizes differ
+    pub fn with_exclusion_mask(mut self, value: GrayImage) -> Self {
+        self.exclusion_mask = Some(value);
+        self
+    }
+
+    /// Decides per word whether it's rotated on the first placement attempt, instead of
+    /// `word_rotate_chance`. Words that don't fit are still tried rotated afterwards
+    pub fn with_rotation_decider(
+        mut self,
+        value: impl Fn(&str, f32) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.rotation_decider = Some(Box::new(value));
+        self
+    }
+
+    /// Rotates words by one of `min_deg, min_deg + step_deg, ..., max_deg` degrees
+    /// counterclockwise, picked at random, instead of always 90. Negative angles turn clockwise
+    pub fn with_rotation_range(mut self, min_deg: f32, max_deg: f32, step_deg: f32) -> Self {
+        self.rotation_range = Some((min_deg, max_deg, step_deg));
+        self
+    }
+
+    /// Turns each rotated word either 90 degrees counterclockwise or 90 degrees clockwise,
+    /// picked at random, instead of always counterclockwise. Has no effect when
+    /// `with_rotation_range` is set
+    pub fn with_bidirectional_rotation(mut self, value: bool) -> Self {
+        self.bidirectional_rotation = value;
+        self
+    }
+
+    /// Only lets words be placed where `value` returns true, e.g. to keep a word in the top
+    /// half of the canvas or out of a region. Called for every free position that is found
+    pub fn with_placement_filter(
+        mut self,
+        value: impl Fn(&str, &Point, &Rect) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.placement_filter = Some(Box::new(value));
+        self
+    }
+
+    pub fn with_glyph_style(mut self, value: GlyphStyle) -> Self {
+        self.glyph_style = value;
+        self
+    }
+
+    /// Multiplies the alpha of every word's color, `with_path_word` words included, and of its
+    /// shadow, by `value` (0.0 - 1.0), e.g. 0.6 to lay a faint cloud over other content. 1.0 by
+    /// default
+    pub fn with_global_alpha(mut self, value: f32) -> Self {
+        self.global_alpha = value.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Limits the memory a canvas may take, at 4 bytes per pixel (1 GiB by default). Larger
+    /// canvases fail with `WordCloudError::CanvasTooLarge` instead of being allocated
+    pub fn with_max_canvas_bytes(mut self, value: usize) -> Self {
+        self.max_canvas_bytes = value;
+        self
+    }
+
+    /// Applies `value` to every generation, failing with `WordCloudError::LimitExceeded`
+    /// instead of allocating or working without bound
+    pub fn with_resource_limits(mut self, value: ResourceLimits) -> Self {
+        self.resource_limits = value;
+        self
+    }
+
+    /// Calls `value` during placement with the fraction (0.0 - 1.0) of words that were placed
+    /// or dropped so far, about every percent and once more with 1.0 at the end, e.g. for a
+    /// progress bar. With `with_auto_enlarge` it starts over for every start size that is tried
+    pub fn with_progress(mut self, value: impl FnMut(f32) + Send + 'static) -> Self {
+        self.progress = Some(Mutex::new(Box::new(value)));
+        self
+    }
+
+    fn report_progress(&self, fraction: f32) {
+        if let Some(Ok(mut progress)) = self.progress.as_ref().map(Mutex::lock) {
+            progress(fraction);
+        }
+    }
+
+    /// Draws `value` along its path. The other words are placed around it
+    pub fn with_path_word(mut self, value: PathWord) -> Self {
+        self.path_words.push(value);
+        self
+    }
+
+    pub fn with_font_from_path(mut self, path: impl Into<PathBuf>) -> Self {
+        let font_file = fs::read(path.into()).expect("Unable to read font file");
+
+        self.font = FontVec::try_from_vec(font_file).expect("Font file may be invalid");
+        self.font_index = 0;
+        self.apply_font_variations();
+
+        self
+    }
+
+    /// Loads the face at `index` of a TrueType collection (`.ttc`), e.g. the bold face. Index 0
+    /// is the only face of a plain font file. Panics if the file can't be read or has no such
+    /// face, see `font_from_collection` and `WordCloudBuilder::font_from_path_indexed`
+    pub fn with_font_from_path_indexed(self, path: impl Into<PathBuf>, index: u32) -> Self {
+        let font_file = fs::read(path.into()).expect("Unable to read font file");
+        let font = font_from_collection(font_file, index).unwrap_or_else(|err| panic!("{err}"));
+
+        self.with_collection_font(font, index)
+    }
+
+    /// 换成字体集里的第 `index` 个字体
+    pub(crate) fn with_collection_font(mut self, font: FontVec, index: u32) -> Self {
+        self.font = font;
+        self.font_index = index;
+        self.apply_font_variations();
+        self
+    }
+
+    /// Panics if the scaled canvas is too large, see `try_render_layout`
+    pub fn render_layout(
+        &self,
+        layout: &Layout,
+        scale: f32,
+        color_func: impl FnMut(&Word, &mut WyRand) -> Rgba<u8>,
+    ) -> RgbaImage {
+        self.try_render_layout(layout, scale, color_func)
+            .expect("Unable to render word cloud")
+    }
+
+    /// Draws a layout onto a new canvas of `layout.width * scale` by `layout.height * scale`,
+    /// or of exactly the size set with `with_output_dimensions`
+    pub fn try_render_layout(
+        &self,
+        layout: &Layout,
+        scale: f32,
+        color_func: impl FnMut(&Word, &mut WyRand) -> Rgba<u8>,
+    ) -> Result<RgbaImage, WordCloudError> {
+        let scaled = self.scale_layout(layout, scale, color_func)?;
+
+        self.render_scaled_layout(&scaled)
+    }
+
+    /// Draws one word on its own, e.g. to check that the font has glyphs for it. Returns a
+    /// transparent image cropped to the drawn pixels, and its width and height
+    pub fn render_word(
+        &self,
+        text: &str,
+        font_size: f32,
+        rotated: bool,
+        color: Rgba<u8>,
+    ) -> (RgbaImage, u32, u32) {
+        if text.is_empty() {
+            return (RgbaImage::new(0, 0), 0, 0);
+        }
+
+        let glyphs = text::text_to_glyphs(
+            text,
+            &self.font,
+            PxScale::from(font_size),
+            self.text_options(),
+        );
+        let angle = if rotated { 90.0 } else { 0.0 };
+        let (width, height) = text::rotated_dimensions(glyphs.width, glyphs.height, angle);
+        let mut image = RgbaImage::new(width, height);
+        text::draw_glyphs_to_rgba_buffer(
+            &mut image,
+            &mut text::DrawContext::new(self.font_index),
+            &glyphs,
+            &self.font,
+            point(0.0, 0.0),
+            angle,
+            color,
+        );
+
+        // 裁掉四周透明的部分
+        let mut bounds: Option<(u32, u32, u32, u32)> = None;
+        for (x, y, px) in image.enumerate_pixels() {
+            if px.0[3] == 0 {
+                continue;
+            }
+            bounds = Some(match bounds {
+                Some((min_x, min_y, max_x, max_y)) => {
+                    (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+                }
+                None => (x, y, x, y),
+            });
+        }
+        let Some((min_x, min_y, max_x, max_y)) = bounds else {
+            return (RgbaImage::new(0, 0), 0, 0);
+        };
+
+        let (width, height) = (max_x - min_x + 1, max_y - min_y + 1);
+        let cropped = image::imageops::crop_imm(&image, min_x, min_y, width, height).to_image();
+        (cropped, width, height)
+    }
+
+    pub(crate) fn path_word_glyphs(
+        &self,
+        path_word: &PathWord,
+        scale_x: f32,
+        scale_y: f32,
+    ) -> Vec<text::PathGlyph> {
+        text::text_to_path_glyphs(
+            &path_word.text,
+            &self.font,
+            PxScale {
+                x: path_word.font_size * scale_x,
+                y: path_word.font_size * scale_y,
+            },
+            &path_word.path.to_polyline(scale_x, scale_y),
+            self.scaled_text_options(scale_x),
+        )
+    }
+
+    /// `width * scale` by `height * scale`, if that fits in `u32`
+    pub(crate) fn scaled_dimensions(
+        &self,
+        width: u32,
+        height: u32,
+        scale: f32,
+    ) -> Result<(u32, u32), WordCloudError> {
+        let scaled_width = (width as f64 * scale as f64).floor();
+        let scaled_height = (height as f64 * scale as f64).floor();
+
+        // NaN 也会走到这里
+        if !(scaled_width <= u32::MAX as f64 && scaled_height <= u32::MAX as f64) {
+            return Err(WordCloudError::CanvasTooLarge {
+                width: scaled_width,
+                height: scaled_height,
+            });
+        }
+
+        Ok((scaled_width as u32, scaled_height as u32))
+    }
+
+    /// `width * scale` by `height * scale`, if that fits in `u32` and in `max_canvas_bytes`
+    pub(crate) fn canvas_dimensions(
+        &self,
+        width: u32,
+        height: u32,
+        scale: f32,
+    ) -> Result<(u32, u32), WordCloudError> {
+        let (scaled_width, scaled_height) = self.scaled_dimensions(width, height, scale)?;
+        let too_large = WordCloudError::CanvasTooLarge {
+            width: scaled_width as f64,
+            height: scaled_height as f64,
+        };
+
+        let bytes = scaled_width as u128 * scaled_height as u128 * 4;
+        if self
+            .resource_limits
+            .max_canvas_bytes
+            .is_some_and(|max| bytes > max as u128)
+        {
+            return Err(WordCloudError::LimitExceeded {
+                which: Limit::CanvasBytes,
+            });
+        }
+        if bytes > self.max_canvas_bytes as u128 {
+            return Err(too_large);
+        }
+
+        Ok((scaled_width, scaled_height))
+    }
+
+    /// The color `generate_from_text` gives a word
+    pub(crate) fn word_color(&self, word: &Word, rng: &mut WyRand) -> Rgba<u8> {
+        if let Some(color) = self.word_colors.get(word.text) {
+            return *color;
+        }
+
+        match &self.color_func {
+            Some(color_func) => color_func(word, rng),
+            None => self.color_strategy.color(word, rng),
+        }
+    }
+
+    /// Panics if words were dropped under `DropPolicy::Error`, see `try_generate_from_text`
+    pub fn generate_from_text(&self, text: &str, size: WordCloudSize, scale: f32) -> RgbaImage {
+        self.try_generate_from_text(text, size, scale)
+            .expect("Unable to generate word cloud")
+    }
+
+    /// Generates a word cloud as `(pixels, width, height)` for FFI callers that don't use the
+    /// `image` crate. The pixels are 8-bit RGBA in row-major order, tightly packed with no
+    /// padding between rows, so the row stride is `width * 4` bytes and the length is
+    /// `width * height * 4`. Panics like `generate_from_text`
+    pub fn generate_raw(&self, text: &str, size: WordCloudSize, scale: f32) -> (Vec<u8>, u32, u32) {
+        let image = self.generate_from_text(text, size, scale);
+        let (width, height) = image.dimensions();
+
+        (image.into_raw(), width, height)
+    }
+
+    pub fn try_generate_from_text(
+        &self,
+        text: &str,
+        size: WordCloudSize,
+        scale: f32,
+    ) -> Result<RgbaImage, WordCloudError> {
+        self.try_generate_from_text_with_color_func(text, size, scale, |word, rng| {
+            self.word_color(word, rng)
+        })
+    }
+
+    /// Panics if words were dropped under `DropPolicy::Error`, see `try_generate_from_text_with_color_func`
+    pub fn generate_from_text_with_color_func(
+        &self,
+        text: &str,
+        size: WordCloudSize,
+        scale: f32,
+        color_func: impl FnMut(&Word, &mut WyRand) -> Rgba<u8>,
+    ) -> RgbaImage {
+        self.try_generate_from_text_with_color_func(text, size, scale, color_func)
+            .expect("Unable to generate word cloud")
+    }
+
+    pub fn try_generate_from_text_with_color_func(
+        &self,
+        text: &str,
+        size: WordCloudSize,
+        scale: f32,
+        color_func: impl FnMut(&Word, &mut WyRand) -> Rgba<u8>,
+    ) -> Result<RgbaImage, WordCloudError> {
+        self.try_generate(text, size, scale, color_func)
+            .map(|result| result.image)
+    }
+
+    /// Panics if words were dropped under `DropPolicy::Error`, see `try_generate_from_text_with_rng`
+    pub fn generate_from_text_with_rng(
+        &self,
+        text: &str,
+        size: WordCloudSize,
+        scale: f32,
+        rng: &mut WyRand,
+        color_func: impl FnMut(&Word, &mut WyRand) -> Rgba<u8>,
+    ) -> RgbaImage {
+        self.try_generate_from_text_with_rng(text, size, scale, rng, color_func)
+            .expect("Unable to generate word cloud")
+    }
+
+    /// Like `try_generate_from_text_with_color_func`, with the random numbers taken from `rng`
+    /// instead of from `rng_seed`. Placement starts from `rng` as it is, `color_func` gets it
+    /// where placement left off and `rng` is left where the colors left off
+    pub fn try_generate_from_text_with_rng(
+        &self,
+        text: &str,
+        size: WordCloudSize,
+        scale: f32,
+        rng: &mut WyRand,
+        mut color_func: impl FnMut(&Word, &mut WyRand) -> Rgba<u8>,
+    ) -> Result<RgbaImage, WordCloudError> {
+        let layout = self.generate_layout_from_text_with_rng(text, size, rng)?;
+
+        self.try_render_layout(&layout, scale, |word, _| color_func(word, rng))
+    }
+
+    /// Places the words of `text` with the random numbers taken from `rng` instead of from
+    /// `rng_seed`, see `generate_from_text_with_rng`. `rng` is left where placement left off,
+    /// the same state as `Layout::rng`
+    pub fn generate_layout_from_text_with_rng<'a>(
+        &'a self,
+        text: &'a str,
+        size: WordCloudSize,
+        rng: &mut WyRand,
+    ) -> Result<Layout<'a>, WordCloudError> {
+        let words = self.tokenizer.get_normalized_word_frequencies(text);
+
+        self.generate_layout_from_words_with_rng(words, size, rng)
+    }
+
+    /// Places the words of `text` without drawing them, see `render_layout`
+    pub fn generate_layout_from_text<'a>(
+        &'a self,
+        text: &'a str,
+        size: WordCloudSize,
+    ) -> Result<Layout<'a>, WordCloudError> {
+        let words = self.tokenizer.get_normalized_word_frequencies(text);
+
+        self.generate_layout_from_words(words, size)
+    }
+
+    /// Generates a word cloud from word counts, e.g. from `ChineseTokenizer::get_word_frequencies_from_reader`.
+    /// Panics if words were dropped under `DropPolicy::Error`, see `generate_layout_from_frequencies`
+    pub fn generate_from_frequencies(
+        &self,
+        frequencies: &HashMap<String, usize>,
+        size: WordCloudSize,
+        scale: f32,
+    ) -> RgbaImage {
+        let layout = self
+            .generate_layout_from_frequencies(frequencies, size)
+            .expect("Unable to generate word cloud");
+
+        self.render_layout(&layout, scale, |word, rng| self.word_color(word, rng))
+    }
+
+    /// Places words from their counts without drawing them, see `render_layout`
+    pub fn generate_layout_from_frequencies<'a>(
+        &'a self,
+        frequencies: &'a HashMap<String, usize>,
+        size: WordCloudSize,
+    ) -> Result<Layout<'a>, WordCloudError> {
+        let frequencies = frequencies
+            .iter()
+            .map(|(word, count)| (word.as_str(), *count))
+            .collect();
+        let words = self.tokenizer.normalize_frequencies(frequencies);
+
+        self.generate_layout_from_words(words, size)
+    }
+
+    /// Generates a word cloud from counts merged from several texts, see `FrequencyMap`.
+    /// Panics if words were dropped under `DropPolicy::Error`, see `generate_layout_from_frequency_map`
+    pub fn generate_from_frequency_map(
+        &self,
+        frequencies: &FrequencyMap,
+        size: WordCloudSize,
+        scale: f32,
+    ) -> RgbaImage {
+        let layout = self
+            .generate_layout_from_frequency_map(frequencies, size)
+            .expect("Unable to generate word cloud");
+
+        self.render_layout(&layout, scale, |word, rng| self.word_color(word, rng))
+    }
+
+    /// Places words from merged counts without drawing them, see `render_layout`. With
+    /// `FrequencyMap::with_consistent_word_case_across_corpus` each word is shown in the casing
+    /// the tokenizer's `with_casing_preference` picks
+    pub fn generate_layout_from_frequency_map<'a>(
+        &'a self,
+        frequencies: &'a FrequencyMap,
+        size: WordCloudSize,
+    ) -> Result<Layout<'a>, WordCloudError> {
+        let words = self.tokenizer.normalize_weighted_frequencies(
+            frequencies
+                .cased_counts(self.tokenizer.casing_preference)
+                .into_iter()
+                .collect(),
+        );
+
+        self.generate_layout_from_words(words, size)
+    }
+
+    /// 按 `rng_seed` 或 `deterministic_seed` 建 rng 再放词
+    pub(crate) fn generate_layout_from_words<'a>(
+        &'a self,
+        words: Vec<(&'a str, f32)>,
+        size: WordCloudSize,
+    ) -> Result<Layout<'a>, WordCloudError> {
+        let mut rng = self.placement_rng(&words, &size);
+
+        self.generate_layout_from_words_with_rng(words, size, &mut rng)
+    }
+
+    /// 放词从 `rng` 当前的状态开始，放完以后 `rng` 停在 `Layout::rng` 的状态
+    fn generate_layout_from_words_with_rng<'a>(
+        &'a self,
+        words: Vec<(&'a str, f32)>,
+        size: WordCloudSize,
+        rng: &mut WyRand,
+    ) -> Result<Layout<'a>, WordCloudError> {
+        let started = Instant::now();
+        if self
+            .resource_limits
+            .max_words
+            .is_some_and(|max| words.len() > max)
+        {
+            return Err(WordCloudError::LimitExceeded {
+                which: Limit::Words,
+            });
+        }
+
+        // 遮罩和形状都有画布上放不了字的地方
+        let has_mask =
+            !matches!(size, WordCloudSize::FromDimensions { .. }) || self.shape_bias.is_some();
+        let gray_buffer = match size {
+            WordCloudSize::FromDimensions { width, height } => {
+                // 求和面积表每个像素占 4 个字节，和最终画布一样
+                self.canvas_dimensions(width, height, 1.0)?;
+                match self.shape_bias {
+                    Some(shape) => mask::shape_mask(shape, width, height),
+                    None => GrayImage::from_pixel(width, height, Luma([0])),
+                }
+            }
+            WordCloudSize::FromMask(image) => self.prepare_mask(image)?,
+            WordCloudSize::FromMaskScaled {
+                mask,
+                max_dimension,
+            } => {
+                if max_dimension == 0 {
+                    return Err(WordCloudError::InvalidConfig(
+                        "max_dimension must be greater than 0".to_string(),
+                    ));
+                }
+                // 先按缩放以后的大小检查，太大的不用真的缩放
+                let (width, height) = mask::fit_dimensions(mask.dimensions(), max_dimension);
+                self.canvas_dimensions(width, height, 1.0)?;
+                self.prepare_mask(mask::scale_to_fit(&mask, max_dimension))?
+            }
+        };
+        let (width, height) = gray_buffer.dimensions();
+        let clip_mask = (self.pixel_clip_to_mask && has_mask).then(|| gray_buffer.clone());
+
+        let gray_buffer = if self.preview_scale < 1.0 {
+            image::imageops::resize(
+                &gray_buffer,
+                ((width as f32 * self.preview_scale) as u32).max(1),
+                ((height as f32 * self.preview_scale) as u32).max(1),
+                FilterType::Nearest,
+            )
+        } else {
+            gray_buffer
+        };
+
+        let mut gray_buffer = gray_buffer;
+        if let Some(exclusion_mask) = &self.exclusion_mask {
+            let resized;
+            let exclusion_mask = if exclusion_mask.dimensions() == gray_buffer.dimensions() {
+                exclusion_mask
+            } else {
+                resized = image::imageops::resize(
+                    exclusion_mask,
+                    gray_buffer.width(),
+                    gray_buffer.height(),
+                    FilterType::Nearest,
+                );
+                &resized
+            };
+            for (px, excluded) in gray_buffer.pixels_mut().zip(exclusion_mask.pixels()) {
+                if excluded.0[0] != 0 && px.0[0] == 0 {
+                    *px = Luma([1]);
+                }
+            }
+        }
+
+        // 先把沿路径的词占住，加上间距
+        let margin = (self.collision_margin.max(0) as f32 * self.preview_scale).ceil() as i64;
+        for path_word in &self.path_words {
+            let glyphs = self.path_word_glyphs(path_word, self.preview_scale, self.preview_scale);
+            text::for_each_path_glyph_pixel(&glyphs, &self.font, |x, y, v| {
+                if v < self.occupancy_threshold {
+                    return;
+                }
+                for ny in y - margin..=y + margin {
+                    for nx in x - margin..=x + margin {
+                        if nx < 0 || ny < 0 {
+                            continue;
+                        }
+                        if let Some(px) = gray_buffer.get_pixel_mut_checked(nx as u32, ny as u32) {
+                            *px = Luma([1]);
+                        }
+                    }
+                }
+            });
+        }
+
+        let mut layout = if self.auto_enlarge {
+            self.place_words_enlarged(&words, gray_buffer, (width, height), has_mask, started, rng)?
+        } else {
+            self.place_words(
+                &words,
+                gray_buffer,
+                (width, height),
+                has_mask,
+                started,
+                1.0,
+                rng.clone(),
+            )?
+        };
+        layout.clip_mask = clip_mask;
+        *rng = layout.rng.clone();
+
+        Ok(layout)
+    }
+
+    /// 词太少、画布很空时二分放大起始字号，留下没有丢词并且占得最满的结果
+    fn place_words_enlarged<'a>(
+        &'a self,
+        words: &[(&'a str, f32)],
+        gray_buffer: GrayImage,
+        (width, height): (u32, u32),
+        has_mask: bool,
+        started: Instant,
+        rng: &WyRand,
+    ) -> Result<Layout<'a>, WordCloudError> {
+        const MAX_START_SCALE: f32 = 4.0;
+        const SEARCH_STEPS: usize = 6;
+        // 外框已经占了可用面积的这么多就不放大了
+        const ENOUGH_FILL: f32 = 0.6;
+
+        let available_pixels = gray_buffer.as_raw().iter().filter(|p| **p == 0).count();
+        let available_fraction = available_pixels as f32 / gray_buffer.len().max(1) as f32;
+        let fill = |layout: &Layout| {
+            score::fill_ratio(layout, width, height) / available_fraction.max(f32::EPSILON)
+        };
+
+        let mut best = self.place_words(
+            words,
+            gray_buffer.clone(),
+            (width, height),
+            has_mask,
+            started,
+            1.0,
+            rng.clone(),
+        )?;
+        if !best.dropped.is_empty() || fill(&best) >= ENOUGH_FILL {
+            return Ok(best);
+        }
+
+        let (mut low, mut high) = (1.0, MAX_START_SCALE);
+        for _ in 0..SEARCH_STEPS {
+            let start_scale = (low + high) / 2.0;
+            match self.place_words(
+                words,
+                gray_buffer.clone(),
+                (width, height),
+                has_mask,
+                started,
+                start_scale,
+                rng.clone(),
+            ) {
+                Ok(layout) if layout.dropped.is_empty() => {
+                    low = start_scale;
+                    if fill(&layout) > fill(&best) {
+                        best = layout;
+                    }
+                }
+                Ok(_) | Err(WordCloudError::WordsDropped(_)) => high = start_scale,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(best)
+    }
+
+    /// 在准备好的占位图上放词，起始字号乘上 `start_scale`，从 `rng` 的状态开始
+    #[allow(clippy::too_many_arguments)]
+    fn place_words<'a>(
+        &'a self,
+        words: &[(&'a str, f32)],
+        gray_buffer: GrayImage,
+        (width, height): (u32, u32),
+        has_mask: bool,
+        started: Instant,
+        start_scale: f32,
+        mut rng: WyRand,
+    ) -> Result<Layout<'a>, WordCloudError> {
+        let mut summed_area_table = gray_buffer
+            .as_raw()
+            .iter()
+            .map(|e| *e as u32)
+            .collect::<Vec<_>>();
+        sat::to_summed_area_table(&mut summed_area_table, gray_buffer.width() as usize, 0);
+
+        let mut final_words = Vec::with_capacity(words.len());
+        let mut dropped_words = vec![];
+        let mut last_freq = 1.0;
+        let skip_list = if has_mask {
+            Some(create_mask_skip_list(&gray_buffer))
+        } else {
+            None
+        };
+        // 只有一块能放字的区域时不用分
+        let regions = (self.balance_across_regions && has_mask)
+            .then(|| mask::label_regions(&gray_buffer))
+            .filter(|regions| regions.areas.len() > 1);
+        let mut region_fill = vec![0; regions.as_ref().map_or(0, |regions| regions.areas.len())];
+        // 多个候选时要比较周围有多少词，遮罩不算
+        let ink_table = (self.placement_candidates > 1).then(|| vec![0; summed_area_table.len()]);
+        let mut canvas = Canvas {
+            background: gray_buffer.clone(),
+            gray_buffer,
+            skip_list,
+            summed_area_table,
+            ink_table,
+            regions,
+            draw_context: text::DrawContext::new(self.font_index),
+        };
+
+        // 用最小字号横竖都放不进画布的词一定放不下，`DropPolicy::Error` 时报错，不然丢掉，
+        // 起始字号从下一个词算。不然生成的图里少了最重要的词
+        let mut words = words;
+        while let Some(first_word) = words
+            .first()
+            .filter(|word| !self.fits_canvas_at_min_size(word, &canvas))
+        {
+            if self.drop_policy == DropPolicy::Error {
+                return Err(WordCloudError::WordTooLarge(first_word.0.to_string()));
+            }
+            dropped_words.push(first_word.0.to_string());
+            words = &words[1..];
+        }
+
+        // 没有词时（比如权重全是 0）是一张空图
+        let Some(first_word) = words.first() else {
+            return Ok(Layout {
+                width,
+                height,
+                words: final_words,
+                dropped: dropped_words,
+                rng,
+                canvas,
+                clip_mask: None,
+            });
+        };
+        // First, we determine an appropriate font size to start with based on the height of the canvas.
+        // Rasterizing the first word in the sorted list at a font size of 55% (or
+        // `first_word_height_fraction`) the canvas height produces a bounding rectangle we can use
+        // as a heuristic
+        let mut font_size = {
+            let fraction_height = canvas.gray_buffer.height() as f32
+                * self
+                    .first_word_height_fraction
+                    .unwrap_or(DEFAULT_FIRST_WORD_HEIGHT_FRACTION);
+            let rect_at_image_height =
+                self.text_dimensions_at_font_size(first_word.0, PxScale::from(fraction_height));
+
+            let height_ration =
+                rect_at_image_height.height as f32 / rect_at_image_height.width as f32;
+
+            // 设置了 `first_word_height_fraction` 时不超过画布高的这个比例，太宽的词按画布的宽缩小
+            let capped = |size: f32| match self.first_word_height_fraction {
+                Some(_) => size.min(fraction_height),
+                None => size,
+            };
+            let (canvas_width, canvas_height) = canvas.gray_buffer.dimensions();
+            let mut start_height = capped(canvas_width as f32 * height_ration);
+            // 横着连最小字号都放不下时按竖着放算，词的宽对着画布的高
+            if start_height < self.min_font_size * self.preview_scale {
+                start_height = start_height
+                    .max(capped(canvas_height as f32 * height_ration).min(canvas_width as f32));
+            }
+
+            // 按遮罩上能放字的面积缩小
+            if has_mask {
+                let black_pixels = canvas
+                    .gray_buffer
+                    .as_raw()
+                    .iter()
+                    .filter(|p| **p == 0)
+                    .count();
+                let available_space = black_pixels as f32 / canvas.gray_buffer.len() as f32;
+                start_height *= available_space;
+            }
+
+            start_height * start_scale
+        };
+
+        let max_font_size = [
+            self.max_font_size.map(|size| size * self.preview_scale),
+            self.max_font_size_ratio
+                .map(|ratio| ratio * canvas.gray_buffer.height() as f32),
+        ]
+        .into_iter()
+        .flatten()
+        .reduce(f32::min);
+
+        let recurrence = FontSizeRecurrence {
+            start_font_size: match self.placement_order {
+                PlacementOrder::LargestFirst => font_size,
+                PlacementOrder::SmallestFirst => {
+                    self.smallest_first_start_size(words, &canvas) * start_scale
+                }
+            },
+            first_freq: first_word.1,
+            // 每个词的外框都要凑到的面积：最常见的词在起始字号时的大小
+            target_area: (self.size_strategy == SizeStrategy::EqualArea).then(|| {
+                let rect =
+                    self.text_dimensions_at_font_size(first_word.0, PxScale::from(font_size));
+                rect.width as f32 * rect.height as f32
+            }),
+            max_font_size,
+            max_equal_area_size: canvas.gray_buffer.height() as f32,
+        };
+        let order: Vec<usize> = match self.placement_order {
+            PlacementOrder::LargestFirst => (0..words.len()).collect(),
+            PlacementOrder::SmallestFirst => (0..words.len()).rev().collect(),
+        };
+        let mut placed_copies = HashMap::<&str, usize>::new();
+        // 每个词已经放下的外框，`min_same_word_distance` 要用
+        let mut placed_boxes = HashMap::<&str, Vec<(Point, (u32, u32))>>::new();
+        let mut attempted = HashSet::new();
+        // 大约每 1% 报告一次进度
+        let progress_step = (order.len() / 100).max(1);
+        for (n, i) in order.into_iter().enumerate() {
+            if n % progress_step == 0 {
+                self.report_progress(n as f32 / words.len() as f32);
+            }
+            let (word, freq) = &words[i];
+            if self
+                .resource_limits
+                .max_generation_time
+                .is_some_and(|max| started.elapsed() > max)
+            {
+                return Err(WordCloudError::LimitExceeded {
+                    which: Limit::GenerationTime,
+                });
+            }
+
+            let repeated = !attempted.insert(*word);
+            if self.fill_empty_space && repeated && !self.fits_empty_space(word, &canvas) {
+                break;
+            }
+            if self
+                .repeat_max_copies
+                .is_some_and(|max_copies| placed_copies.get(word).is_some_and(|n| *n >= max_copies))
+            {
+                continue;
+            }
+
+            font_size = self.next_font_size(&recurrence, (word, *freq), font_size, last_freq);
+
+            if font_size < self.min_font_size * self.preview_scale {
+                // 字号不随词频变小时，后面的词不一定也太小
+                if self.placement_order == PlacementOrder::SmallestFirst
+                    || recurrence.target_area.is_some()
+                {
+                    dropped_words.push(word.to_string());
+                    continue;
+                }
+                dropped_words.extend(words[i..].iter().map(|(word, _)| word.to_string()));
+                break;
+            }
+
+            let (pos, glyphs, angle) = match self.place_word(
+                word,
+                *freq,
+                font_size,
+                &canvas,
+                placed_boxes.get(word).map_or(&[], Vec::as_slice),
+                canvas
+                    .regions
+                    .as_ref()
+                    .map(|regions| regions.least_filled(&region_fill)),
+                &mut rng,
+            ) {
+                Ok((pos, glyphs, angle, new_font_size)) => {
+                    font_size = new_font_size;
+                    (pos, glyphs, angle)
+                }
+                Err(new_font_size) => {
+                    font_size = new_font_size;
+                    dropped_words.push(word.to_string());
+                    continue;
+                }
+            };
+
+            let font = self.font_for_frequency(*freq);
+            self.mark_word(&mut canvas, &glyphs, font, pos, angle);
+
+            final_words.push(Word {
+                text: word,
+                font,
+                font_size: PxScale::from(font_size),
+                glyphs: glyphs.clone(),
+                rotated: angle != 0.0,
+                angle,
+                position: pos,
+                frequency: *freq,
+                index: final_words.len(),
+            });
+
+            canvas.update_summed_area_table(pos);
+            if let Some(regions) = &canvas.regions {
+                // 按外框中心所在的区域记面积
+                let (width, height) = text::rotated_dimensions(glyphs.width, glyphs.height, angle);
+                let center = (
+                    (pos.x + width as f32 / 2.0).max(0.0) as u32,
+                    (pos.y + height as f32 / 2.0).max(0.0) as u32,
+                );
+                if let Some(label) = regions.label_at(center.0, center.1) {
+                    region_fill[label] += (width * height) as usize;
+                }
+            }
+            *placed_copies.entry(word).or_insert(0) += 1;
+            if self.min_same_word_distance > 0.0 {
+                let size = text::rotated_dimensions(glyphs.width, glyphs.height, angle);
+                placed_boxes.entry(word).or_default().push((pos, size));
+            }
+
+            last_freq = *freq;
+        }
+
+        self.report_progress(1.0);
+
+        if self.drop_policy == DropPolicy::Error && !dropped_words.is_empty() {
+            return Err(WordCloudError::WordsDropped(dropped_words));
+        }
+
+        // 预览模式下把位置映射回原画布
+        if self.preview_scale < 1.0 {
+            for word in &mut final_words {
+                let font_size = PxScale::from(word.font_size.y / self.preview_scale);
+                word.glyphs =
+                    text::text_to_glyphs(word.text, word.font, font_size, self.text_options());
+                word.font_size = font_size;
+                word.position = point(
+                    word.position.x / self.preview_scale,
+                    word.position.y / self.preview_scale,
+                );
+            }
+        }
+
+        Ok(Layout {
+            width,
+            height,
+            words: final_words,
+            dropped: dropped_words,
+            rng,
+            canvas,
+            clip_mask: None,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn place_word(
+        &self,
+        word: &str,
+        freq: f32,
+        mut font_size: f32,
+        canvas: &Canvas,
+        same_word_boxes: &[(Point, (u32, u32))],
+        region: Option<usize>,
+        rng: &mut WyRand,
+    ) -> Result<(Point, GlyphData, f32, f32), f32> {
+        let initial_font_size = font_size;
+        let min_font_size = self.min_font_size * self.preview_scale;
+        let font_step = self.font_step * self.preview_scale;
+        let mut relocate_threshold = self
+            .relocate_threshold
+            .map(|threshold| threshold * self.preview_scale);
+        let mut relocated_at = None;
+        let mut shold_rotate = match &self.rotation_decider {
+            Some(decider) => decider(word, freq),
+            None => rng.generate::<u8>() <= (255.0 * self.word_rotate_chance) as u8,
+        };
+        let rotation_angle = self.rotation_angle(rng);
+        let mut tried_rotate = false;
+        loop {
+            let angle = if shold_rotate { rotation_angle } else { 0.0 };
+            let glyphs = text::text_to_glyphs(
+                word,
+                self.font_for_frequency(freq),
+                PxScale::from(font_size),
+                self.scaled_text_options(self.preview_scale),
+            );
+            let (rect, offset) = self.reserved_rect(&glyphs, angle, freq);
+            let to_word_position = |x: u32, y: u32| point(x as f32 + offset.x, y as f32 + offset.y);
+            let size = text::rotated_dimensions(glyphs.width, glyphs.height, angle);
+            let accept = |x: u32, y: u32| {
+                self.accepts_position(word, to_word_position(x, y), size, same_word_boxes)
+            };
+
+            // 比画布还大的话直接当作放不下
+            let place_res = if rect.width > canvas.gray_buffer.width()
+                || rect.height > canvas.gray_buffer.height()
+            {
+                None
+            } else {
+                let (table_width, table_height) = canvas.gray_buffer.dimensions();
+                // 先只在要分给的区域里找，外框中心要在区域里
+                let in_region = |x: u32, y: u32| canvas.in_region(region, x, y, &rect);
+                let search = |rng: &mut WyRand, restricted: bool| {
+                    sat::find_candidates_for_rect(
+                        &canvas.summed_area_table,
+                        table_width,
+                        table_height,
+                        canvas.skip_list.as_deref(),
+                        &rect,
+                        self.placement_candidates,
+                        rng,
+                        |x, y| (!restricted || in_region(x, y)) && accept(x, y),
+                    )
+                };
+                let mut candidates = search(rng, region.is_some());
+                if candidates.is_empty() && region.is_some() {
+                    candidates = search(rng, false);
+                }
+                // 多个候选时选周围被占得最多的，词挤得更紧
+                let margin = (rect.width.min(rect.height) / 2).max(1);
+                let ink_table = canvas
+                    .ink_table
+                    .as_ref()
+                    .unwrap_or(&canvas.summed_area_table);
+                candidates.into_iter().rev().max_by_key(|candidate| {
+                    sat::surrounding_occupancy(
+                        ink_table,
+                        table_width,
+                        table_height,
+                        candidate,
+                        &rect,
+                        margin,
+                    )
+                })
+            };
+
+            match place_res {
+                Some(pos) => {
+                    let position = to_word_position(pos.x, pos.y);
+
+                    return Ok((position, glyphs, angle, font_size));
+                }
+                None => {
+                    let next_font_size = Self::check_font_size(font_size, font_step, min_font_size);
+                    // 要缩到阈值以下之前，先在这个大小换个方向试试
+                    let crosses_threshold = relocate_threshold.is_some_and(|threshold| {
+                        font_size >= threshold && next_font_size.is_none_or(|next| next < threshold)
+                    });
+
+                    if crosses_threshold && relocated_at.is_none() {
+                        relocated_at = Some(font_size);
+                        shold_rotate = !shold_rotate;
+                    } else if let Some(next_font_size) = next_font_size {
+                        if relocated_at == Some(font_size) {
+                            // 换方向也放不下，换回来继续缩小
+                            shold_rotate = !shold_rotate;
+                        }
+                        font_size = next_font_size;
+                    } else if !tried_rotate {
+                        //TODO 横着放不行，试下竖着放
+                        shold_rotate = true;
+                        tried_rotate = true;
+                        font_size = initial_font_size;
+                        // 阈值那一档两个方向都试过了
+                        relocate_threshold = None;
+                    } else {
+                        return Err(font_size);
+                    }
+                }
+            }
+        }
+    }
+
+    /// 词放在 `position`（占位图上的坐标）时离同一个词的其他副本够不够远，`placement_filter`
+    /// 同不同意，`place_word` 和 `replace_word` 都用
+    fn accepts_position(
+        &self,
+        word: &str,
+        position: Point,
+        (width, height): (u32, u32),
+        same_word_boxes: &[(Point, (u32, u32))],
+    ) -> bool {
+        let min_distance = self.min_same_word_distance * self.preview_scale;
+        let far_from_copies = same_word_boxes
+            .iter()
+            .all(|(other, (other_width, other_height))| {
+                // 两个外框之间的距离，重叠时是 0
+                let dx = (other.x - (position.x + width as f32))
+                    .max(position.x - (other.x + *other_width as f32))
+                    .max(0.0);
+                let dy = (other.y - (position.y + height as f32))
+                    .max(position.y - (other.y + *other_height as f32))
+                    .max(0.0);
+                dx.hypot(dy) >= min_distance
+            });
+        // 过滤器用的是原画布上的坐标
+        match &self.placement_filter {
+            _ if !far_from_copies => false,
+            Some(filter) => filter(
+                word,
+                &point(
+                    position.x / self.preview_scale,
+                    position.y / self.preview_scale,
+                ),
+                &Rect {
+                    width: (width as f32 / self.preview_scale) as u32,
+                    height: (height as f32 / self.preview_scale) as u32,
+                },
+            ),
+            None => true,
+        }
+    }
+
+    /// 最小字号的 `word` 加上间距能不能放进整个画布（横竖都试），和 `place_word` 的判断一样
+    fn fits_canvas_at_min_size(&self, (word, freq): &(&str, f32), canvas: &Canvas) -> bool {
+        let glyphs = text::text_to_glyphs(
+            word,
+            self.font_for_frequency(*freq),
+            PxScale::from(self.min_font_size * self.preview_scale),
+            self.scaled_text_options(self.preview_scale),
+        );
+        let (width, height) = canvas.gray_buffer.dimensions();
+
+        [0.0, 90.0].into_iter().any(|angle| {
+            let (rect, _) = self.reserved_rect(&glyphs, angle, *freq);
+            rect.width <= width && rect.height <= height
+        })
+    }
+
+    /// 画布上最大的空白矩形还能不能放下最小字号的 `word`（横竖都试）
+    fn fits_empty_space(&self, word: &str, canvas: &Canvas) -> bool {
+        let empty = sat::largest_empty_rect(
+            canvas.gray_buffer.as_raw(),
+            canvas.gray_buffer.width(),
+            canvas.gray_buffer.height(),
+        );
+        let rect = self.text_dimensions_at_font_size(
+            word,
+            PxScale::from(self.min_font_size * self.preview_scale),
+        );
+
+        (rect.width <= empty.width && rect.height <= empty.height)
+            || (rect.height <= empty.width && rect.width <= empty.height)
+    }
+
+    /// 检查遮罩的大小，按设置反转、往里收
+    fn prepare_mask(&self, mut image: GrayImage) -> Result<GrayImage, WordCloudError> {
+        self.canvas_dimensions(image.width(), image.height(), 1.0)?;
+        if self.mask_invert {
+            image::imageops::invert(&mut image);
+        }
+        if self.mask_threshold > 0 {
+            for px in image.pixels_mut() {
+                if px.0[0] <= self.mask_threshold {
+                    *px = Luma([0]);
+                }
+            }
+        }
+        if self.mask_edge_margin > 0 {
+            image = mask::erode_available(&image, self.mask_edge_margin);
+        }
+
+        Ok(image)
+    }
+
+    pub(crate) fn text_options(&self) -> text::TextOptions {
+        text::TextOptions {
+            tab_width: self.tab_width,
+            missing_glyph: self.missing_glyph_policy,
+            letter_spacing: self.letter_spacing,
+            align: self.text_align,
+        }
+    }
+
+    /// 按 `scale` 倍的画布排版时的设置，字距也跟着缩放
+    pub(crate) fn scaled_text_options(&self, scale: f32) -> text::TextOptions {
+        text::TextOptions {
+            letter_spacing: self.letter_spacing * scale,
+            ..self.text_options()
+        }
+    }
+
+    /// 旋转的词转多少度，设置了 `rotation_range` 时从 `min, min + step, ..., max` 里随机选一个
+    fn rotation_angle(&self, rng: &mut WyRand) -> f32 {
+        match self.rotation_range {
+            Some((min, max, step)) => {
+                let steps = ((max - min) / step).floor() as u32;
+                min + step * rng.generate_range(0..=steps) as f32
+            }
+            // 关掉时不消耗随机数，和以前的结果一样
+            None if self.bidirectional_rotation && rng.generate::<bool>() => -90.0,
+            None => 90.0,
+        }
+    }
+
+    /// 从小到大放时没有大词先占住画布，按所有词的总面积估算最大词的字号，
+    /// 让所有词大概占可用面积的一半
+    fn smallest_first_start_size(&self, words: &[(&str, f32)], canvas: &Canvas) -> f32 {
+        const ESTIMATE_FONT_SIZE: f32 = 64.0;
+        const FILL_RATIO: f32 = 0.5;
+
+        let max_freq = words.first().map_or(1.0, |(_, freq)| *freq);
+        let words_area = words
+            .iter()
+            .map(|(word, freq)| {
+                let relative_size = self.relative_font_scaling * (freq / max_freq)
+                    + (1.0 - self.relative_font_scaling);
+                let rect =
+                    self.text_dimensions_at_font_size(word, PxScale::from(ESTIMATE_FONT_SIZE));
+                relative_size * relative_size * (rect.width * rect.height) as f32
+            })
+            .sum::<f32>();
+        let available_area = canvas
+            .gray_buffer
+            .as_raw()
+            .iter()
+            .filter(|p| **p == 0)
+            .count() as f32;
+
+        ESTIMATE_FONT_SIZE * (FILL_RATIO * available_area / words_area.max(1.0)).sqrt()
+    }
+
+    /// 没给 rng 时放词用的 rng，`deterministic_seed` 的种子按放词用的画布大小算
+    fn placement_rng(&self, words: &[(&str, f32)], size: &WordCloudSize) -> WyRand {
+        match self.rng_seed {
+            Some(seed) => WyRand::new_seed(seed),
+            None if self.deterministic_seed => {
+                let (width, height) = match size {
+                    WordCloudSize::FromDimensions { width, height } => (*width, *height),
+                    WordCloudSize::FromMask(image) => image.dimensions(),
+                    WordCloudSize::FromMaskScaled {
+                        mask,
+                        max_dimension,
+                    } => mask::fit_dimensions(mask.dimensions(), *max_dimension),
+                };
+                WyRand::new_seed(self.content_seed(words, width, height))
+            }
+            None => WyRand::new(),
+        }
+    }
+
+    /// 用 FNV-1a 算种子，不用 `DefaultHasher`，它的结果在不同版本的 Rust 之间不保证一样
+    fn content_seed(&self, words: &[(&str, f32)], width: u32, height: u32) -> u64 {
+        let mut hash = FNV_OFFSET;
+        let mut write = |bytes: &[u8]| hash = fnv1a(hash, bytes);
+
+        for (word, freq) in words {
+            write(word.as_bytes());
+            write(&[0]);
+            write(&freq.to_le_bytes());
+        }
+        write(&width.to_le_bytes());
+        write(&height.to_le_bytes());
+        write(&self.min_font_size.to_le_bytes());
+        write(&self.max_font_size.unwrap_or(0.0).to_le_bytes());
+        write(&self.font_step.to_le_bytes());
+        write(&self.visual_margin.to_le_bytes());
+        write(&self.collision_margin.to_le_bytes());
+        write(&self.word_rotate_chance.to_le_bytes());
+        write(&self.relative_font_scaling.to_le_bytes());
+        write(&self.preview_scale.to_le_bytes());
+        if let Some((min, max, step)) = self.rotation_range {
+            for value in [min, max, step] {
+                write(&value.to_le_bytes());
+            }
+        }
+        // 没开的设置不算进去，以前的种子不变
+        if self.frequency_margin_scale != 0.0 {
+            write(&self.frequency_margin_scale.to_le_bytes());
+        }
+
+        hash
+    }
+
+    /// 在占位图上画出一个词（`Luma([1])`），阴影也占位置
+    fn mark_word(
+        &self,
+        canvas: &mut Canvas,
+        glyphs: &GlyphData,
+        font: &FontVec,
+        pos: Point,
+        angle: f32,
+    ) {
+        let pixel = Luma([1]);
+        text::draw_glyphs_to_gray_buffer(
+            &mut canvas.gray_buffer,
+            &mut canvas.draw_context,
+            glyphs.clone(),
+            font,
+            pos,
+            angle,
+            pixel,
+            self.occupancy_threshold,
+        );
+        if let Some(shadow) = &self.text_shadow {
+            let offset = point(
+                shadow.offset_x as f32 * self.preview_scale,
+                shadow.offset_y as f32 * self.preview_scale,
+            );
+            if shadow.blur > 0.0 {
+                // 模糊出去的部分也占着，后面的词不会压在上面
+                text::draw_shadow_to_gray_buffer(
+                    &mut canvas.gray_buffer,
+                    glyphs,
+                    font,
+                    pos,
+                    angle,
+                    offset,
+                    shadow.blur * self.preview_scale,
+                    pixel,
+                    self.occupancy_threshold,
+                );
+            } else {
+                text::draw_glyphs_to_gray_buffer(
+                    &mut canvas.gray_buffer,
+                    &mut canvas.draw_context,
+                    glyphs.clone(),
+                    font,
+                    pos + offset,
+                    angle,
+                    pixel,
+                    self.occupancy_threshold,
+                );
+            }
+        }
+    }
+
+    /// `word` 在占位图上的字形、位置和字号。开了 `with_preview_scale` 时占位图是预览尺寸的，
+    /// 布局里的词是最终尺寸的
+    fn placement_glyphs(&self, word: &Word) -> (GlyphData, Point, f32) {
+        if self.preview_scale < 1.0 {
+            let font_size = word.font_size.y * self.preview_scale;
+            (
+                text::text_to_glyphs(
+                    word.text,
+                    word.font,
+                    PxScale::from(font_size),
+                    self.scaled_text_options(self.preview_scale),
+                ),
+                point(
+                    word.position.x * self.preview_scale,
+                    word.position.y * self.preview_scale,
+                ),
+                font_size,
+            )
+        } else {
+            (word.glyphs.clone(), word.position, word.font_size.y)
+        }
+    }
+
+    /// The space `layout` considers taken, for debugging placement. Like a mask, black pixels
+    /// are free and white ones are taken by a word, its margin or the mask. It is the size of
+    /// the placement canvas, smaller than the layout with `with_preview_scale`
+    pub fn occupancy_snapshot(&self, layout: &Layout) -> GrayImage {
+        let mut snapshot = layout.canvas.gray_buffer.clone();
+        for px in snapshot.pixels_mut() {
+            if px.0[0] != 0 {
+                *px = Luma([255]);
+            }
+        }
+
+        snapshot
+    }
+
+    /// Replaces the text of `layout.words[index]` with `new_text`, keeping the rest of the
+    /// layout. The new text is placed as close as possible to where the old word was, at the
+    /// same size if it fits and smaller otherwise, following the same placement filter, same
+    /// word distance and region rules as the first placement. Fails with
+    /// `WordCloudError::WordsDropped` and leaves the layout untouched if it can't be placed
+    /// anywhere
+    pub fn replace_word<'a>(
+        &'a self,
+        layout: &mut Layout<'a>,
+        index: usize,
+        new_text: &'a str,
+    ) -> Result<(), WordCloudError> {
+        let old_word = &layout.words[index];
+        let font = self.font_for_frequency(old_word.frequency);
+        let (old_glyphs, old_position, old_font_size) = self.placement_glyphs(old_word);
+        let old_angle = old_word.angle;
+        // 和 `place_word` 一样要离同一个词的其他副本够远
+        let same_word_boxes = layout
+            .words
+            .iter()
+            .enumerate()
+            .filter(|(i, word)| {
+                self.min_same_word_distance > 0.0 && *i != index && word.text == new_text
+            })
+            .map(|(_, word)| {
+                let (width, height) =
+                    text::rotated_dimensions(word.glyphs.width, word.glyphs.height, word.angle);
+                (
+                    point(
+                        word.position.x * self.preview_scale,
+                        word.position.y * self.preview_scale,
+                    ),
+                    (
+                        (width as f32 * self.preview_scale) as u32,
+                        (height as f32 * self.preview_scale) as u32,
+                    ),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let canvas = &mut layout.canvas;
+        // 先在原来的词所在的区域里找
+        let region = canvas.regions.as_ref().and_then(|regions| {
+            let (width, height) =
+                text::rotated_dimensions(old_glyphs.width, old_glyphs.height, old_angle);
+            regions.label_at(
+                (old_position.x + width as f32 / 2.0).max(0.0) as u32,
+                (old_position.y + height as f32 / 2.0).max(0.0) as u32,
+            )
+        });
+        // 不能把原来的词画成 0 擦掉：间距是负的或者有阴影时和别的词重叠的像素也会被擦掉，
+        // 所以用剩下的词重新画一遍占位图
+        canvas.gray_buffer = canvas.background.clone();
+        for (_, word) in layout.words.iter().enumerate().filter(|(i, _)| *i != index) {
+            let (glyphs, position, _) = self.placement_glyphs(word);
+            self.mark_word(canvas, &glyphs, word.font, position, word.angle);
+        }
+        canvas.update_summed_area_table(point(0.0, 0.0));
+
+        let min_font_size = self.min_font_size * self.preview_scale;
+        let font_step = self.font_step * self.preview_scale;
+        let mut placed = None;
+        // 换方向时，转过的词摆正，没转的词转 90 度
+        let other_angle = if old_angle == 0.0 { 90.0 } else { 0.0 };
+        'orientations: for angle in [old_angle, other_angle] {
+            let mut font_size = old_font_size;
+            loop {
+                let glyphs = text::text_to_glyphs(
+                    new_text,
+                    font,
+                    PxScale::from(font_size),
+                    self.scaled_text_options(self.preview_scale),
+                );
+                let (rect, offset) = self.reserved_rect(&glyphs, angle, old_word.frequency);
+                let target = old_position - offset;
+                let size = text::rotated_dimensions(glyphs.width, glyphs.height, angle);
+                let search = |restricted: bool| {
+                    sat::find_nearest_space_for_rect(
+                        &canvas.summed_area_table,
+                        canvas.gray_buffer.width(),
+                        canvas.gray_buffer.height(),
+                        &rect,
+                        target.x,
+                        target.y,
+                        |x, y| {
+                            (!restricted || canvas.in_region(region, x, y, &rect))
+                                && self.accepts_position(
+                                    new_text,
+                                    point(x as f32 + offset.x, y as f32 + offset.y),
+                                    size,
+                                    &same_word_boxes,
+                                )
+                        },
+                    )
+                };
+                let found = search(region.is_some())
+                    .or_else(|| region.is_some().then(|| search(false)).flatten());
+
+                if let Some(pos) = found {
+                    let pos = point(pos.x as f32 + offset.x, pos.y as f32 + offset.y);
+                    placed = Some((pos, glyphs, angle, font_size));
+                    break 'orientations;
+                }
+
+                match Self::check_font_size(font_size, font_step, min_font_size) {
+                    Some(next_font_size) => font_size = next_font_size,
+                    None => break,
+                }
+            }
+        }
+
+        let Some((pos, glyphs, angle, font_size)) = placed else {
+            // 放不下就把原来的词画回去
+            let old_font = old_word.font;
+            self.mark_word(canvas, &old_glyphs, old_font, old_position, old_angle);
+            canvas.update_summed_area_table(old_position);
+            return Err(WordCloudError::WordsDropped(vec![new_text.to_string()]));
+        };
+
+        self.mark_word(canvas, &glyphs, font, pos, angle);
+        canvas.update_summed_area_table(pos);
+
+        let word = &mut layout.words[index];
+        word.text = new_text;
+        word.font = font;
+        word.rotated = angle != 0.0;
+        word.angle = angle;
+        if self.preview_scale < 1.0 {
+            word.font_size = PxScale::from(font_size / self.preview_scale);
+            word.glyphs = text::text_to_glyphs(new_text, font, word.font_size, self.text_options());
+            word.position = point(pos.x / self.preview_scale, pos.y / self.preview_scale);
+        } else {
+            word.font_size = PxScale::from(font_size);
+            word.glyphs = glyphs;
+            word.position = pos;
+        }
+
+        Ok(())
+    }
+
+    /// 一个词在画布上要占的空间（加上间距和阴影），以及从这块空间的左上角到词的位置的偏移。
+    /// 间距按 `freq` 加上 `frequency_margin_scale` 那一份
+    fn reserved_rect(&self, glyphs: &GlyphData, angle: f32, freq: f32) -> (Rect, Point) {
+        let (shadow_left, shadow_top, shadow_right, shadow_bottom) = self
+            .text_shadow
+            .map(|shadow| shadow.extents(self.preview_scale))
+            .unwrap_or_default();
+        let (width, height) = text::rotated_dimensions(glyphs.width, glyphs.height, angle);
+        let extra_margin = (self.frequency_margin_scale * freq).round() as i32;
+        let collision_margin = self.collision_margin.saturating_add(extra_margin);
+        let visual_margin = self.visual_margin as i64 + extra_margin as i64;
+
+        let rect = Rect {
+            width: with_margin(width + shadow_left + shadow_right, collision_margin),
+            height: with_margin(height + shadow_top + shadow_bottom, collision_margin),
+        };
+        // 间距是负的时候字比占的位置大，往左上超出一半
+        let half_margin = visual_margin.min(collision_margin as i64) as f32 / 2.0;
+        let offset = point(
+            half_margin + shadow_left as f32,
+            half_margin + shadow_top as f32,
+        );
+
+        (rect, offset)
+    }
+
+    /// The space a word takes up at the given font size, including the collision margin
+    pub fn text_dimensions_at_font_size(&self, text: &str, font_size: PxScale) -> Rect {
+        let glyphs = text::text_to_glyphs(text, &self.font, font_size, self.text_options());
+        Rect {
+            width: with_margin(glyphs.width, self.collision_margin),
+            height: with_margin(glyphs.height, self.collision_margin),
+        }
+    }
+
+    /// 上一个词的字号是 `font_size`、词频是 `last_freq` 时 `word` 的字号，还没有按
+    /// `min_font_size` 检查
+    fn next_font_size(
+        &self,
+        recurrence: &FontSizeRecurrence,
+        (word, freq): (&str, f32),
+        font_size: f32,
+        last_freq: f32,
+    ) -> f32 {
+        let font_size = match (recurrence.target_area, self.placement_order) {
+            (Some(target_area), _) => {
+                self.equal_area_font_size(word, target_area, recurrence.max_equal_area_size)
+            }
+            (None, PlacementOrder::LargestFirst) => {
+                if !self.tokenizer.repeat && self.relative_font_scaling != 0.0 {
+                    font_size
+                        * (self.relative_font_scaling * (freq / last_freq)
+                            + (1.0 - self.relative_font_scaling))
+                } else {
+                    font_size
+                }
+            }
+            // 从小到大放时前一个词更小，只能按和最大词频的比例算
+            (None, PlacementOrder::SmallestFirst) => {
+                recurrence.start_font_size
+                    * (self.relative_font_scaling * (freq / recurrence.first_freq)
+                        + (1.0 - self.relative_font_scaling))
+            }
+        };
+
+        match recurrence.max_font_size {
+            Some(max_font_size) => font_size.min(max_font_size),
+            None => font_size,
+        }
+    }
+
+    /// 二分找外框面积最接近 `target_area` 又不超过它的字号，最大 `max_font_size`
+    fn equal_area_font_size(&self, word: &str, target_area: f32, max_font_size: f32) -> f32 {
+        const SEARCH_STEPS: usize = 16;
+
+        let area = |font_size: f32| {
+            let rect = self.text_dimensions_at_font_size(word, PxScale::from(font_size));
+            rect.width as f32 * rect.height as f32
+        };
+        if area(max_font_size) <= target_area {
+            return max_font_size;
+        }
+
+        let (mut low, mut high) = (0.0, max_font_size);
+        for _ in 0..SEARCH_STEPS {
+            let font_size = (low + high) / 2.0;
+            if area(font_size) <= target_area {
+                low = font_size;
+            } else {
+                high = font_size;
+            }
+        }
+
+        low
+    }
+
+    /// The font size each word of `text` would start at on a canvas `canvas_height` pixels
+    /// high, most frequent first, to tune `relative_font_scaling` and `max_words` without
+    /// rendering. Assumes the first word fits the canvas width and no word has to shrink to
+    /// find a free spot, so the real sizes can only be smaller. Words whose size is below
+    /// `min_font_size` would be dropped
+    pub fn font_size_schedule(&self, text: &str, canvas_height: u32) -> Vec<(String, f32)> {
+        let words = self.tokenizer.get_normalized_word_frequencies(text);
+        let Some(first_freq) = words.first().map(|(_, freq)| *freq) else {
+            return vec![];
+        };
+
+        let canvas_height = canvas_height as f32;
+        let start_font_size = canvas_height
+            * self
+                .first_word_height_fraction
+                .unwrap_or(DEFAULT_FIRST_WORD_HEIGHT_FRACTION);
+        let max_font_size = [
+            self.max_font_size,
+            self.max_font_size_ratio.map(|ratio| ratio * canvas_height),
+        ]
+        .into_iter()
+        .flatten()
+        .reduce(f32::min);
+
+        let recurrence = FontSizeRecurrence {
+            start_font_size,
+            first_freq,
+            target_area: (self.size_strategy == SizeStrategy::EqualArea).then(|| {
+                let rect =
+                    self.text_dimensions_at_font_size(words[0].0, PxScale::from(start_font_size));
+                rect.width as f32 * rect.height as f32
+            }),
+            max_font_size,
+            max_equal_area_size: canvas_height,
+        };
+
+        // 和放词时一样的递推
+        let mut font_size = start_font_size;
+        let mut last_freq = 1.0;
+        words
+            .iter()
+            .map(|(word, freq)| {
+                font_size = self.next_font_size(&recurrence, (word, *freq), font_size, last_freq);
+                last_freq = *freq;
+
+                (word.to_string(), font_size)
+            })
+            .collect()
+    }
+
+    /// Roughly estimates a canvas size (2:1) that can hold the `target_words` most frequent
+    /// words of `text` without dropping them, by summing their bounding box areas at the
+    /// font sizes placement would give them and adding packing overhead
+    pub fn estimate_canvas_size(&self, text: &str, target_words: usize) -> (u32, u32) {
+        const ESTIMATE_FONT_SIZE: f32 = 64.0;
+        const PACKING_OVERHEAD: f32 = 1.6;
+        const ASPECT_RATIO: f32 = 2.0;
+
+        let words = self.tokenizer.get_normalized_word_frequencies(text);
+        let words = &words[..words.len().min(target_words)];
+        let Some((first_word, first_freq)) = words.first().copied() else {
+            return (0, 0);
+        };
+
+        let start_font_size = self.max_font_size.unwrap_or(ESTIMATE_FONT_SIZE);
+        let first_rect =
+            self.text_dimensions_at_font_size(first_word, PxScale::from(start_font_size));
+        let recurrence = FontSizeRecurrence {
+            start_font_size,
+            first_freq,
+            target_area: (self.size_strategy == SizeStrategy::EqualArea)
+                .then_some(first_rect.width as f32 * first_rect.height as f32),
+            max_font_size: self.max_font_size,
+            // 画布至少和最常见的词一样宽，等面积的词不会比这还高
+            max_equal_area_size: first_rect.width as f32,
+        };
+
+        // 和放词时一样的递推
+        let mut font_size = start_font_size;
+        let mut last_freq = 1.0;
+        let font_sizes = words
+            .iter()
+            .map(|(word, freq)| {
+                font_size = self.next_font_size(&recurrence, (word, *freq), font_size, last_freq);
+                last_freq = *freq;
+                font_size
+            })
+            .collect::<Vec<_>>();
+
+        // 保证最小的词也不会小于 min_font_size
+        let smallest = font_sizes.iter().copied().fold(f32::INFINITY, f32::min);
+        let scale = if smallest > 0.0 {
+            (self.min_font_size / smallest).max(1.0)
+        } else {
+            1.0
+        };
+
+        let (mut area, mut min_width, mut min_height) = (0.0, 0, 0);
+        for ((word, _), font_size) in words.iter().zip(font_sizes) {
+            let rect = self.text_dimensions_at_font_size(word, PxScale::from(font_size * scale));
+            area += (rect.width * rect.height) as f32;
+            min_width = min_width.max(rect.width);
+            min_height = min_height.max(rect.height);
+        }
+
+        let area = area * PACKING_OVERHEAD;
+        let height = (area / ASPECT_RATIO).sqrt().ceil() as u32;
+        let width = (height as f32 * ASPECT_RATIO) as u32;
+
+        (width.max(min_width), height.max(min_height))
+    }
+
+    fn check_font_size(font_size: f32, font_step: f32, min_font_size: f32) -> Option<f32> {
+        let next_font_size = font_size - font_step;
+
+        if next_font_size >= min_font_size && next_font_size > 0.0 {
+            Some(next_font_size)
+        } else {
+            None
+        }
+    }
+}
+
+/// Parses the face at `index` of a TrueType collection (`.ttc`), or of a plain font file when
+/// `index` is 0. Fails with `WordCloudError::InvalidConfig` if the collection has no such face.
+/// With `with_frequency_font_variation` prefer `WordCloud::with_font_from_path_indexed`, which
+/// remembers the index
+pub fn font_from_collection(data: Vec<u8>, index: u32) -> Result<FontVec, WordCloudError> {
+    // 字体集的文件头是 "ttcf"、版本、字体个数，普通字体文件只有一个字体
+    let faces = match data.get(..12) {
+        Some([b't', b't', b'c', b'f', _, _, _, _, count @ ..]) => {
+            u32::from_be_bytes([count[0], count[1], count[2], count[3]])
+        }
+        _ => 1,
+    };
+    if index >= faces {
+        return Err(WordCloudError::InvalidConfig(format!(
+            "The font has {faces} faces, there is no face at index {index}"
+        )));
+    }
+
+    FontVec::try_from_vec_and_index(data, index).map_err(|_| {
+        WordCloudError::InvalidConfig(format!("The font face at index {index} is invalid"))
+    })
+}
+
+/// 轴的标签是 4 个字节，短的用空格补齐
+fn axis_tag(axis: &str) -> [u8; 4] {
+    assert!(axis.len() <= 4, "A font axis tag has at most 4 characters");
+
+    let mut tag = [b' '; 4];
+    tag[..axis.len()].copy_from_slice(axis.as_bytes());
+    tag
+}
+
+/// 字号递推里不变的部分，放词、`font_size_schedule` 和 `estimate_canvas_size` 共用
+struct FontSizeRecurrence {
+    /// 最常见的词的字号，从小到大放时按它和词频的比例算
+    start_font_size: f32,
+    first_freq: f32,
+    /// `SizeStrategy::EqualArea` 时每个词的外框都要凑到的面积
+    target_area: Option<f32>,
+    max_font_size: Option<f32>,
+    /// `SizeStrategy::EqualArea` 时最大的字号
+    max_equal_area_size: f32,
+}
+
+/// 放置过程中的画布状态
+struct Canvas {
+    gray_buffer: GrayImage,
+    /// 放词之前的占位图，遮罩、排除的区域和路径上的词
+    background: GrayImage,
+    skip_list: Option<Vec<(usize, usize)>>,
+    summed_area_table: Vec<u32>,
+    /// 放上去的词占的像素记 1 的求和面积表，`placement_candidates` 大于 1 时才有
+    ink_table: Option<Vec<u32>>,
+    /// 开了 `balance_across_regions` 时遮罩上分开的区域
+    regions: Option<mask::Regions>,
+    /// 画词占位置时所有字形共用
+    draw_context: text::DrawContext,
+}
+
+impl Canvas {
+    /// 左上角在 (`x`, `y`) 的 `rect` 的中心在不在 `region` 里，没有要求时都算在
+    fn in_region(&self, region: Option<usize>, x: u32, y: u32, rect: &Rect) -> bool {
+        match (&self.regions, region) {
+            (Some(regions), Some(label)) => {
+                regions.label_at(x + rect.width / 2, y + rect.height / 2) == Some(label)
+            }
+            _ => true,
+        }
+    }
+
+    /// 在 `pos` 放了新词之后重新计算 summed area table
+    fn update_summed_area_table(&mut self, pos: Point) {
+        u8_to_u32_vec(&self.gray_buffer, &mut self.summed_area_table);
+        let start_row = (pos.y - 1.0).min(0.0) as usize;
+        sat::to_summed_area_table(
+            &mut self.summed_area_table,
+            self.gray_buffer.width() as usize,
+            start_row,
+        );
+
+        if let Some(ink_table) = &mut self.ink_table {
+            let pixels = self
+                .gray_buffer
+                .as_raw()
+                .iter()
+                .zip(self.background.as_raw());
+            for (dst, (px, background)) in ink_table.iter_mut().zip(pixels) {
+                *dst = (px != background) as u32;
+            }
+            sat::to_summed_area_table(ink_table, self.gray_buffer.width() as usize, 0);
+        }
+    }
+}
+
+/// 加上可能是负的间距，至少 1 个像素
+fn with_margin(size: u32, margin: i32) -> u32 {
+    (size as i64 + margin as i64).clamp(1, u32::MAX as i64) as u32
+}
+
+/// FNV-1a 的初始值
+pub(crate) const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+
+/// 接着 `hash` 把 `bytes` 算进 FNV-1a。不用 std 的 hasher：它的结果不保证在不同的 Rust
+/// 版本和平台上一样
+pub(crate) fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    const FNV_PRIME: u64 = 0x100000001b3;
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+fn create_mask_skip_list(img: &GrayImage) -> Vec<(usize, usize)> {
+    img.rows()
+        .map(|mut row| {
+            let furthest_right = row
+                .rposition(|p| p == &Luma::from([0]))
+                .unwrap_or(img.width() as usize);
+            let furthest_left = row.position(|p| p == &Luma::from([0])).unwrap_or(0);
+
+            (furthest_left, furthest_right)
+        })
+        .collect()
+}
+
+fn u8_to_u32_vec(buffer: &GrayImage, dst: &mut [u32]) {
+    for (i, el) in buffer.as_raw().iter().enumerate() {
+        dst[i] = *el as u32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GrayImage, Luma, Rgba};
+
+    use ab_glyph::{point, PxScale};
+    use nanorand::{Rng, WyRand};
+
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::{
+        font_from_collection, text, Align, ChineseTokenizer, DropPolicy, Limit, PathWord,
+        PlacementOrder, ResourceLimits, ShapeBias, SizeStrategy, Word, WordCloud, WordCloudError,
+        WordCloudSize,
+    };
+    use crate::{layout_score, WordPath};
+
+    const TEXT: &str = "apple apple apple banana banana cherry date elderberry fig grape";
+
+    #[test]
+    fn inverted_mask() {
+        let mask = GrayImage::from_fn(300, 150, |x, y| {
+            let (dx, dy) = (x as f32 / 150.0 - 1.0, y as f32 / 75.0 - 1.0);
+            if dx * dx + dy * dy <= 1.0 {
+                Luma([0])
+            } else {
+                Luma([255])
+            }
+        });
+        let mut inverted_mask = mask.clone();
+        image::imageops::invert(&mut inverted_mask);
+
+        let wordcloud = WordCloud::default().with_rng_seed(1);
+        let layout = wordcloud
+            .generate_layout_from_text(TEXT, WordCloudSize::FromMask(mask))
+            .unwrap();
+
+        let wordcloud = WordCloud::default().with_rng_seed(1).with_mask_invert(true);
+        let inverted_layout = wordcloud
+            .generate_layout_from_text(TEXT, WordCloudSize::FromMask(inverted_mask))
+            .unwrap();
+
+        assert!(!layout.words.is_empty());
+        assert_eq!(layout.words.len(), inverted_layout.words.len());
+        for (word, inverted_word) in layout.words.iter().zip(&inverted_layout.words) {
+            assert_eq!(word.text, inverted_word.text);
+            assert_eq!(word.position, inverted_word.position);
+            assert_eq!(word.rotated, inverted_word.rotated);
+        }
+    }
+
+    #[test]
+    fn max_font_size_ratio() {
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_max_font_size_ratio(0.2);
+
+        let small = wordcloud
+            .generate_layout_from_text(
+                TEXT,
+                WordCloudSize::FromDimensions {
+                    width: 400,
+                    height: 200,
+                },
+            )
+            .unwrap();
+        let large = wordcloud
+            .generate_layout_from_text(
+                TEXT,
+                WordCloudSize::FromDimensions {
+                    width: 800,
+                    height: 400,
+                },
+            )
+            .unwrap();
+
+        assert_eq!(small.words[0].font_size.y, 40.0);
+        assert_eq!(large.words[0].font_size.y, 80.0);
+        assert!(small.words.iter().all(|word| word.font_size.y <= 40.0));
+        assert!(large.words.iter().all(|word| word.font_size.y <= 80.0));
+    }
+
+    #[test]
+    fn first_word_height_fraction() {
+        let first_word_height = |fraction: f32| {
+            let wordcloud = WordCloud::default()
+                .with_rng_seed(1)
+                .with_first_word_height_fraction(fraction);
+            let layout = wordcloud
+                .generate_layout_from_text(
+                    TEXT,
+                    WordCloudSize::FromDimensions {
+                        width: 800,
+                        height: 200,
+                    },
+                )
+                .unwrap();
+            assert_eq!(layout.words[0].text, "apple");
+            layout.words[0].glyphs.height
+        };
+
+        let small = first_word_height(0.3);
+        let large = first_word_height(0.8);
+        assert!(large > small * 2, "{} {}", small, large);
+
+        // 超出范围的值在 setter 和 builder 里都按 0.1 - 0.95 算
+        let set = WordCloud::default().with_first_word_height_fraction(2.0);
+        let built = WordCloud::builder()
+            .first_word_height_fraction(2.0)
+            .build()
+            .unwrap();
+        assert_eq!(set.first_word_height_fraction, Some(0.95));
+        assert_eq!(built.first_word_height_fraction, Some(0.95));
+        assert_eq!(WordCloud::default().first_word_height_fraction, None);
+    }
+
+    #[test]
+    fn estimate_canvas_size() {
+        let text = "apple apple apple apple apple apple banana banana cherry date";
+        let area = |wordcloud: WordCloud| {
+            let (width, height) = wordcloud.estimate_canvas_size(text, 10);
+            width * height
+        };
+
+        let frequency = area(WordCloud::default());
+        assert!(frequency > 0);
+        // 和放词时一样，等面积、从小到大放和重复的词都比按词频一路缩小的词大
+        assert!(area(WordCloud::default().with_size_strategy(SizeStrategy::EqualArea)) > frequency);
+        assert!(
+            area(WordCloud::default().with_placement_order(PlacementOrder::SmallestFirst))
+                > frequency
+        );
+        let repeat = ChineseTokenizer::default()
+            .with_repeat(true)
+            .with_max_words(10);
+        assert!(area(WordCloud::default().with_tokenizer(repeat)) > frequency);
+        assert_eq!(WordCloud::default().estimate_canvas_size("", 10), (0, 0));
+    }
+
+    #[test]
+    fn text_dimensions_at_font_size() {
+        let wordcloud = WordCloud::default().with_word_margin(0);
+        let small = wordcloud.text_dimensions_at_font_size("apple", PxScale::from(20.0));
+        let large = wordcloud.text_dimensions_at_font_size("apple", PxScale::from(40.0));
+        assert!(small.width.abs_diff(large.width / 2) <= 1);
+        assert!(small.height.abs_diff(large.height / 2) <= 1);
+
+        // 按估计的大小生成，词都放得下
+        let (width, height) = wordcloud.estimate_canvas_size(TEXT, 10);
+        let wordcloud = wordcloud.with_rng_seed(1);
+        let layout = wordcloud
+            .generate_layout_from_text(TEXT, WordCloudSize::FromDimensions { width, height })
+            .unwrap();
+        assert_eq!(layout.words.len(), 7);
+        assert!(layout.dropped.is_empty());
+    }
+
+    #[test]
+    fn multi_line_word() {
+        let frequencies = HashMap::from(
+            [
+                ("apple", 12),
+                ("banana", 10),
+                ("cherry", 9),
+                ("date", 8),
+                ("elderberry", 7),
+                ("fig", 6),
+                ("two\nlines", 6),
+                ("grape", 5),
+                ("kiwi", 4),
+                ("lemon", 3),
+                ("mango", 2),
+            ]
+            .map(|(word, count)| (word.to_string(), count)),
+        );
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(0)
+            .with_word_margin(0)
+            .with_text_align(Align::Center);
+        let layout = wordcloud
+            .generate_layout_from_frequencies(
+                &frequencies,
+                WordCloudSize::FromDimensions {
+                    width: 300,
+                    height: 200,
+                },
+            )
+            .unwrap();
+
+        let ink = |word: &Word| {
+            let mut buffer = GrayImage::new(300, 200);
+            text::draw_glyphs_to_gray_buffer(
+                &mut buffer,
+                &mut text::DrawContext::new(0),
+                word.glyphs.clone(),
+                word.font,
+                word.position,
+                word.angle,
+                Luma([1]),
+                0.5,
+            );
+            buffer
+        };
+        let two_lines = layout
+            .words
+            .iter()
+            .find(|word| word.text == "two\nlines")
+            .unwrap();
+        let one_line = text::text_to_glyphs(
+            "two",
+            two_lines.font,
+            two_lines.font_size,
+            wordcloud.text_options(),
+        );
+        assert!(two_lines.glyphs.height >= one_line.height * 2 - 1);
+
+        // 第二行也占了位置，别的词不会画在上面
+        let two_lines_ink = ink(two_lines);
+        assert!(two_lines_ink.pixels().any(|px| px.0[0] != 0));
+        for word in layout.words.iter().filter(|word| word.text != "two\nlines") {
+            let overlap = ink(word)
+                .as_raw()
+                .iter()
+                .zip(two_lines_ink.as_raw())
+                .filter(|(a, b)| **a != 0 && **b != 0)
+                .count();
+            assert_eq!(overlap, 0, "{}", word.text);
+        }
+    }
+
+    #[test]
+    fn preview_scale() {
+        let size = || WordCloudSize::FromDimensions {
+            width: 400,
+            height: 200,
+        };
+        let wordcloud = || {
+            WordCloud::default()
+                .with_rng_seed(1)
+                .with_word_rotate_chance(0.0)
+        };
+        let (full, preview) = (wordcloud(), wordcloud().with_preview_scale(0.5));
+        let full = full.generate_layout_from_text(TEXT, size()).unwrap();
+        let preview = preview.generate_layout_from_text(TEXT, size()).unwrap();
+
+        // 位置和字号映射回原画布
+        assert_eq!((preview.width, preview.height), (400, 200));
+        assert_eq!(preview.words.len(), full.words.len());
+        let (preview_size, full_size) = (preview.words[0].font_size.y, full.words[0].font_size.y);
+        assert!((preview_size - full_size).abs() <= full_size * 0.1);
+        for word in &preview.words {
+            assert!(word.position.x >= 0.0 && word.position.y >= 0.0);
+            assert!(word.position.x + word.glyphs.width as f32 <= 402.0);
+            assert!(word.position.y + word.glyphs.height as f32 <= 202.0);
+        }
+        // 映射回来以后字形基本不重叠
+        let mut covered = vec![0u8; 400 * 200];
+        for word in &preview.words {
+            let mut buffer = GrayImage::new(400, 200);
+            text::draw_glyphs_to_gray_buffer(
+                &mut buffer,
+                &mut text::DrawContext::new(0),
+                word.glyphs.clone(),
+                word.font,
+                word.position,
+                word.angle,
+                Luma([1]),
+                0.5,
+            );
+            for (count, px) in covered.iter_mut().zip(buffer.as_raw()) {
+                *count += px;
+            }
+        }
+        let ink = covered.iter().filter(|count| **count > 0).count();
+        let overlap = covered.iter().filter(|count| **count > 1).count();
+        assert!(overlap * 100 < ink, "{overlap} {ink}");
+    }
+
+    #[test]
+    fn font_size_schedule() {
+        let wordcloud = WordCloud::default().with_relative_font_scaling(0.5);
+        let schedule = wordcloud.font_size_schedule("apple apple apple apple pear pear plum", 200);
+
+        assert_eq!(
+            schedule,
+            vec![
+                ("apple".to_string(), 110.0),
+                ("pear".to_string(), 82.5),
+                ("plum".to_string(), 61.875),
+            ]
+        );
+
+        let capped = wordcloud
+            .with_max_font_size(Some(90.0))
+            .font_size_schedule("apple apple apple apple pear pear plum", 200);
+        assert_eq!(capped[0].1, 90.0);
+        assert!(capped.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+
+        assert!(WordCloud::default().font_size_schedule("", 200).is_empty());
+    }
+
+    #[test]
+    fn placement_candidates_with_mask() {
+        // 四周挡住的遮罩，只有中间能放字
+        let mask = || {
+            WordCloudSize::FromMask(GrayImage::from_fn(400, 240, |x, y| {
+                if (20..380).contains(&x) && (20..220).contains(&y) {
+                    Luma([0])
+                } else {
+                    Luma([255])
+                }
+            }))
+        };
+        // 每个词的中心到最近的词的中心的平均距离
+        let mean_gap = |wordcloud: &WordCloud| {
+            let layout = wordcloud.generate_layout_from_text(TEXT, mask()).unwrap();
+            let centers = layout
+                .words
+                .iter()
+                .map(|word| {
+                    let (width, height) =
+                        text::rotated_dimensions(word.glyphs.width, word.glyphs.height, word.angle);
+                    (
+                        word.position.x + width as f32 / 2.0,
+                        word.position.y + height as f32 / 2.0,
+                    )
+                })
+                .collect::<Vec<_>>();
+            let nearest = centers.iter().enumerate().map(|(i, (x, y))| {
+                centers
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .map(|(_, (other_x, other_y))| (x - other_x).hypot(y - other_y))
+                    .fold(f32::MAX, f32::min)
+            });
+            nearest.sum::<f32>() / centers.len() as f32
+        };
+
+        // 字小、空的地方多。遮罩的边不算被词占了，词还是挤在一起，不会都贴到遮罩边上
+        let wordcloud = || {
+            WordCloud::default()
+                .with_rng_seed(2)
+                .with_max_font_size(Some(30.0))
+        };
+        let random = wordcloud();
+        let tight = wordcloud().with_placement_candidates(20);
+        assert!(mean_gap(&tight) < mean_gap(&random) * 0.75);
+    }
+
+    #[test]
+    fn balance_across_regions() {
+        // 中间隔开的两个一样大的区域
+        let mask = GrayImage::from_fn(400, 200, |x, _| {
+            Luma([if (180..220).contains(&x) { 255 } else { 0 }])
+        });
+        let text = (0..6)
+            .map(|i| format!("word{i} ").repeat(8 - i))
+            .collect::<String>();
+        // 每个词在不在右边，和右边占的面积比例
+        let sides = |wordcloud: WordCloud| {
+            let layout = wordcloud
+                .generate_layout_from_text(&text, WordCloudSize::FromMask(mask.clone()))
+                .unwrap();
+            let (mut right_side, mut left, mut right) = (vec![], 0.0, 0.0);
+            for word in &layout.words {
+                let (width, height) =
+                    text::rotated_dimensions(word.glyphs.width, word.glyphs.height, word.angle);
+                let area = (width * height) as f32;
+                let is_right = word.position.x + width as f32 / 2.0 >= 200.0;
+                right_side.push(is_right);
+                if is_right {
+                    right += area;
+                } else {
+                    left += area;
+                }
+            }
+            (right_side, right / (left + right))
+        };
+
+        let (mut plain_off, mut balanced_off) = (0.0, 0.0);
+        for seed in 0..6 {
+            let (_, plain) = sides(
+                WordCloud::default()
+                    .with_first_word_height_fraction(0.55)
+                    .with_rng_seed(seed),
+            );
+            let (right_side, balanced) = sides(
+                WordCloud::default()
+                    .with_first_word_height_fraction(0.55)
+                    .with_rng_seed(seed)
+                    .with_balance_across_regions(true),
+            );
+            // 最大的两个词分到两边
+            assert_ne!(right_side[0], right_side[1], "{seed}");
+            plain_off += (plain - 0.5f32).abs();
+            balanced_off += (balanced - 0.5f32).abs();
+        }
+        assert!(
+            balanced_off < plain_off * 0.75,
+            "{balanced_off} {plain_off}"
+        );
+    }
+
+    #[test]
+    fn mask_threshold() {
+        // 左半边纯黑，右半边是抗锯齿留下的深灰
+        let mask = GrayImage::from_fn(
+            400,
+            200,
+            |x, _| if x < 200 { Luma([0]) } else { Luma([20]) },
+        );
+        let size = || WordCloudSize::FromMask(mask.clone());
+        let first_word = |wordcloud: WordCloud| {
+            let layout = wordcloud.generate_layout_from_text(TEXT, size()).unwrap();
+            let right = layout
+                .words
+                .iter()
+                .map(|word| word.position.x + word.glyphs.width as f32)
+                .fold(0.0, f32::max);
+            (layout.words[0].font_size.y, right)
+        };
+
+        let (black_only, black_only_right) = first_word(
+            WordCloud::default()
+                .with_first_word_height_fraction(0.55)
+                .with_rng_seed(1),
+        );
+        let (thresholded, thresholded_right) = first_word(
+            WordCloud::default()
+                .with_first_word_height_fraction(0.55)
+                .with_rng_seed(1)
+                .with_mask_threshold(32),
+        );
+        assert!(black_only_right <= 200.0);
+        assert!(thresholded_right > 200.0);
+        // 可用面积翻倍，起始字号不再减半
+        assert!(thresholded > black_only * 1.5);
+
+        // 没有遮罩时不按黑色像素缩小
+        let blank = WordCloud::default()
+            .with_first_word_height_fraction(0.55)
+            .with_rng_seed(1);
+        let excluded = WordCloud::default()
+            .with_first_word_height_fraction(0.55)
+            .with_rng_seed(1)
+            .with_exclusion_mask(GrayImage::from_fn(400, 200, |_, y| {
+                Luma([(y >= 150) as u8])
+            }));
+        let dimensions = || WordCloudSize::FromDimensions {
+            width: 400,
+            height: 200,
+        };
+        let font_size = |wordcloud: &WordCloud| {
+            wordcloud
+                .generate_layout_from_text(TEXT, dimensions())
+                .unwrap()
+                .words[0]
+                .font_size
+                .y
+        };
+        assert_eq!(font_size(&blank), font_size(&excluded));
+    }
+
+    #[test]
+    fn visual_and_collision_margin() {
+        let wordcloud = WordCloud::default()
+            .with_collision_margin(20)
+            .with_visual_margin(4);
+        let glyphs = text::text_to_glyphs(
+            "apple",
+            &wordcloud.font,
+            PxScale::from(40.0),
+            wordcloud.text_options(),
+        );
+        let (rect, offset) = wordcloud.reserved_rect(&glyphs, 0.0, 0.0);
+        assert_eq!(
+            (rect.width, rect.height),
+            (glyphs.width + 20, glyphs.height + 20)
+        );
+        assert_eq!((offset.x, offset.y), (2.0, 2.0));
+        let rect = wordcloud.text_dimensions_at_font_size("apple", PxScale::from(40.0));
+        assert_eq!(rect.width, glyphs.width + 20);
+
+        // 画的位置不会超出占的位置
+        let (_, offset) = wordcloud
+            .with_visual_margin(40)
+            .reserved_rect(&glyphs, 0.0, 0.0);
+        assert_eq!((offset.x, offset.y), (10.0, 10.0));
+
+        // 只改画的位置时找到的空间一样，第一个词只是挪了半个间距
+        let first_position = |visual_margin| {
+            WordCloud::default()
+                .with_rng_seed(1)
+                .with_collision_margin(20)
+                .with_visual_margin(visual_margin)
+                .generate_layout_from_text(
+                    TEXT,
+                    WordCloudSize::FromDimensions {
+                        width: 400,
+                        height: 200,
+                    },
+                )
+                .unwrap()
+                .words[0]
+                .position
+        };
+        let (near, far) = (first_position(0), first_position(20));
+        assert_eq!((far.x - near.x, far.y - near.y), (10.0, 10.0));
+    }
+
+    #[test]
+    fn tight_margin() {
+        let font_size = PxScale::from(40.0);
+        let loose = WordCloud::default().with_word_margin(0);
+        let tight = WordCloud::default().with_tight_margin(-6);
+        let loose_rect = loose.text_dimensions_at_font_size("苹果", font_size);
+        let tight_rect = tight.text_dimensions_at_font_size("苹果", font_size);
+        assert_eq!(tight_rect.width, loose_rect.width - 6);
+        assert_eq!(tight_rect.height, loose_rect.height - 6);
+
+        // 占的位置不会小于 1 个像素
+        let too_tight = WordCloud::default().with_tight_margin(-1000);
+        let rect = too_tight.text_dimensions_at_font_size("苹果", font_size);
+        assert_eq!((rect.width, rect.height), (1, 1));
+
+        let size = || WordCloudSize::FromDimensions {
+            width: 400,
+            height: 200,
+        };
+        let placed = |wordcloud: WordCloud| {
+            wordcloud
+                .with_rng_seed(1)
+                .with_tokenizer(
+                    ChineseTokenizer::default()
+                        .with_repeat(true)
+                        .with_max_words(80),
+                )
+                .generate_layout_from_text(TEXT, size())
+                .unwrap()
+                .words
+                .len()
+        };
+        assert!(placed(WordCloud::default().with_tight_margin(-4)) >= placed(WordCloud::default()));
+    }
+
+    #[test]
+    fn frequency_scaled_margin() {
+        let wordcloud = WordCloud::default().with_frequency_scaled_margin(2, 40.0);
+        let glyphs = text::text_to_glyphs(
+            "apple",
+            &wordcloud.font,
+            PxScale::from(40.0),
+            wordcloud.text_options(),
+        );
+        let (rare, rare_offset) = wordcloud.reserved_rect(&glyphs, 0.0, 0.0);
+        let (half, half_offset) = wordcloud.reserved_rect(&glyphs, 0.0, 0.5);
+        let (frequent, frequent_offset) = wordcloud.reserved_rect(&glyphs, 0.0, 1.0);
+        assert_eq!(rare.width, glyphs.width + 2);
+        assert_eq!(half.width, glyphs.width + 22);
+        assert_eq!(frequent.height, glyphs.height + 42);
+        assert_eq!(rare_offset.x, 1.0);
+        assert_eq!(half_offset.x, 11.0);
+        assert_eq!(frequent_offset.y, 21.0);
+
+        let size = || WordCloudSize::FromDimensions {
+            width: 400,
+            height: 200,
+        };
+        let positions = |wordcloud: WordCloud| {
+            wordcloud
+                .with_rng_seed(1)
+                .generate_layout_from_text(TEXT, size())
+                .unwrap()
+                .words
+                .iter()
+                .map(|word| word.position)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            positions(WordCloud::default().with_frequency_scaled_margin(2, 0.0)),
+            positions(WordCloud::default().with_word_margin(2))
+        );
+        assert_ne!(
+            positions(WordCloud::default().with_frequency_scaled_margin(2, 40.0)),
+            positions(WordCloud::default().with_word_margin(2))
+        );
+
+        assert!(WordCloud::builder()
+            .frequency_scaled_margin(2, -1.0)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn first_word_too_large() {
+        let text = "supercalifragilisticexpialidocious supercalifragilisticexpialidocious fig";
+        let layout = |width, height, drop_policy| {
+            WordCloud::default()
+                .with_rng_seed(1)
+                .with_min_font_size(10.0)
+                .with_drop_policy(drop_policy)
+                .generate_layout_from_text(text, WordCloudSize::FromDimensions { width, height })
+                .map(|layout| {
+                    let words = layout
+                        .words
+                        .iter()
+                        .map(|word| word.text)
+                        .collect::<Vec<_>>();
+                    (words.join(" "), layout.dropped)
+                })
+        };
+        assert!(matches!(
+            layout(60, 40, DropPolicy::Error),
+            Err(WordCloudError::WordTooLarge(word)) if word == "supercalifragilisticexpialidocious"
+        ));
+        // 默认丢掉放不下的词，放剩下的
+        assert_eq!(
+            layout(60, 40, DropPolicy::Silent).unwrap(),
+            (
+                "fig".to_string(),
+                vec!["supercalifragilisticexpialidocious".to_string()]
+            )
+        );
+        // 竖着能放下就不报错
+        assert!(layout(40, 400, DropPolicy::Error).unwrap().1.is_empty());
+        assert!(layout(400, 40, DropPolicy::Error).unwrap().1.is_empty());
+    }
+
+    #[test]
+    fn drop_policy() {
+        let size = || WordCloudSize::FromDimensions {
+            width: 200,
+            height: 100,
+        };
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_min_font_size(24.0);
+        let dropped = wordcloud
+            .generate_layout_from_text(TEXT, size())
+            .unwrap()
+            .dropped;
+        assert!(!dropped.is_empty());
+
+        // 同样的种子报错时列出的词和默认丢掉的一样
+        let strict = wordcloud.with_drop_policy(DropPolicy::Error);
+        assert!(matches!(
+            strict.generate_layout_from_text(TEXT, size()),
+            Err(WordCloudError::WordsDropped(words)) if words == dropped
+        ));
+        let roomy = WordCloudSize::FromDimensions {
+            width: 800,
+            height: 400,
+        };
+        assert!(strict.generate_layout_from_text(TEXT, roomy).is_ok());
+    }
+
+    #[test]
+    fn equal_area() {
+        let wordcloud = WordCloud::default().with_size_strategy(SizeStrategy::EqualArea);
+        let schedule: HashMap<_, _> = wordcloud
+            .font_size_schedule(TEXT, 200)
+            .into_iter()
+            .collect();
+        let area = |word: &str| {
+            let rect = wordcloud.text_dimensions_at_font_size(word, PxScale::from(schedule[word]));
+            rect.width as f32 * rect.height as f32
+        };
+        // 词频一样时短的词字号更大，外框面积都差不多
+        assert!(schedule["fig"] > schedule["elderberry"]);
+        for word in ["banana", "fig", "elderberry"] {
+            assert!((area(word) / area("apple") - 1.0).abs() < 0.1, "{word}");
+        }
+        let by_frequency: HashMap<_, _> = WordCloud::default()
+            .font_size_schedule(TEXT, 200)
+            .into_iter()
+            .collect();
+        assert_eq!(by_frequency["fig"], by_frequency["elderberry"]);
+
+        let wordcloud = wordcloud.with_rng_seed(1);
+        let layout = wordcloud
+            .generate_layout_from_text(
+                TEXT,
+                WordCloudSize::FromDimensions {
+                    width: 800,
+                    height: 400,
+                },
+            )
+            .unwrap();
+        let font_size = |text| {
+            layout
+                .words
+                .iter()
+                .find(|word| word.text == text)
+                .unwrap()
+                .font_size
+                .y
+        };
+        assert!(font_size("fig") > font_size("elderberry"));
+    }
+
+    #[test]
+    fn auto_enlarge() {
+        let size = || WordCloudSize::FromDimensions {
+            width: 400,
+            height: 200,
+        };
+        let text = "apple apple apple banana banana cherry";
+
+        let sparse = WordCloud::default()
+            .with_rng_seed(1)
+            .with_first_word_height_fraction(0.1);
+        let enlarged = WordCloud::default()
+            .with_rng_seed(1)
+            .with_first_word_height_fraction(0.1)
+            .with_auto_enlarge(true);
+        let sparse = sparse.generate_layout_from_text(text, size()).unwrap();
+        let enlarged = enlarged.generate_layout_from_text(text, size()).unwrap();
+
+        assert_eq!(enlarged.words.len(), sparse.words.len());
+        assert!(enlarged.dropped.is_empty());
+        assert!(enlarged.words[0].font_size.y > sparse.words[0].font_size.y * 1.5);
+        assert!(layout_score(&enlarged, 400, 200) > layout_score(&sparse, 400, 200));
+    }
+
+    #[test]
+    fn shape_bias() {
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_transparent_background()
+            .with_shape_bias(ShapeBias::Ellipse);
+        let image = wordcloud.generate_from_text(
+            TEXT,
+            WordCloudSize::FromDimensions {
+                width: 400,
+                height: 200,
+            },
+            1.0,
+        );
+
+        // 椭圆外面没有字
+        let mut inked = 0;
+        for (x, y, px) in image.enumerate_pixels() {
+            if px.0[3] == 0 {
+                continue;
+            }
+            inked += 1;
+            let dx = (x as f32 + 0.5 - 200.0) / 200.0;
+            let dy = (y as f32 + 0.5 - 100.0) / 100.0;
+            assert!(dx * dx + dy * dy <= 1.0, "{x} {y}");
+        }
+        assert!(inked > 0);
+    }
+
+    #[test]
+    fn from_mask_scaled() {
+        let mask = GrayImage::from_pixel(100, 50, Luma([0]));
+        let wordcloud = WordCloud::default().with_rng_seed(1);
+
+        let layout = wordcloud
+            .generate_layout_from_text(
+                TEXT,
+                WordCloudSize::FromMaskScaled {
+                    mask: mask.clone(),
+                    max_dimension: 400,
+                },
+            )
+            .unwrap();
+        assert_eq!((layout.width, layout.height), (400, 200));
+
+        assert!(matches!(
+            wordcloud.generate_layout_from_text(
+                TEXT,
+                WordCloudSize::FromMaskScaled {
+                    mask,
+                    max_dimension: 0,
+                },
+            ),
+            Err(WordCloudError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn from_mask_scaled_checks_canvas_size() {
+        let mask = GrayImage::from_pixel(100, 50, Luma([0]));
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_max_canvas_bytes(400 * 200 * 4);
+        let size = |max_dimension| WordCloudSize::FromMaskScaled {
+            mask: mask.clone(),
+            max_dimension,
+        };
+
+        assert!(wordcloud.generate_layout_from_text(TEXT, size(400)).is_ok());
+        // 缩放以前就报错，不会先分配一张很大的遮罩
+        assert!(matches!(
+            wordcloud.generate_layout_from_text(TEXT, size(200_000)),
+            Err(WordCloudError::CanvasTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn canvas_too_large() {
+        let size = WordCloudSize::FromDimensions {
+            width: 200,
+            height: 100,
+        };
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_max_canvas_bytes(200 * 100 * 4);
+
+        let layout = wordcloud.generate_layout_from_text(TEXT, size).unwrap();
+        assert!(wordcloud
+            .try_render_layout(&layout, 1.0, |_, _| image::Rgba([255; 4]))
+            .is_ok());
+        assert!(matches!(
+            wordcloud.try_render_layout(&layout, 2.0, |_, _| image::Rgba([255; 4])),
+            Err(WordCloudError::CanvasTooLarge { .. })
+        ));
+        assert!(matches!(
+            wordcloud.try_render_layout(&layout, f32::MAX, |_, _| image::Rgba([255; 4])),
+            Err(WordCloudError::CanvasTooLarge { .. })
+        ));
+
+        let size = WordCloudSize::FromDimensions {
+            width: 400,
+            height: 200,
+        };
+        assert!(matches!(
+            wordcloud.generate_layout_from_text(TEXT, size),
+            Err(WordCloudError::CanvasTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn path_word_is_reserved() {
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_path_word(PathWord {
+                text: "banana banana banana".to_string(),
+                font_size: 40.0,
+                path: WordPath::Polyline(vec![point(0.0, 120.0), point(400.0, 120.0)]),
+                color: image::Rgba([255; 4]),
+            });
+        let layout = wordcloud
+            .generate_layout_from_text(
+                TEXT,
+                WordCloudSize::FromDimensions {
+                    width: 400,
+                    height: 200,
+                },
+            )
+            .unwrap();
+
+        // 基线上方的一条，所有词都不能压在上面
+        assert!(!layout.words.is_empty());
+        for word in &layout.words {
+            let height = if word.rotated {
+                word.glyphs.width
+            } else {
+                word.glyphs.height
+            };
+            let (top, bottom) = (word.position.y, word.position.y + height as f32);
+            assert!(bottom <= 105.0 || top >= 120.0, "{} overlaps", word.text);
+        }
+    }
+
+    #[test]
+    fn frequency_font_variation() {
+        assert_eq!(super::axis_tag("wght"), *b"wght");
+        assert_eq!(super::axis_tag("ab"), *b"ab  ");
+
+        let wordcloud = WordCloud::default();
+        assert!(std::ptr::eq(
+            wordcloud.font_for_frequency(1.0),
+            &wordcloud.font
+        ));
+
+        let wordcloud = wordcloud.with_frequency_font_variation("wght", 300.0, 900.0);
+        let heaviest = wordcloud.variation_fonts.last().unwrap();
+        let lightest = wordcloud.variation_fonts.first().unwrap();
+        assert_eq!(wordcloud.variation_fonts.len(), 5);
+        assert!(std::ptr::eq(wordcloud.font_for_frequency(1.0), heaviest));
+        assert!(std::ptr::eq(wordcloud.font_for_frequency(0.05), lightest));
+    }
+
+    #[test]
+    fn relocate_before_shrink() {
+        // 宽矮的画布，竖着放只能用很小的字号
+        let size = || WordCloudSize::FromDimensions {
+            width: 400,
+            height: 40,
+        };
+        let text = "banana";
+
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_rotation_decider(|_, _| true);
+        let layout = wordcloud.generate_layout_from_text(text, size()).unwrap();
+        assert!(layout.words[0].rotated);
+        assert!(layout.words[0].font_size.y < 20.0);
+
+        let wordcloud = wordcloud.with_relocate_before_shrink_below(20.0);
+        let layout = wordcloud.generate_layout_from_text(text, size()).unwrap();
+        assert!(!layout.words[0].rotated);
+        assert!(layout.words[0].font_size.y >= 20.0);
+    }
+
+    #[test]
+    fn placement_candidates() {
+        let size = || WordCloudSize::FromDimensions {
+            width: 400,
+            height: 200,
+        };
+        // 每个词的中心到最近的词的中心的平均距离
+        let mean_gap = |wordcloud: &WordCloud| {
+            let layout = wordcloud.generate_layout_from_text(TEXT, size()).unwrap();
+            let centers = layout
+                .words
+                .iter()
+                .map(|word| {
+                    let (width, height) =
+                        text::rotated_dimensions(word.glyphs.width, word.glyphs.height, word.angle);
+                    (
+                        word.position.x + width as f32 / 2.0,
+                        word.position.y + height as f32 / 2.0,
+                    )
+                })
+                .collect::<Vec<_>>();
+            let nearest = centers.iter().enumerate().map(|(i, (x, y))| {
+                centers
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .map(|(_, (other_x, other_y))| (x - other_x).hypot(y - other_y))
+                    .fold(f32::MAX, f32::min)
+            });
+            nearest.sum::<f32>() / centers.len() as f32
+        };
+        let positions = |wordcloud: &WordCloud| {
+            let layout = wordcloud.generate_layout_from_text(TEXT, size()).unwrap();
+            layout
+                .words
+                .iter()
+                .map(|word| word.position)
+                .collect::<Vec<_>>()
+        };
+
+        let random = WordCloud::default()
+            .with_first_word_height_fraction(0.55)
+            .with_rng_seed(1);
+        let one = WordCloud::default()
+            .with_first_word_height_fraction(0.55)
+            .with_rng_seed(1)
+            .with_placement_candidates(1);
+        assert_eq!(positions(&random), positions(&one));
+
+        let tight = WordCloud::default()
+            .with_first_word_height_fraction(0.55)
+            .with_rng_seed(1)
+            .with_placement_candidates(20);
+        assert!(mean_gap(&tight) < mean_gap(&random));
+    }
+
+    #[test]
+    fn placement_filter() {
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_placement_filter(|_, position, rect| position.y + rect.height as f32 <= 100.0);
+        let layout = wordcloud
+            .generate_layout_from_text(
+                TEXT,
+                WordCloudSize::FromDimensions {
+                    width: 400,
+                    height: 200,
+                },
+            )
+            .unwrap();
+
+        assert!(!layout.words.is_empty());
+        for word in &layout.words {
+            let height = if word.rotated {
+                word.glyphs.width
+            } else {
+                word.glyphs.height
+            };
+            assert!(word.position.y + height as f32 <= 100.0);
+        }
+    }
+
+    #[test]
+    fn repeat_max_copies() {
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_tokenizer(
+                ChineseTokenizer::default()
+                    .with_repeat(true)
+                    .with_max_words(60),
+            )
+            .with_repeat_max_copies(2);
+        let layout = wordcloud
+            .generate_layout_from_text(
+                TEXT,
+                WordCloudSize::FromDimensions {
+                    width: 400,
+                    height: 200,
+                },
+            )
+            .unwrap();
+
+        let mut copies = std::collections::HashMap::new();
+        for word in &layout.words {
+            *copies.entry(word.text).or_insert(0) += 1;
+        }
+        assert!(copies.values().all(|n| *n <= 2));
+        assert!(copies.values().any(|n| *n == 2));
+    }
+
+    #[test]
+    fn min_same_word_distance() {
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_tokenizer(
+                ChineseTokenizer::default()
+                    .with_repeat(true)
+                    .with_max_words(40),
+            )
+            .with_min_same_word_distance(30.0);
+        let layout = wordcloud
+            .generate_layout_from_text(
+                TEXT,
+                WordCloudSize::FromDimensions {
+                    width: 400,
+                    height: 200,
+                },
+            )
+            .unwrap();
+
+        let bounds = |word: &Word| {
+            let (width, height) =
+                text::rotated_dimensions(word.glyphs.width, word.glyphs.height, word.angle);
+            (word.position, width as f32, height as f32)
+        };
+        let mut copies = 0;
+        for (i, a) in layout.words.iter().enumerate() {
+            for b in layout.words[i + 1..].iter().filter(|b| b.text == a.text) {
+                let ((pa, wa, ha), (pb, wb, hb)) = (bounds(a), bounds(b));
+                let dx = (pb.x - (pa.x + wa)).max(pa.x - (pb.x + wb)).max(0.0);
+                let dy = (pb.y - (pa.y + ha)).max(pa.y - (pb.y + hb)).max(0.0);
+                assert!(dx.hypot(dy) >= 30.0);
+                copies += 1;
+            }
+        }
+        assert!(copies > 0);
+    }
+
+    #[test]
+    fn deterministic_seed() {
+        let size = || WordCloudSize::FromDimensions {
+            width: 400,
+            height: 200,
+        };
+        let wordcloud = WordCloud::default().with_deterministic_seed(true);
+        let positions = |text| {
+            wordcloud
+                .generate_layout_from_text(text, size())
+                .unwrap()
+                .words
+                .iter()
+                .map(|word| (word.text.to_string(), word.position))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(positions(TEXT), positions(TEXT));
+        assert_ne!(positions(TEXT), positions("apple banana banana cherry"));
+        // 种子在不同的编译之间必须一样
+        assert_eq!(
+            wordcloud.content_seed(&[("apple", 1.0)], 400, 200),
+            0x6712d67a54f7d877
+        );
+    }
+
+    #[test]
+    fn generate_raw() {
+        let wordcloud = WordCloud::default().with_rng_seed(1);
+        let size = || WordCloudSize::FromDimensions {
+            width: 200,
+            height: 100,
+        };
+
+        let (pixels, width, height) = wordcloud.generate_raw(TEXT, size(), 1.5);
+        assert_eq!((width, height), (300, 150));
+        assert_eq!(pixels.len(), 300 * 150 * 4);
+        assert_eq!(
+            pixels,
+            wordcloud.generate_from_text(TEXT, size(), 1.5).into_raw()
+        );
+    }
+
+    #[test]
+    fn boosted_word_renders_large() {
+        let tokenizer = ChineseTokenizer::default()
+            .with_max_words(3)
+            .with_word_weight("grape", 10.0);
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_tokenizer(tokenizer);
+        let layout = wordcloud
+            .generate_layout_from_text(
+                TEXT,
+                WordCloudSize::FromDimensions {
+                    width: 400,
+                    height: 200,
+                },
+            )
+            .unwrap();
+
+        let grape = layout
+            .words
+            .iter()
+            .find(|word| word.text == "grape")
+            .unwrap();
+        assert!(layout
+            .words
+            .iter()
+            .all(|word| word.font_size.y <= grape.font_size.y));
+    }
+
+    #[test]
+    fn smallest_first() {
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_placement_order(PlacementOrder::SmallestFirst);
+        let layout = wordcloud
+            .generate_layout_from_text(
+                TEXT,
+                WordCloudSize::FromDimensions {
+                    width: 400,
+                    height: 200,
+                },
+            )
+            .unwrap();
+
+        assert_eq!(layout.words.len(), 7);
+        assert_eq!(layout.words.first().unwrap().text, "grape");
+        assert_eq!(layout.words.last().unwrap().text, "apple");
+        assert!(layout.words.last().unwrap().font_size.y > layout.words[0].font_size.y);
+    }
+
+    #[test]
+    fn replace_word() {
+        let wordcloud = WordCloud::default().with_rng_seed(1);
+        let mut layout = wordcloud
+            .generate_layout_from_text(
+                TEXT,
+                WordCloudSize::FromDimensions {
+                    width: 400,
+                    height: 200,
+                },
+            )
+            .unwrap();
+        let before = layout
+            .words
+            .iter()
+            .map(|word| (word.text, word.position))
+            .collect::<Vec<_>>();
+
+        wordcloud.replace_word(&mut layout, 0, "app").unwrap();
+        assert_eq!(layout.words[0].text, "app");
+        for (word, (text, position)) in layout.words.iter().zip(&before).skip(1) {
+            assert_eq!((word.text, word.position), (*text, *position));
+        }
+
+        let too_long = "x".repeat(200);
+        assert!(wordcloud
+            .replace_word(&mut layout, 1, too_long.as_str())
+            .is_err());
+        assert_eq!(layout.words[1].text, before[1].0);
+    }
+
+    /// 把每个词依次换成别的文字，数别的词还有多少像素在占位图上被当成空的
+    fn pixels_freed_by_replace_word(wordcloud: &WordCloud) -> usize {
+        let mut layout = wordcloud
+            .generate_layout_from_text(
+                TEXT,
+                WordCloudSize::FromDimensions {
+                    width: 400,
+                    height: 200,
+                },
+            )
+            .unwrap();
+        let mut freed = 0;
+        for index in 0..layout.words.len() {
+            wordcloud.replace_word(&mut layout, index, "kiwi").unwrap();
+            let snapshot = wordcloud.occupancy_snapshot(&layout);
+            for word in &layout.words {
+                let mut buffer = GrayImage::new(400, 200);
+                text::draw_glyphs_to_gray_buffer(
+                    &mut buffer,
+                    &mut text::DrawContext::new(0),
+                    word.glyphs.clone(),
+                    word.font,
+                    word.position,
+                    word.angle,
+                    Luma([1]),
+                    0.5,
+                );
+                freed += buffer
+                    .as_raw()
+                    .iter()
+                    .zip(snapshot.as_raw())
+                    .filter(|(ink, taken)| **ink != 0 && **taken == 0)
+                    .count();
+            }
+        }
+
+        freed
+    }
+
+    #[test]
+    fn replace_word_keeps_neighbours() {
+        // 阴影和旁边的词重叠，擦掉阴影时不能把旁边的词也擦掉
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(0)
+            .with_word_margin(0)
+            .with_text_shadow(3, 3, 0.0, Rgba([0, 0, 0, 255]));
+        assert_eq!(pixels_freed_by_replace_word(&wordcloud), 0);
+    }
+
+    #[test]
+    fn replace_word_tight_margin() {
+        // 负的间距让外框重叠，换掉一个词时重叠的地方还是旁边的词的
+        let wordcloud = WordCloud::default().with_rng_seed(1).with_tight_margin(-6);
+        assert_eq!(pixels_freed_by_replace_word(&wordcloud), 0);
+    }
+
+    #[test]
+    fn replace_word_constraints() {
+        let size = || WordCloudSize::FromDimensions {
+            width: 400,
+            height: 200,
+        };
+        // "kiwi" 只能放在右半边
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_placement_filter(|word, position, _| word != "kiwi" || position.x >= 200.0);
+        let mut layout = wordcloud.generate_layout_from_text(TEXT, size()).unwrap();
+        let left = layout
+            .words
+            .iter()
+            .position(|word| word.position.x < 100.0)
+            .unwrap();
+        wordcloud.replace_word(&mut layout, left, "kiwi").unwrap();
+        assert!(layout.words[left].position.x >= 200.0);
+
+        // 换成另一个词已经有的文字时也要离得够远
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_min_same_word_distance(50.0);
+        let mut layout = wordcloud.generate_layout_from_text(TEXT, size()).unwrap();
+        let copy = layout.words[2].text;
+        wordcloud.replace_word(&mut layout, 1, copy).unwrap();
+        let (a, b) = (&layout.words[1], &layout.words[2]);
+        let (a_width, a_height) =
+            text::rotated_dimensions(a.glyphs.width, a.glyphs.height, a.angle);
+        let (b_width, b_height) =
+            text::rotated_dimensions(b.glyphs.width, b.glyphs.height, b.angle);
+        let dx = (b.position.x - (a.position.x + a_width as f32))
+            .max(a.position.x - (b.position.x + b_width as f32))
+            .max(0.0);
+        let dy = (b.position.y - (a.position.y + a_height as f32))
+            .max(a.position.y - (b.position.y + b_height as f32))
+            .max(0.0);
+        assert!(dx.hypot(dy) >= 50.0, "{dx} {dy}");
+    }
+
+    #[test]
+    fn font_collection() {
+        // 把自带的字体放两份进一个字体集，表的位置要加上文件头的长度
+        let ttf = include_bytes!("../fonts/Dengb.ttf");
+        let header_len = 12 + 4 * 2;
+        let mut ttc = b"ttcf\0\x01\0\0\0\0\0\x02".to_vec();
+        ttc.extend_from_slice(&(header_len as u32).to_be_bytes());
+        ttc.extend_from_slice(&(header_len as u32).to_be_bytes());
+        let mut font = ttf.to_vec();
+        let tables = u16::from_be_bytes([font[4], font[5]]) as usize;
+        for record in 0..tables {
+            let at = 12 + record * 16 + 8;
+            let offset = u32::from_be_bytes(font[at..at + 4].try_into().unwrap());
+            font[at..at + 4].copy_from_slice(&(offset + header_len as u32).to_be_bytes());
+        }
+        ttc.extend_from_slice(&font);
+
+        assert!(font_from_collection(ttc.clone(), 0).is_ok());
+        assert!(font_from_collection(ttc.clone(), 1).is_ok());
+        assert!(matches!(
+            font_from_collection(ttc.clone(), 2),
+            Err(WordCloudError::InvalidConfig(_))
+        ));
+        assert!(font_from_collection(ttf.to_vec(), 0).is_ok());
+        assert!(font_from_collection(ttf.to_vec(), 1).is_err());
+
+        let path = std::env::temp_dir().join(format!("rust_wcloud_{}.ttc", std::process::id()));
+        std::fs::write(&path, &ttc).unwrap();
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_font_from_path_indexed(&path, 1)
+            .with_frequency_font_variation("wght", 300.0, 900.0);
+        assert_eq!(wordcloud.font_index, 1);
+        let layout = wordcloud.generate_layout_from_text(
+            TEXT,
+            WordCloudSize::FromDimensions {
+                width: 200,
+                height: 100,
+            },
+        );
+        assert!(!layout.unwrap().words.is_empty());
+
+        assert!(WordCloud::builder()
+            .font_from_path_indexed(&path, 1)
+            .build()
+            .is_ok());
+        assert!(matches!(
+            WordCloud::builder()
+                .font_from_path_indexed(&path, 3)
+                .build(),
+            Err(WordCloudError::InvalidConfig(_))
+        ));
+        assert!(WordCloud::builder()
+            .font_from_path_indexed(path.with_extension("missing"), 0)
+            .build()
+            .is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn seed_str() {
+        let seed = |value| WordCloud::default().with_seed_str(value).rng_seed;
+        // FNV-1a 的标准结果，不随版本和平台变
+        assert_eq!(seed(""), Some(0xcbf29ce484222325));
+        assert_eq!(seed("a"), Some(0xaf63dc4c8601ec8c));
+        assert_eq!(seed("foobar"), Some(0x85944171f73967e8));
+        assert_ne!(seed("my-article"), seed("my-articles"));
+
+        let size = || WordCloudSize::FromDimensions {
+            width: 200,
+            height: 100,
+        };
+        let image = |wordcloud: WordCloud| wordcloud.generate_from_text(TEXT, size(), 1.0);
+        assert_eq!(
+            image(WordCloud::default().with_seed_str("alice")),
+            image(WordCloud::default().with_seed_str("alice"))
+        );
+    }
+
+    #[test]
+    fn generate_with_rng() {
+        let size = || WordCloudSize::FromDimensions {
+            width: 200,
+            height: 100,
+        };
+        let color = |_: &Word, _: &mut WyRand| Rgba([255, 255, 255, 255]);
+        // 传进来的 rng 优先，rng_seed 不起作用
+        let wordcloud = WordCloud::default().with_rng_seed(3);
+        let image = wordcloud.generate_from_text_with_rng(
+            TEXT,
+            size(),
+            1.0,
+            &mut WyRand::new_seed(7),
+            color,
+        );
+        assert_eq!(
+            image,
+            wordcloud.generate_from_text_with_rng(
+                TEXT,
+                size(),
+                1.0,
+                &mut WyRand::new_seed(7),
+                color
+            )
+        );
+        assert_eq!(
+            image,
+            WordCloud::default()
+                .with_rng_seed(7)
+                .generate_from_text_with_color_func(TEXT, size(), 1.0, color)
+        );
+        assert_ne!(
+            image,
+            wordcloud.generate_from_text_with_rng(
+                TEXT,
+                size(),
+                1.0,
+                &mut WyRand::new_seed(8),
+                color
+            )
+        );
+
+        // 同一个 rng 接着用，下一张图从上一张停下的地方开始
+        let mut rng = WyRand::new_seed(7);
+        let first = wordcloud.generate_from_text_with_rng(TEXT, size(), 1.0, &mut rng, color);
+        assert_eq!(image, first);
+        assert_ne!(
+            image,
+            wordcloud.generate_from_text_with_rng(TEXT, size(), 1.0, &mut rng, color)
+        );
+
+        let mut rng = WyRand::new_seed(7);
+        let mut layout = wordcloud
+            .generate_layout_from_text_with_rng(TEXT, size(), &mut rng)
+            .unwrap();
+        assert_eq!(rng.generate::<u64>(), layout.rng.generate::<u64>());
+        let seeded = WordCloud::default().with_rng_seed(7);
+        let expected = seeded.generate_layout_from_text(TEXT, size()).unwrap();
+        assert_eq!(layout.words.len(), expected.words.len());
+        for (word, expected) in layout.words.iter().zip(&expected.words) {
+            assert_eq!(
+                (word.text, word.position),
+                (expected.text, expected.position)
+            );
+        }
+    }
+
+    #[test]
+    fn occupancy_snapshot() {
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_transparent_background();
+        let layout = wordcloud
+            .generate_layout_from_text(
+                TEXT,
+                WordCloudSize::FromDimensions {
+                    width: 400,
+                    height: 200,
+                },
+            )
+            .unwrap();
+
+        let snapshot = wordcloud.occupancy_snapshot(&layout);
+        assert_eq!(snapshot.dimensions(), (400, 200));
+        assert!(snapshot.pixels().all(|px| px.0[0] == 0 || px.0[0] == 255));
+        // 画出来的字都在占用的地方
+        let image = wordcloud.render_layout(&layout, 1.0, |_, _| Rgba([255, 255, 255, 255]));
+        for (x, y, px) in image.enumerate_pixels() {
+            if px.0[3] > 32 {
+                assert_eq!(snapshot.get_pixel(x, y), &Luma([255]), "{x} {y}");
+            }
+        }
+        let taken = snapshot.pixels().filter(|px| px.0[0] == 255).count();
+        assert!(taken > 0 && taken < snapshot.len());
+    }
+
+    #[test]
+    fn shadow_blur_occupancy() {
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_transparent_background()
+            .with_text_shadow(4, 4, 3.0, Rgba([0, 0, 0, 255]));
+        let layout = wordcloud
+            .generate_layout_from_text(
+                TEXT,
+                WordCloudSize::FromDimensions {
+                    width: 400,
+                    height: 200,
+                },
+            )
+            .unwrap();
+
+        // 模糊出去的阴影也在占用的地方，别的词不会压在上面
+        let snapshot = wordcloud.occupancy_snapshot(&layout);
+        let image = wordcloud.render_layout(&layout, 1.0, |_, _| Rgba([255, 255, 255, 255]));
+        for (x, y, px) in image.enumerate_pixels() {
+            if px.0[3] > 32 {
+                assert_eq!(snapshot.get_pixel(x, y), &Luma([255]), "{x} {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn rotation_decider() {
+        let calls = Arc::new(Mutex::new(vec![]));
+        let recorded = calls.clone();
+        // 概率是 1 也只转长的词
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_word_rotate_chance(1.0)
+            .with_rotation_decider(move |word, freq| {
+                recorded.lock().unwrap().push((word.to_string(), freq));
+                word.chars().count() > 4
+            });
+        let layout = wordcloud
+            .generate_layout_from_text(
+                TEXT,
+                WordCloudSize::FromDimensions {
+                    width: 600,
+                    height: 400,
+                },
+            )
+            .unwrap();
+
+        assert_eq!(layout.words.len(), 7);
+        for word in &layout.words {
+            assert_eq!(word.rotated, word.text.chars().count() > 4, "{}", word.text);
+        }
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 7);
+        assert_eq!(calls[0], ("apple".to_string(), 1.0));
+    }
+
+    #[test]
+    fn bidirectional_rotation() {
+        let angles = |bidirectional: bool| {
+            let wordcloud = WordCloud::default()
+                .with_rng_seed(1)
+                .with_rotation_decider(|_, _| true)
+                .with_bidirectional_rotation(bidirectional);
+            let layout = wordcloud
+                .generate_layout_from_text(
+                    TEXT,
+                    WordCloudSize::FromDimensions {
+                        width: 400,
+                        height: 200,
+                    },
+                )
+                .unwrap();
+            layout
+                .words
+                .iter()
+                .filter(|word| word.rotated)
+                .map(|word| word.angle)
+                .collect::<Vec<_>>()
+        };
+
+        assert!(angles(false).iter().all(|angle| *angle == 90.0));
+        let both = angles(true);
+        assert!(both.iter().all(|angle| angle.abs() == 90.0));
+        assert!(both.contains(&90.0) && both.contains(&-90.0));
+    }
+
+    #[test]
+    fn rotation_range() {
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_rotation_decider(|_, _| true)
+            .with_rotation_range(-45.0, 45.0, 15.0);
+        let layout = wordcloud
+            .generate_layout_from_text(
+                TEXT,
+                WordCloudSize::FromDimensions {
+                    width: 400,
+                    height: 200,
+                },
+            )
+            .unwrap();
+
+        let angles = [-45.0, -30.0, -15.0, 0.0, 15.0, 30.0, 45.0];
+        for word in &layout.words {
+            assert!(angles.contains(&word.angle), "{}", word.angle);
+            assert_eq!(word.rotated, word.angle != 0.0);
+
+            // 旋转后的外接矩形也在画布里
+            let (width, height) =
+                crate::text::rotated_dimensions(word.glyphs.width, word.glyphs.height, word.angle);
+            assert!(word.position.x + width as f32 <= 400.0);
+            assert!(word.position.y + height as f32 <= 200.0);
+        }
+        assert!(layout.words.iter().any(|word| word.angle % 90.0 != 0.0));
+
+        assert_eq!(crate::text::rotated_dimensions(100, 20, 180.0), (100, 20));
+        assert_eq!(crate::text::rotated_dimensions(100, 20, -90.0), (20, 100));
+        assert_eq!(crate::text::rotated_dimensions(100, 20, 45.0), (85, 85));
+    }
+
+    #[test]
+    fn transparent_background() {
+        let red = Rgba([255, 0, 0, 255]);
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_transparent_background()
+            .with_color_closure(move |_, _| red);
+        let image = wordcloud.generate_from_text(
+            TEXT,
+            WordCloudSize::FromDimensions {
+                width: 200,
+                height: 100,
+            },
+            1.0,
+        );
+
+        // 空白处透明，字的边缘按覆盖率半透明，颜色不会混进黑色
+        assert!(image.pixels().any(|px| px.0[3] == 0));
+        assert!(image.pixels().any(|px| px.0[3] == 255));
+        assert!(image.pixels().any(|px| px.0[3] > 0 && px.0[3] < 255));
+        for px in image.pixels().filter(|px| px.0[3] != 0) {
+            assert_eq!(px.0[..3], red.0[..3]);
+        }
+    }
+
+    #[test]
+    fn output_dimensions() {
+        let wordcloud = WordCloud::default().with_rng_seed(1);
+        let layout = wordcloud
+            .generate_layout_from_text(
+                TEXT,
+                WordCloudSize::FromDimensions {
+                    width: 400,
+                    height: 300,
+                },
+            )
+            .unwrap();
+
+        let stretched = WordCloud::default().with_output_dimensions(1920, 1080);
+        let image = stretched.render_layout(&layout, 2.0, |_, _| Rgba([255, 255, 255, 255]));
+        assert_eq!(image.dimensions(), (1920, 1080));
+        assert!(image.pixels().any(|px| px.0 == [255, 255, 255, 255]));
+
+        assert!(matches!(
+            WordCloud::builder().output_dimensions(0, 1080).build(),
+            Err(WordCloudError::InvalidConfig(_))
+        ));
+        // 不经过构建器时渲染的时候报错
+        let empty = WordCloud::default().with_output_dimensions(0, 1080);
+        assert!(matches!(
+            empty.try_render_layout(&layout, 1.0, |_, _| Rgba([255, 255, 255, 255])),
+            Err(WordCloudError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn exclusion_mask() {
+        // 左半边不能放字
+        let exclusion_mask =
+            GrayImage::from_fn(
+                400,
+                200,
+                |x, _| if x < 200 { Luma([255]) } else { Luma([0]) },
+            );
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_exclusion_mask(exclusion_mask);
+        let layout = wordcloud
+            .generate_layout_from_text(
+                TEXT,
+                WordCloudSize::FromDimensions {
+                    width: 400,
+                    height: 200,
+                },
+            )
+            .unwrap();
+
+        assert!(!layout.words.is_empty());
+        for word in &layout.words {
+            assert!(
+                word.position.x >= 200.0,
+                "{} {:?}",
+                word.text,
+                word.position
+            );
+        }
+    }
+
+    #[test]
+    fn fill_empty_space() {
+        let layout_words = |fill_empty_space: bool| {
+            let wordcloud = WordCloud::default()
+                .with_rng_seed(1)
+                .with_tokenizer(
+                    ChineseTokenizer::default()
+                        .with_repeat(true)
+                        .with_max_words(2000),
+                )
+                .with_fill_empty_space(fill_empty_space);
+            let layout = wordcloud
+                .generate_layout_from_text(
+                    TEXT,
+                    WordCloudSize::FromDimensions {
+                        width: 400,
+                        height: 200,
+                    },
+                )
+                .unwrap();
+            layout.words.len()
+        };
+
+        let forced = layout_words(false);
+        let filled = layout_words(true);
+        assert!(filled > 7);
+        assert!(filled < forced);
+    }
+
+    #[test]
+    fn render_word() {
+        let wordcloud = WordCloud::default();
+        let color = Rgba([255, 0, 0, 255]);
+
+        let (image, width, height) = wordcloud.render_word("apple", 32.0, false, color);
+        assert_eq!(image.dimensions(), (width, height));
+        assert!(width > height);
+        assert!(image.pixels().any(|px| *px == color));
+        // 四条边上都有画到的像素
+        assert!((0..height).any(|y| image.get_pixel(0, y).0[3] > 0));
+        assert!((0..height).any(|y| image.get_pixel(width - 1, y).0[3] > 0));
+        assert!((0..width).any(|x| image.get_pixel(x, 0).0[3] > 0));
+        assert!((0..width).any(|x| image.get_pixel(x, height - 1).0[3] > 0));
+
+        let (_, rotated_width, rotated_height) = wordcloud.render_word("apple", 32.0, true, color);
+        assert!(rotated_width.abs_diff(height) <= 1);
+        assert!(rotated_height.abs_diff(width) <= 1);
+
+        assert_eq!(wordcloud.render_word("", 32.0, false, color).1, 0);
+    }
+
+    #[test]
+    fn word_colors() {
+        let brand = Rgba([0, 82, 155, 255]);
+        let warning = Rgba([255, 0, 0, 255]);
+        let fallback = Rgba([128, 128, 128, 255]);
+        let wordcloud = WordCloud::default()
+            .with_rng_seed(1)
+            .with_color_closure(move |_, _| fallback)
+            .with_word_colors(HashMap::from([
+                ("apple".to_string(), brand),
+                ("fig".to_string(), warning),
+            ]));
+
+        let result = wordcloud.generate(
+            TEXT,
+            WordCloudSize::FromDimensions {
+                width: 400,
+                height: 200,
+            },
+            1.0,
+            |word, rng| wordcloud.word_color(word, rng),
+        );
+        for word in &result.words {
+            let expected = match word.text.as_str() {
+                "apple" => brand,
+                "fig" => warning,
+                _ => fallback,
+            };
+            assert_eq!(word.color, expected, "{}", word.text);
+        }
+        assert!(result.words.iter().any(|word| word.text == "fig"));
+        assert!(result.image.pixels().any(|px| *px == brand));
+    }
+
+    #[test]
+    fn progress() {
+        let reported = Arc::new(Mutex::new(vec![]));
+        let wordcloud = WordCloud::default().with_rng_seed(1).with_progress({
+            let reported = reported.clone();
+            move |fraction| reported.lock().unwrap().push(fraction)
+        });
+        let layout = wordcloud
+            .generate_layout_from_text(
+                TEXT,
+                WordCloudSize::FromDimensions {
+                    width: 400,
+                    height: 200,
+                },
+            )
+            .unwrap();
+
+        let reported = reported.lock().unwrap();
+        assert_eq!(reported.first(), Some(&0.0));
+        assert_eq!(reported.last(), Some(&1.0));
+        assert!(reported.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(reported.len() <= layout.words.len() + layout.dropped.len() + 1);
+    }
+
+    #[test]
+    fn resource_limits() {
+        let size = || WordCloudSize::FromDimensions {
+            width: 400,
+            height: 200,
+        };
+        let exceeded = |limits: ResourceLimits| match WordCloud::default()
+            .with_resource_limits(limits)
+            .try_generate_from_text(TEXT, size(), 1.0)
+        {
+            Err(WordCloudError::LimitExceeded { which }) => Some(which),
+            _ => None,
+        };
+
+        assert_eq!(exceeded(ResourceLimits::default()), None);
+        assert_eq!(
+            exceeded(ResourceLimits {
+                max_canvas_bytes: Some(400 * 200 * 4 - 1),
+                ..Default::default()
+            }),
+            Some(Limit::CanvasBytes)
+        );
+        assert_eq!(
+            exceeded(ResourceLimits {
+                max_words: Some(3),
+                ..Default::default()
+            }),
+            Some(Limit::Words)
+        );
+        assert_eq!(
+            exceeded(ResourceLimits {
+                max_generation_time: Some(Duration::ZERO),
+                ..Default::default()
+            }),
+            Some(Limit::GenerationTime)
+        );
+        // 渲染时放大也受限制
+        let wordcloud = WordCloud::default().with_resource_limits(ResourceLimits {
+            max_canvas_bytes: Some(400 * 200 * 4),
+            ..Default::default()
+        });
+        assert!(matches!(
+            wordcloud.try_generate_from_text(TEXT, size(), 2.0),
+            Err(WordCloudError::LimitExceeded {
+                which: Limit::CanvasBytes
+            })
+        ));
+    }
+}